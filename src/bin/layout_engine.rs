@@ -3,13 +3,12 @@ use layout::backends::svg::SVGWriter;
 use layout::core::utils::save_to_file;
 use layout::gv;
 use layout::gv::GraphBuilder;
-use layout::std_shapes::shapes::{Element, ShapeKind};
+use layout::std_shapes::shapes::ShapeKind;
 use layout::topo::layout::VisualGraph;
-use layout::topo::placer::Placer;
 
 fn main() {
     let contents = include_str!("../../spacetraders.dot");
-    let mut parser = gv::DotParser::new(&contents);
+    let mut parser = gv::DotParser::new(contents);
 
     match parser.process() {
         Ok(g) => {
@@ -25,7 +24,6 @@ fn main() {
         },
         Err(err) => {
             parser.print_error();
-            #[cfg(feature = "log")]
             log::error!("Error: {}", err);
         }
     }
@@ -47,7 +45,7 @@ fn generate_svg(graph: &mut VisualGraph) {
         let element = graph.element(node);
         match &element.shape {
             ShapeKind::None => {}
-            ShapeKind::Box(content) => {
+            ShapeKind::Box(_) => {
                 positions.push(pos.middle());
             }
             ShapeKind::Circle(_) => {}
@@ -79,8 +77,8 @@ fn generate_svg(graph: &mut VisualGraph) {
     let x_positions_overview = positions.iter().map(|pos| (pos.x.round() as u32, pos.y.round() as u32)).into_group_map();
     let y_positions_overview = positions.iter().map(|pos| (pos.y.round() as u32, pos.x.round() as u32)).into_group_map();
 
-    let x_positions = x_positions_overview.keys().into_iter().sorted().collect_vec();
-    let y_positions = y_positions_overview.keys().into_iter().sorted().collect_vec();
+    let _x_positions = x_positions_overview.keys().sorted().collect_vec();
+    let _y_positions = y_positions_overview.keys().sorted().collect_vec();
 
     // dbg!(&x_positions_overview);
     // dbg!(&y_positions_overview);
@@ -101,3 +99,49 @@ fn generate_svg(graph: &mut VisualGraph) {
     }
     log::info!("Wrote {}", output_path);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a -> b -> c -> d plus the shortcut a -> c: four ranks deep
+    const FIXTURE: &str = r#"digraph G {
+        rankdir=LR;
+        node [shape=box];
+        "a"; "b"; "c"; "d";
+        "a" -> "b";
+        "b" -> "c";
+        "c" -> "d";
+        "a" -> "c";
+    }"#;
+
+    #[test]
+    fn lays_out_fixture_with_finite_coordinates_and_expected_ranks() {
+        let mut parser = gv::DotParser::new(FIXTURE);
+        let ast = parser.process().expect("fixture should parse");
+        let mut gb = GraphBuilder::new();
+        gb.visit_graph(&ast);
+        let mut graph = gb.get();
+
+        let mut svg = SVGWriter::new();
+        graph.do_it(false, false, false, &mut svg);
+
+        let boxes = graph
+            .iter_nodes()
+            .filter(|&node| matches!(graph.element(node).shape, ShapeKind::Box(_)))
+            .collect_vec();
+        assert_eq!(boxes.len(), 4);
+        for &node in &boxes {
+            let middle = graph.pos(node).middle();
+            assert!(middle.x.is_finite() && middle.y.is_finite(), "node {:?} at {:?}", node, middle);
+        }
+
+        let ranks_with_boxes = graph
+            .dag
+            .ranks()
+            .iter()
+            .filter(|rank| rank.iter().any(|node| boxes.contains(node)))
+            .count();
+        assert_eq!(ranks_with_boxes, 4);
+    }
+}
@@ -2,10 +2,11 @@
 // [dependencies]
 // petgraph = "0.6.2"
 
-use petgraph::graph::{DiGraph, NodeIndex};
-use petgraph::visit::{Topo, EdgeRef};
+use clap::Parser;
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
+use petgraph::visit::{depth_first_search, DfsEvent, EdgeRef, Topo};
 use petgraph::Direction;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 // NodeLayout struct for storing node positioning data
 struct NodeLayout {
@@ -15,16 +16,114 @@ struct NodeLayout {
     height: f64,
 }
 
-// Function to perform layer assignment in a way more similar to Mermaid/Dagre
-fn layered_dag_layout<N, E>(graph: &DiGraph<N, E>) -> HashMap<NodeIndex, NodeLayout> {
+// Effort spent on ordering nodes within their layers to reduce crossings
+struct OrderingOptions {
+    // Barycenter sweeps, alternating top-down and bottom-up
+    max_iterations: usize,
+    // Stop early once a sweep removes less than this share of the crossings before it
+    // (0.0 = never stop early)
+    improvement_epsilon: f64,
+    // Weight of an edge in the barycenters, by index into the laid out graph (cycle breaking
+    // keeps the indices); heavier edges pull their ends closer. Unlisted edges weigh 1.
+    edge_weights: HashMap<EdgeIndex, f64>,
+}
+
+impl Default for OrderingOptions {
+    fn default() -> Self {
+        // One top-down and one bottom-up pass
+        Self {
+            max_iterations: 2,
+            improvement_epsilon: 0.0,
+            edge_weights: HashMap::new(),
+        }
+    }
+}
+
+// What the ordering weighs the edges of the sample graph by (see `OrderingOptions::edge_weights`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+enum WeightBy {
+    // Trade volume, so the heavy hauls from the extraction sites stay short
+    #[default]
+    Volume,
+    // Every edge counts once
+    Uniform,
+}
+
+impl WeightBy {
+    fn weight(self, volume: f64) -> f64 {
+        match self {
+            WeightBy::Volume => volume,
+            WeightBy::Uniform => 1.0,
+        }
+    }
+}
+
+// Command line of the `petgraph` binary
+#[derive(Parser, Debug)]
+#[command(about = "Lay out a sample supply chain with a layered DAG layout on petgraph")]
+struct Args {
+    /// Edge field that weighs edges in crossing minimization: volume, or uniform to count every
+    /// edge once
+    #[arg(long, value_enum, default_value_t = WeightBy::Volume)]
+    weight_by: WeightBy,
+    /// Barycenter sweeps of crossing minimization, alternating top-down and bottom-up
+    #[arg(long, default_value_t = 2)]
+    max_iterations: usize,
+    /// Stop crossing minimization early once a sweep removes less than this share of the
+    /// crossings before it; 0 runs all sweeps
+    #[arg(long, default_value_t = 0.0)]
+    improvement_epsilon: f64,
+}
+
+// Function to perform layer assignment in a way more similar to Mermaid/Dagre.
+// Also returns the edges that were laid out against their direction to break cycles and the
+// number of edge crossings of the final ordering (see `count_crossings`).
+fn layered_dag_layout<N, E>(
+    graph: &DiGraph<N, E>,
+    options: &OrderingOptions,
+) -> (HashMap<NodeIndex, NodeLayout>, Vec<EdgeIndex>, usize) {
+    // 0. Cycle Removal: Reverse back edges so the graph is a DAG
+    let (acyclic, reversed) = reverse_back_edges(graph);
+
     // 1. Layer Assignment: Assign each node to a layer (rank)
-    let node_ranks = assign_layers(graph);
+    let node_ranks = assign_layers(&acyclic);
 
     // 2. Node Ordering: Order nodes within each layer to minimize edge crossings
-    let nodes_by_rank = order_nodes_within_layers(graph, &node_ranks);
+    let nodes_by_rank = order_nodes_within_layers(&acyclic, &node_ranks, options);
+
+    let edges: Vec<(NodeIndex, NodeIndex)> = acyclic.edge_indices().filter_map(|edge| acyclic.edge_endpoints(edge)).collect();
+    let crossings = count_crossings(&nodes_by_rank, &edges);
 
     // 3. Coordinate Assignment: Assign x, y coordinates to nodes
-    assign_coordinates(graph, &nodes_by_rank)
+    (assign_coordinates(&acyclic, &nodes_by_rank), reversed, crossings)
+}
+
+// Copy of the graph's structure (same node indices) in which every edge closing a cycle in a
+// depth-first search points the other way, plus the indices of those edges in `graph`
+fn reverse_back_edges<N, E>(graph: &DiGraph<N, E>) -> (DiGraph<(), ()>, Vec<EdgeIndex>) {
+    let mut back_edges: HashSet<EdgeIndex> = HashSet::new();
+    depth_first_search(graph, graph.node_indices(), |event| {
+        if let DfsEvent::BackEdge(source, target) = event {
+            back_edges.extend(graph.edges_connecting(source, target).map(|edge| edge.id()));
+        }
+    });
+
+    let mut acyclic = DiGraph::with_capacity(graph.node_count(), graph.edge_count());
+    for _ in graph.node_indices() {
+        acyclic.add_node(());
+    }
+    for edge in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(edge).unwrap();
+        if back_edges.contains(&edge) {
+            acyclic.add_edge(target, source, ());
+        } else {
+            acyclic.add_edge(source, target, ());
+        }
+    }
+
+    let mut reversed: Vec<EdgeIndex> = back_edges.into_iter().collect();
+    reversed.sort();
+    (acyclic, reversed)
 }
 
 // Layer assignment using the longest path algorithm
@@ -66,12 +165,15 @@ fn assign_layers<N, E>(graph: &DiGraph<N, E>) -> HashMap<NodeIndex, usize> {
         }
     }
 
-    // Process sink nodes to ensure they're all at the maximum rank
+    // Pull sink nodes (no outgoing edges) down to the maximum rank so the last layer is flush.
+    // Isolated nodes are sources as well and stay on the first rank.
     let max_rank = node_ranks.values().max().cloned().unwrap_or(0);
 
-    for rank in node_ranks.values_mut() {
-        if *rank == usize::MAX {
-            *rank = max_rank + 1;
+    for (node, rank) in node_ranks.iter_mut() {
+        let is_sink = graph.neighbors_directed(*node, Direction::Outgoing).next().is_none();
+        let is_source = graph.neighbors_directed(*node, Direction::Incoming).next().is_none();
+        if is_sink && !is_source {
+            *rank = max_rank;
         }
     }
 
@@ -86,10 +188,79 @@ fn assign_layers<N, E>(graph: &DiGraph<N, E>) -> HashMap<NodeIndex, usize> {
     node_ranks
 }
 
+// Number of pairwise crossings between edges drawn between the ranks of `nodes_by_rank` in
+// that order. Edges spanning several ranks are split into one segment per gap, through dummy
+// positions on the straight line from source to target (in slot units). Edges within a rank,
+// or with an endpoint missing from `nodes_by_rank`, don't count.
+fn count_crossings(nodes_by_rank: &HashMap<usize, Vec<NodeIndex>>, edges: &[(NodeIndex, NodeIndex)]) -> usize {
+    let mut slots: HashMap<NodeIndex, (usize, f64)> = HashMap::new();
+    for (&rank, nodes) in nodes_by_rank {
+        for (slot, &node) in nodes.iter().enumerate() {
+            slots.insert(node, (rank, slot as f64));
+        }
+    }
+
+    // Segments (slot in the upper rank, slot in the lower rank) of every gap, keyed by its upper rank
+    let mut gaps: HashMap<usize, Vec<(f64, f64)>> = HashMap::new();
+    for (source, target) in edges {
+        let (Some(&(source_rank, source_slot)), Some(&(target_rank, target_slot))) = (slots.get(source), slots.get(target)) else {
+            continue;
+        };
+        let ((top_rank, top_slot), (bottom_rank, bottom_slot)) = if source_rank <= target_rank {
+            ((source_rank, source_slot), (target_rank, target_slot))
+        } else {
+            ((target_rank, target_slot), (source_rank, source_slot))
+        };
+        let span = (bottom_rank - top_rank) as f64;
+        let slot_at = |rank: usize| top_slot + (bottom_slot - top_slot) * (rank - top_rank) as f64 / span;
+        for rank in top_rank..bottom_rank {
+            gaps.entry(rank).or_default().push((slot_at(rank), slot_at(rank + 1)));
+        }
+    }
+
+    // Two segments cross when their order flips between the upper and the lower rank; after
+    // sorting by the upper end these are the inversions of the lower ends
+    gaps.into_values()
+        .map(|mut segments| {
+            segments.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+            let mut lower: Vec<f64> = segments.into_iter().map(|(_, lower)| lower).collect();
+            count_inversions(&mut lower)
+        })
+        .sum()
+}
+
+// Pairs i < j with values[i] > values[j], counted while merge sorting `values`
+fn count_inversions(values: &mut [f64]) -> usize {
+    if values.len() < 2 {
+        return 0;
+    }
+    let middle = values.len() / 2;
+    let mut inversions = count_inversions(&mut values[..middle]) + count_inversions(&mut values[middle..]);
+
+    let mut merged = Vec::with_capacity(values.len());
+    let (mut i, mut j) = (0, middle);
+    while i < middle && j < values.len() {
+        if values[j] < values[i] {
+            // values[j] comes before every value left in the first half
+            inversions += middle - i;
+            merged.push(values[j]);
+            j += 1;
+        } else {
+            merged.push(values[i]);
+            i += 1;
+        }
+    }
+    merged.extend_from_slice(&values[i..middle]);
+    merged.extend_from_slice(&values[j..]);
+    values.copy_from_slice(&merged);
+    inversions
+}
+
 // Fixed version of the function with borrowing issues resolved
 fn order_nodes_within_layers<N, E>(
     graph: &DiGraph<N, E>,
     node_ranks: &HashMap<NodeIndex, usize>,
+    options: &OrderingOptions,
 ) -> HashMap<usize, Vec<NodeIndex>> {
     let mut nodes_by_rank: HashMap<usize, Vec<NodeIndex>> = HashMap::new();
 
@@ -101,10 +272,14 @@ fn order_nodes_within_layers<N, E>(
     // Find the maximum rank
     let max_rank = nodes_by_rank.keys().max().cloned().unwrap_or(0);
 
+    let edges: Vec<(NodeIndex, NodeIndex)> = graph.edge_indices().filter_map(|edge| graph.edge_endpoints(edge)).collect();
+    let mut crossings = count_crossings(&nodes_by_rank, &edges);
+
     // Order nodes within each rank to minimize crossings
-    // Two passes: top-down and bottom-up
-    for iter in 0..2 {
-        let rank_range = if iter == 0 {
+    // Alternating passes: top-down and bottom-up
+    for iter in 0..options.max_iterations {
+        let top_down = iter.is_multiple_of(2);
+        let rank_range = if top_down {
             (0..=max_rank).collect::<Vec<_>>() // Top-down
         } else {
             (0..=max_rank).rev().collect::<Vec<_>>() // Bottom-up
@@ -128,51 +303,47 @@ fn order_nodes_within_layers<N, E>(
 
                 for &node in &current_nodes {
                     let mut sum_pos = 0.0;
-                    let mut count = 0;
+                    let mut total_weight = 0.0;
+                    let current_rank = *node_ranks.get(&node).unwrap();
 
-                    // Get connected nodes in adjacent rank
-                    let connected_nodes = if iter == 0 {
+                    // Get connected nodes in adjacent rank, with the weight of the connecting edge
+                    let direction = if top_down {
                         // Top-down: look at predecessors
-                        graph.neighbors_directed(node, Direction::Incoming)
-                            .filter(|&pred| {
-                                if let Some(&pred_rank) = node_ranks.get(&pred) {
-                                    let current_rank = *node_ranks.get(&node).unwrap();
-                                    pred_rank < current_rank
-                                } else {
-                                    false
-                                }
-                            })
-                            .collect::<Vec<_>>()
+                        Direction::Incoming
                     } else {
                         // Bottom-up: look at successors
-                        graph.neighbors_directed(node, Direction::Outgoing)
-                            .filter(|&succ| {
-                                if let Some(&succ_rank) = node_ranks.get(&succ) {
-                                    let current_rank = *node_ranks.get(&node).unwrap();
-                                    succ_rank > current_rank
-                                } else {
-                                    false
-                                }
-                            })
-                            .collect::<Vec<_>>()
+                        Direction::Outgoing
                     };
+                    let connected_nodes = graph
+                        .edges_directed(node, direction)
+                        .map(|edge| {
+                            let other = if top_down { edge.source() } else { edge.target() };
+                            (other, options.edge_weights.get(&edge.id()).copied().unwrap_or(1.0))
+                        })
+                        .filter(|&(other, _)| {
+                            node_ranks.get(&other).is_some_and(|&other_rank| {
+                                if top_down { other_rank < current_rank } else { other_rank > current_rank }
+                            })
+                        })
+                        .collect::<Vec<_>>();
 
                     // Calculate barycenter based on positions of connected nodes
-                    for &connected in &connected_nodes {
+                    for &(connected, weight) in &connected_nodes {
                         if let Some(&connected_rank) = node_ranks.get(&connected) {
                             // Use the copied nodes_by_rank to look up positions
-                            if let Some(nodes_in_rank) = nodes_by_rank_copy.get(&connected_rank) {
-                                if let Some(pos) = nodes_in_rank.iter().position(|&n| n == connected) {
-                                    sum_pos += pos as f64;
-                                    count += 1;
-                                }
+                            if let Some(pos) = nodes_by_rank_copy
+                                .get(&connected_rank)
+                                .and_then(|nodes_in_rank| nodes_in_rank.iter().position(|&n| n == connected))
+                            {
+                                sum_pos += pos as f64 * weight;
+                                total_weight += weight;
                             }
                         }
                     }
 
                     // Calculate final barycenter
-                    let barycenter = if count > 0 {
-                        sum_pos / count as f64
+                    let barycenter = if total_weight > 0.0 {
+                        sum_pos / total_weight
                     } else {
                         // Default position if no connections
                         let node_pos = current_nodes.iter().position(|&n| n == node).unwrap_or(0);
@@ -191,6 +362,13 @@ fn order_nodes_within_layers<N, E>(
                 *nodes = node_barycenters.into_iter().map(|(node, _)| node).collect();
             }
         }
+
+        // Converged once a sweep hardly removes any crossings
+        let before_sweep = std::mem::replace(&mut crossings, count_crossings(&nodes_by_rank, &edges));
+        let removed = before_sweep as f64 - crossings as f64;
+        if options.improvement_epsilon > 0.0 && removed < options.improvement_epsilon * before_sweep as f64 {
+            break;
+        }
     }
 
     nodes_by_rank
@@ -198,7 +376,7 @@ fn order_nodes_within_layers<N, E>(
 
 // Assign x and y coordinates to nodes
 fn assign_coordinates<N, E>(
-    graph: &DiGraph<N, E>,
+    _graph: &DiGraph<N, E>,
     nodes_by_rank: &HashMap<usize, Vec<NodeIndex>>,
 ) -> HashMap<NodeIndex, NodeLayout> {
     let mut layout = HashMap::new();
@@ -208,6 +386,7 @@ fn assign_coordinates<N, E>(
     let vertical_spacing = 150.0;   // Space between ranks
     let node_width = 180.0;
     let node_height = 60.0;
+    let min_gap = 20.0;             // Smallest gap left between neighbours of a rank
 
     // Layout direction (horizontal layout like in Mermaid)
     let is_horizontal = true; // Use LR direction
@@ -246,34 +425,57 @@ fn assign_coordinates<N, E>(
     }
 
     // Adjust node positions for better separation
-    adjust_positions(&mut layout);
+    adjust_positions(&mut layout, nodes_by_rank, is_horizontal, min_gap);
 
     layout
 }
 
-// Helper function to adjust node positions for better aesthetics
-fn adjust_positions(layout: &mut HashMap<NodeIndex, NodeLayout>) {
-    // This is a simplified version that avoids borrowing issues
-    // Clone all the necessary data first
-    let node_positions: Vec<(NodeIndex, (f64, f64))> = layout
-        .iter()
-        .map(|(&node, pos)| (node, (pos.x, pos.y)))
-        .collect();
-
-    // Now update positions without multiple borrows
-    for (node, _) in node_positions {
-        if let Some(node_layout) = layout.get_mut(&node) {
-            // Apply small adjustments if needed
-            // For example, add a small random offset to avoid overlaps
-            // This simplified version just ensures we don't have borrowing errors
-            node_layout.x += (node.index() % 5) as f64 * 0.1; // Small adjustment
+// Separate overlapping nodes of each rank along the cross axis (y for horizontal layouts).
+// Nodes keep their order, each one is pushed away from its predecessor until `min_gap`
+// lies between their boxes, and the rank is shifted back so its mean position is unchanged.
+fn adjust_positions(
+    layout: &mut HashMap<NodeIndex, NodeLayout>,
+    nodes_by_rank: &HashMap<usize, Vec<NodeIndex>>,
+    is_horizontal: bool,
+    min_gap: f64,
+) {
+    let cross = |node: &NodeLayout| if is_horizontal { node.y } else { node.x };
+    // Extent of a box along the cross axis
+    let extent = |node: &NodeLayout| if is_horizontal { node.height } else { node.width };
+
+    for nodes in nodes_by_rank.values() {
+        let mut rank: Vec<(NodeIndex, f64, f64)> = nodes
+            .iter()
+            .filter_map(|node| layout.get(node).map(|pos| (*node, cross(pos), extent(pos))))
+            .collect();
+        rank.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+        let mut separated: Vec<f64> = Vec::with_capacity(rank.len());
+        for (i, &(_, position, extent)) in rank.iter().enumerate() {
+            let position = match i.checked_sub(1) {
+                Some(prev) => position.max(separated[prev] + (rank[prev].2 + extent) / 2.0 + min_gap),
+                None => position,
+            };
+            separated.push(position);
+        }
+
+        let shift = rank.iter().zip(&separated).map(|(node, new)| new - node.1).sum::<f64>() / rank.len().max(1) as f64;
+        for ((node, _, _), position) in rank.iter().zip(separated) {
+            let pos = layout.get_mut(node).expect("collected from the layout");
+            if is_horizontal {
+                pos.y = position - shift;
+            } else {
+                pos.x = position - shift;
+            }
         }
     }
 }
 
 fn main() {
+    let args = Args::parse();
+
     // Create a directed graph for your example
-    let mut graph = DiGraph::<&str, &str>::new();
+    let mut graph = DiGraph::<&str, f64>::new();
 
     // Create a mapping from node names to indices for easier reference
     let mut node_map = HashMap::new();
@@ -312,53 +514,63 @@ fn main() {
         node_map.insert(node_name, node_idx);
     }
 
-    // Add edges (connections between nodes)
+    // Add edges (connections between nodes) with their trade volume: raw materials hauled
+    // from the extraction sites outweigh the refined goods
     let edges = vec![
-        ("iron_at_x_1_ad_75_h_51", "machinery_at_x_1_ad_75_e_46"),
-        ("liquid_hydrogen_at_x_1_ad_75_c_41", "plastics_at_x_1_ad_75_g_50"),
-        ("silicon_crystals_at_x_1_ad_75_h_53", "electronics_at_x_1_ad_75_f_49"),
-        ("copper_at_x_1_ad_75_h_51", "electronics_at_x_1_ad_75_f_49"),
-        ("aluminum_at_x_1_ad_75_h_51", "equipment_at_x_1_ad_75_k_81"),
-        ("plastics_at_x_1_ad_75_g_50", "equipment_at_x_1_ad_75_k_81"),
-        ("aluminum_at_x_1_ad_75_h_51", "ship_plating_at_x_1_ad_75_d_44"),
-        ("machinery_at_x_1_ad_75_e_46", "ship_plating_at_x_1_ad_75_d_44"),
-        ("iron_at_x_1_ad_75_h_51", "fab_mats_at_x_1_ad_75_f_49"),
-        ("quartz_sand_at_x_1_ad_75_h_53", "fab_mats_at_x_1_ad_75_f_49"),
-        ("silicon_crystals_at_x_1_ad_75_h_53", "microprocessors_at_x_1_ad_75_a_3"),
-        ("copper_at_x_1_ad_75_h_51", "microprocessors_at_x_1_ad_75_a_3"),
-        ("liquid_nitrogen_at_x_1_ad_75_c_41", "fertilizers_at_x_1_ad_75_g_50"),
-        ("equipment_at_x_1_ad_75_k_81", "ship_parts_at_x_1_ad_75_d_43"),
-        ("electronics_at_x_1_ad_75_f_49", "ship_parts_at_x_1_ad_75_d_43"),
-        ("fertilizers_at_x_1_ad_75_g_50", "fabrics_at_x_1_ad_75_e_46"),
-        ("electronics_at_x_1_ad_75_f_49", "advanced_circuitry_at_x_1_ad_75_d_44"),
-        ("microprocessors_at_x_1_ad_75_a_3", "advanced_circuitry_at_x_1_ad_75_d_44"),
-        ("fabrics_at_x_1_ad_75_e_46", "clothing_at_x_1_ad_75_k_81"),
-        ("quartz_sand_at_x_1_ad_75_xd_5_a", "quartz_sand_at_x_1_ad_75_h_53"),
-        ("liquid_nitrogen_at_x_1_ad_75_c_40", "liquid_nitrogen_at_x_1_ad_75_c_41"),
-        ("copper_ore_at_x_1_ad_75_xd_5_a", "copper_at_x_1_ad_75_h_51"),
-        ("liquid_hydrogen_at_x_1_ad_75_c_40", "liquid_hydrogen_at_x_1_ad_75_c_41"),
-        ("iron_ore_at_x_1_ad_75_xd_5_a", "iron_at_x_1_ad_75_h_51"),
-        ("aluminum_ore_at_x_1_ad_75_xd_5_a", "aluminum_at_x_1_ad_75_h_51"),
-        ("silicon_crystals_at_x_1_ad_75_xd_5_a", "silicon_crystals_at_x_1_ad_75_h_53"),
+        ("iron_at_x_1_ad_75_h_51", "machinery_at_x_1_ad_75_e_46", 1.0),
+        ("liquid_hydrogen_at_x_1_ad_75_c_41", "plastics_at_x_1_ad_75_g_50", 1.0),
+        ("silicon_crystals_at_x_1_ad_75_h_53", "electronics_at_x_1_ad_75_f_49", 1.0),
+        ("copper_at_x_1_ad_75_h_51", "electronics_at_x_1_ad_75_f_49", 1.0),
+        ("aluminum_at_x_1_ad_75_h_51", "equipment_at_x_1_ad_75_k_81", 1.0),
+        ("plastics_at_x_1_ad_75_g_50", "equipment_at_x_1_ad_75_k_81", 1.0),
+        ("aluminum_at_x_1_ad_75_h_51", "ship_plating_at_x_1_ad_75_d_44", 1.0),
+        ("machinery_at_x_1_ad_75_e_46", "ship_plating_at_x_1_ad_75_d_44", 1.0),
+        ("iron_at_x_1_ad_75_h_51", "fab_mats_at_x_1_ad_75_f_49", 1.0),
+        ("quartz_sand_at_x_1_ad_75_h_53", "fab_mats_at_x_1_ad_75_f_49", 1.0),
+        ("silicon_crystals_at_x_1_ad_75_h_53", "microprocessors_at_x_1_ad_75_a_3", 1.0),
+        ("copper_at_x_1_ad_75_h_51", "microprocessors_at_x_1_ad_75_a_3", 1.0),
+        ("liquid_nitrogen_at_x_1_ad_75_c_41", "fertilizers_at_x_1_ad_75_g_50", 1.0),
+        ("equipment_at_x_1_ad_75_k_81", "ship_parts_at_x_1_ad_75_d_43", 1.0),
+        ("electronics_at_x_1_ad_75_f_49", "ship_parts_at_x_1_ad_75_d_43", 1.0),
+        ("fertilizers_at_x_1_ad_75_g_50", "fabrics_at_x_1_ad_75_e_46", 1.0),
+        ("electronics_at_x_1_ad_75_f_49", "advanced_circuitry_at_x_1_ad_75_d_44", 1.0),
+        ("microprocessors_at_x_1_ad_75_a_3", "advanced_circuitry_at_x_1_ad_75_d_44", 1.0),
+        ("fabrics_at_x_1_ad_75_e_46", "clothing_at_x_1_ad_75_k_81", 1.0),
+        ("quartz_sand_at_x_1_ad_75_xd_5_a", "quartz_sand_at_x_1_ad_75_h_53", 3.0),
+        ("liquid_nitrogen_at_x_1_ad_75_c_40", "liquid_nitrogen_at_x_1_ad_75_c_41", 3.0),
+        ("copper_ore_at_x_1_ad_75_xd_5_a", "copper_at_x_1_ad_75_h_51", 3.0),
+        ("liquid_hydrogen_at_x_1_ad_75_c_40", "liquid_hydrogen_at_x_1_ad_75_c_41", 3.0),
+        ("iron_ore_at_x_1_ad_75_xd_5_a", "iron_at_x_1_ad_75_h_51", 3.0),
+        ("aluminum_ore_at_x_1_ad_75_xd_5_a", "aluminum_at_x_1_ad_75_h_51", 3.0),
+        ("silicon_crystals_at_x_1_ad_75_xd_5_a", "silicon_crystals_at_x_1_ad_75_h_53", 3.0),
 
 
     ];
 
-    for (source, target) in &edges {
+    for &(source, target, volume) in &edges {
         if let (Some(&source_idx), Some(&target_idx)) = (node_map.get(source), node_map.get(target)) {
-            graph.add_edge(source_idx, target_idx, "");
+            graph.add_edge(source_idx, target_idx, volume);
         }
     }
 
-    // Apply the layered DAG layout algorithm
-    let layout = layered_dag_layout(&graph);
+    // Apply the layered DAG layout algorithm, weighing the edges as asked
+    let options = OrderingOptions {
+        max_iterations: args.max_iterations,
+        improvement_epsilon: args.improvement_epsilon,
+        edge_weights: graph.edge_indices().map(|edge| (edge, args.weight_by.weight(graph[edge]))).collect(),
+    };
+    let (layout, reversed, crossings) = layered_dag_layout(&graph, &options);
 
     // Print the resulting layout
     println!("Node positions after layered DAG layout:");
     for (node_idx, pos) in &layout {
         let node_name = graph[*node_idx];
-        println!("Node {}: x={:.1}, y={:.1}", node_name, pos.x, pos.y);
+        println!(
+            "Node {}: x={:.1}, y={:.1}, size={:.0}x{:.0}",
+            node_name, pos.x, pos.y, pos.width, pos.height
+        );
     }
+    println!("Edge crossings: {}", crossings);
 
     // Output a simple DOT format for visualization
     println!("\nDOT format for visualization:");
@@ -377,8 +589,157 @@ fn main() {
         let (source, target) = graph.edge_endpoints(edge).unwrap();
         let source_name = graph[source];
         let target_name = graph[target];
-        println!("  \"{}\" -> \"{}\";", source_name, target_name);
+        // Edges closing a cycle run against the layout direction
+        let style = if reversed.contains(&edge) { " [style=dashed]" } else { "" };
+        println!("  \"{}\" -> \"{}\"{};", source_name, target_name, style);
     }
 
     println!("}}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a -> b -> c -> d plus the shortcut a -> c: four ranks deep
+    fn fixture() -> DiGraph<&'static str, &'static str> {
+        let mut graph = DiGraph::new();
+        let a = graph.add_node("a");
+        let b = graph.add_node("b");
+        let c = graph.add_node("c");
+        let d = graph.add_node("d");
+        graph.extend_with_edges([(a, b, ""), (b, c, ""), (c, d, ""), (a, c, "")]);
+        graph
+    }
+
+    #[test]
+    fn lays_out_fixture_with_finite_coordinates_and_expected_ranks() {
+        let graph = fixture();
+
+        let (layout, reversed, _) = layered_dag_layout(&graph, &OrderingOptions::default());
+        assert!(reversed.is_empty());
+        assert_eq!(layout.len(), graph.node_count());
+        for (node, pos) in &layout {
+            assert!(pos.x.is_finite() && pos.y.is_finite(), "node {} at ({}, {})", graph[*node], pos.x, pos.y);
+        }
+
+        let ranks = assign_layers(&graph);
+        let rank_count = ranks.values().max().map_or(0, |max| max + 1);
+        assert_eq!(rank_count, 4);
+    }
+
+    #[test]
+    fn aligns_sinks_on_the_last_rank() {
+        // e hangs off a and would otherwise sit on rank 1 of the four
+        let mut graph = fixture();
+        let a = graph.node_indices().find(|&node| graph[node] == "a").unwrap();
+        let d = graph.node_indices().find(|&node| graph[node] == "d").unwrap();
+        let e = graph.add_node("e");
+        graph.add_edge(a, e, "");
+
+        let ranks = assign_layers(&graph);
+        assert_eq!(ranks[&e], 3);
+        assert_eq!(ranks[&e], ranks[&d]);
+        assert_eq!(ranks[&a], 0);
+    }
+
+    #[test]
+    fn counts_crossings_including_long_edges() {
+        let [a, b, c, d, e, f] = [0, 1, 2, 3, 4, 5].map(NodeIndex::new);
+        // a b / c d / e f: a -> d and b -> c cross, the long edge a -> f crosses b -> c only
+        let nodes_by_rank = HashMap::from([(0, vec![a, b]), (1, vec![c, d]), (2, vec![e, f])]);
+        assert_eq!(count_crossings(&nodes_by_rank, &[(a, d), (b, c)]), 1);
+        assert_eq!(count_crossings(&nodes_by_rank, &[(a, f), (b, c)]), 1);
+        assert_eq!(count_crossings(&nodes_by_rank, &[(a, c), (b, d), (c, e), (d, f)]), 0);
+        // Edges sharing an endpoint don't cross
+        assert_eq!(count_crossings(&nodes_by_rank, &[(a, c), (a, d), (b, d)]), 0);
+    }
+
+    #[test]
+    fn ordering_never_increases_crossings() {
+        // Two sources each feeding the middle of the other's chain
+        let mut graph = DiGraph::<&str, &str>::new();
+        let [a, b, c, d, e, f] = ["a", "b", "c", "d", "e", "f"].map(|name| graph.add_node(name));
+        graph.extend_with_edges([(a, c, ""), (b, d, ""), (a, d, ""), (c, e, ""), (d, f, ""), (b, e, ""), (a, f, "")]);
+        let edges: Vec<(NodeIndex, NodeIndex)> = graph.edge_indices().filter_map(|edge| graph.edge_endpoints(edge)).collect();
+
+        let ranks = assign_layers(&graph);
+        let initial = order_nodes_within_layers(&graph, &ranks, &OrderingOptions { max_iterations: 0, ..OrderingOptions::default() });
+        let ordered = order_nodes_within_layers(&graph, &ranks, &OrderingOptions::default());
+        assert!(count_crossings(&ordered, &edges) <= count_crossings(&initial, &edges));
+    }
+
+    #[test]
+    fn stops_once_a_sweep_removes_too_few_crossings() {
+        let mut graph = DiGraph::<&str, &str>::new();
+        let [a, b, c, d, e, f] = ["a", "b", "c", "d", "e", "f"].map(|name| graph.add_node(name));
+        graph.extend_with_edges([(a, c, ""), (b, d, ""), (a, d, ""), (c, e, ""), (d, f, ""), (b, e, ""), (a, f, "")]);
+        let edges: Vec<(NodeIndex, NodeIndex)> = graph.edge_indices().filter_map(|edge| graph.edge_endpoints(edge)).collect();
+        let ranks = assign_layers(&graph);
+
+        // Demanding every sweep to remove all remaining crossings ends after the first one
+        let sweeps = |max_iterations: usize, improvement_epsilon: f64| {
+            let options = OrderingOptions { max_iterations, improvement_epsilon, ..OrderingOptions::default() };
+            order_nodes_within_layers(&graph, &ranks, &options)
+        };
+        let first = sweeps(1, 0.0);
+        assert!(count_crossings(&first, &edges) > 0);
+        assert_eq!(sweeps(8, 1.0), first);
+    }
+
+    #[test]
+    fn ranks_every_node_of_a_cycle() {
+        let mut graph = DiGraph::new();
+        let a = graph.add_node("a");
+        let b = graph.add_node("b");
+        let c = graph.add_node("c");
+        graph.extend_with_edges([(a, b, ""), (b, c, ""), (c, a, "")]);
+
+        let (layout, reversed, _) = layered_dag_layout(&graph, &OrderingOptions::default());
+        assert_eq!(layout.len(), 3);
+        assert_eq!(reversed.len(), 1);
+
+        let (acyclic, _) = reverse_back_edges(&graph);
+        assert_eq!(assign_layers(&acyclic).len(), 3);
+    }
+
+    #[test]
+    fn heavy_edges_pull_their_ends_to_the_same_side() {
+        // x and y both hang off p and q, x mostly off q
+        let mut graph = DiGraph::<&str, &str>::new();
+        let [p, q, x, y] = ["p", "q", "x", "y"].map(|name| graph.add_node(name));
+        graph.extend_with_edges([(p, x, ""), (q, x, ""), (p, y, ""), (q, y, "")]);
+        let heavy = graph.find_edge(q, x).unwrap();
+
+        let ranks = assign_layers(&graph);
+        let options = OrderingOptions {
+            max_iterations: 1,
+            edge_weights: HashMap::from([(heavy, 9.0)]),
+            ..OrderingOptions::default()
+        };
+        let ordered = order_nodes_within_layers(&graph, &ranks, &options);
+        let slot = |node: NodeIndex| ordered[&ranks[&node]].iter().position(|&n| n == node).unwrap();
+        assert_eq!(slot(x), slot(q));
+    }
+
+    #[test]
+    fn adjust_positions_separates_overlapping_nodes() {
+        let (a, b) = (NodeIndex::new(0), NodeIndex::new(1));
+        let node = |y: f64| NodeLayout { x: 0.0, y, width: 180.0, height: 60.0 };
+        let mut layout = HashMap::from([(a, node(0.0)), (b, node(10.0))]);
+        let nodes_by_rank = HashMap::from([(0, vec![a, b])]);
+
+        // Stacked along y, so the heights are what must not overlap
+        let min_gap = 20.0;
+        adjust_positions(&mut layout, &nodes_by_rank, true, min_gap);
+        let gap = layout[&b].y - layout[&a].y;
+        assert!((gap - (60.0 + min_gap)).abs() < 1e-9, "{} and {}", layout[&a].y, layout[&b].y);
+        // Centered where the pair was
+        assert!((layout[&a].y + layout[&b].y - 10.0).abs() < 1e-9);
+
+        // Side by side along x, the widths
+        let mut layout = HashMap::from([(a, node(0.0)), (b, NodeLayout { x: 10.0, ..node(0.0) })]);
+        adjust_positions(&mut layout, &nodes_by_rank, false, min_gap);
+        assert!((layout[&b].x - layout[&a].x - (180.0 + min_gap)).abs() < 1e-9);
+    }
+}
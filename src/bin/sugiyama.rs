@@ -1,1073 +1,630 @@
-use std::borrow::Cow;
-use petgraph::stable_graph::{NodeIndex, StableDiGraph};
-use rust_sugiyama::configure::{CrossingMinimization, RankingType};
-use rust_sugiyama::{configure::Config, from_graph};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fmt;
-use rand::Rng;
-use strum::{Display, EnumIter, IntoEnumIterator};
-
-// Your existing types (assuming these are defined elsewhere)
-type TradeGoodSymbol = String;
-type WaypointSymbol = String;
-type TradeGoodType = String;
-
-
-#[derive(
-    Serialize, Deserialize, Clone, Debug, Display, EnumIter, Eq, PartialEq, Hash, Ord, PartialOrd,
-)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-pub enum SupplyLevel {
-    Abundant,
-    High,
-    Moderate,
-    Limited,
-    Scarce,
-}
-
-#[derive(
-    Serialize, Deserialize, Clone, Debug, Display, EnumIter, Eq, PartialEq, Hash, Ord, PartialOrd,
-)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-pub enum ActivityLevel {
-    Weak,
-    Growing,
-    Strong,
-    Restricted,
-}
-
-type Point = (f64, f64);
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct TechNode {
-    id: String,
-    name: TradeGoodSymbol,
-    waypoint_symbol: WaypointSymbol,
-    waypoint_type: TradeGoodType,
-    supply: SupplyLevel,
-    activity: ActivityLevel,
-    cost: u32,
-    volume: u32,
-    width: f64,
-    height: f64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    x: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    y: Option<f64>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct TechEdge {
-    source: String,
-    target: String,
-    cost: u32,
-    activity: ActivityLevel,
-    volume: u32,
-    supply: SupplyLevel,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    points: Option<Vec<Point>>,
-    // Add a curve factor for each edge
-    #[serde(skip_serializing_if = "Option::is_none")]
-    curve_factor: Option<f64>,
-    // New fields
-    #[serde(skip_serializing_if = "Option::is_none")]
-    distance: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    profit: Option<i32>,  // Can be negative
-}
-
-// ColorString newtype using Cow for efficiency
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct ColorString(pub Cow<'static, str>);
-
-impl ColorString {
-    pub fn new(color: &str) -> Self {
-        Self(Cow::Owned(color.to_string()))
-    }
-
-    pub fn as_str(&self) -> &str {
-        &self.0
-    }
-}
-
-impl From<String> for ColorString {
-    fn from(value: String) -> Self {
-        Self(Cow::Owned(value))
-    }
-}
-
-impl From<&'static str> for ColorString {
-    fn from(value: &'static str) -> Self {
-        Self(Cow::Borrowed(value))
-    }
+use hierarchical_petgraph_layout::supply_chain::{
+    ActivityLevel, Corner, CornerStyle, DepthMetric, DuplicateNames, Frame, LabelMergeFn,
+    LabelPlacement, LayoutOptions, LayoutResult, LevelFilter, LineCap, LineJoin, NodeGroup,
+    NodeSizing, RankConstraints, Rect, RenderConfig, SecondaryOrder, SecondaryOrderMode, Side,
+    SupplyLevel, SvgSize, TechNode, Theme, ThemeName, TitleEmphasis, UnpositionedEdges,
+    VerticalAlign, WeightBy, ZoomMetadata, ancestors, apply_node_sizes, build_component_layouts,
+    build_supply_chain_layout, check_label_fit, create_full_supply_chain, descendants, diff_layouts,
+    fit_nodes_to_text, load_graph, output_html, output_svg, output_svg_with_metrics,
+    parse_scale_bar, render_png, save_graph, sides_leaving_by, sum_parallel_edges, supply_badges_at,
+    to_dot, to_geojson, to_regions_json, transpose_layout,
+};
+use hierarchical_petgraph_layout::Orientation;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use clap::Parser;
+
+// Command line of the `sugiyama` binary
+#[derive(Parser, Debug)]
+#[command(about = "Lay out a supply chain with rust-sugiyama and render it as SVG")]
+struct Args {
+    /// Graph to lay out, a JSON document as written by `save_graph`. Without it a sample
+    /// supply chain with random values is generated.
+    #[arg(long)]
+    input: Option<PathBuf>,
+    /// File the drawing is written to [default: sugiyama.svg, .png, .html, .dot or .geojson]
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Write the drawing as SVG, as a PNG rasterized from the SVG, as an HTML page panning and
+    /// zooming the SVG, as a GraphViz graph without the layout, or as GeoJSON features
+    #[arg(long, value_enum, default_value_t = OutputFormat::Svg)]
+    format: OutputFormat,
+    /// Also write the graph with its layout as positions and routes to redraw it from, in the
+    /// format of `--input`
+    #[arg(long)]
+    save_graph: Option<PathBuf>,
+    /// Also write the rectangles of the nodes and edges in SVG coordinates as JSON, e.g. to lay
+    /// tooltips over the drawing
+    #[arg(long)]
+    regions: Option<PathBuf>,
+    /// Graph written by `--save-graph` earlier, to report how far each node moved since
+    #[arg(long)]
+    previous: Option<PathBuf>,
+    /// Pixels per SVG unit of the PNG, 1.0 rasterizes at 96 DPI
+    #[arg(long, default_value_t = 1.0)]
+    png_scale: f32,
+    /// CSS font stack of all text, e.g. "DejaVu Sans" where Arial isn't installed for PNGs
+    /// [default: Arial, Helvetica, sans-serif]
+    #[arg(long)]
+    font_family: Option<String>,
+    /// Prefix of the ids defined in the SVG, to inline several diagrams into one page
+    #[arg(long, default_value = "")]
+    id_prefix: String,
+    /// Emphasize the nodes whose supply is at or below this level, dimming the others
+    #[arg(long, value_enum)]
+    supply_at_or_below: Option<SupplyLevel>,
+    /// Emphasize the nodes whose supply is at or above this level, dimming the others
+    #[arg(long, value_enum, conflicts_with = "supply_at_or_below")]
+    supply_at_or_above: Option<SupplyLevel>,
+    /// Emphasize the nodes whose activity is at or below this level (restricted being the
+    /// lowest), dimming the others. Combined with a supply level, a node matching either one
+    /// is emphasized.
+    #[arg(long, value_enum)]
+    activity_at_or_below: Option<ActivityLevel>,
+    /// Emphasize the nodes whose activity is at or above this level, dimming the others
+    #[arg(long, value_enum, conflicts_with = "activity_at_or_below")]
+    activity_at_or_above: Option<ActivityLevel>,
+    /// Emphasize the node with this id and everything up- and downstream of it, dimming the others
+    #[arg(long)]
+    focus: Option<String>,
+    /// Colors of the drawing
+    #[arg(long, value_enum, default_value_t = ThemeName::Dark)]
+    theme: ThemeName,
+    /// Fill nodes along a color ramp by their shortest (min) or longest (max) distance from the
+    /// sources instead of by type
+    #[arg(long, value_enum)]
+    depth_coloring: Option<DepthMetric>,
+    /// Mark scarce supply with a dot and abundant supply with a star on this corner of the nodes
+    #[arg(long, value_enum)]
+    badges: Option<Corner>,
+    /// Corners of the node boxes: sharp, rounded:<radius> or cut:<length>
+    #[arg(long, default_value = "rounded:5")]
+    corners: CornerStyle,
+    /// Size of the first text line of a node: standard, off (as large as the others) or a factor
+    #[arg(long, default_value = "standard")]
+    title_emphasis: TitleEmphasis,
+    /// How the titles of nodes sharing a name are told apart
+    #[arg(long, value_enum, default_value_t = DuplicateNames::Keep)]
+    duplicate_names: DuplicateNames,
+    /// Placement of the text block within the node box
+    #[arg(long, value_enum, default_value_t = VerticalAlign::Top)]
+    node_text_align: VerticalAlign,
+    /// Where along its edge a label is placed
+    #[arg(long, value_enum, default_value_t = LabelPlacement::Target)]
+    label_placement: LabelPlacement,
+    /// Show one label for parallel edges between the same two nodes, totalling their volume
+    /// and profit and averaging their cost
+    #[arg(long)]
+    merge_parallel_labels: bool,
+    /// Tint the background of the ranks in alternating shades
+    #[arg(long)]
+    rank_bands: bool,
+    /// Draw a dotted line through every rank, e.g. to check that the ranks are evenly spaced
+    #[arg(long)]
+    rank_guides: bool,
+    /// Wrap the nodes of every rank and all edges in groups with ids, e.g. to style or animate
+    /// them from a page
+    #[arg(long)]
+    group_ranks: bool,
+    /// Draw a border around the whole diagram, inside the margin of the canvas
+    #[arg(long)]
+    frame: bool,
+    /// Drop corners of right-angled edge paths closer than this to the simplified path
+    #[arg(long)]
+    simplify_tolerance: Option<f64>,
+    /// Write edge paths with relative commands, which makes the SVG smaller
+    #[arg(long)]
+    relative_paths: bool,
+    /// Connect edge labels that sit away from their edge back to it with a dashed line
+    #[arg(long)]
+    label_leaders: bool,
+    /// Explain the supply, activity and node type colors below the drawing
+    #[arg(long)]
+    legend: bool,
+    /// Draw nodes the layout left without a position as ghosts in a row below the drawing
+    /// instead of leaving them out
+    #[arg(long)]
+    park_unpositioned: bool,
+    /// Mark the SVG up for screen readers: an image role with a title and a description
+    #[arg(long)]
+    accessibility: bool,
+    /// Title screen readers announce for the drawing with `--accessibility`
+    /// [default: Supply chain diagram]
+    #[arg(long, requires = "accessibility")]
+    title: Option<String>,
+    /// Ends of the edge paths
+    #[arg(long, value_enum, default_value_t = LineCap::Round)]
+    line_cap: LineCap,
+    /// Corners of the edge paths
+    #[arg(long, value_enum, default_value_t = LineJoin::Round)]
+    line_join: LineJoin,
+    /// Zoom range for interactive viewers embedded into the SVG: off, auto or
+    /// <min>:<max>:<natural scale>
+    #[arg(long, default_value = "off")]
+    zoom_hints: ZoomMetadata,
+    /// Draw a bar this many layout units long below the drawing, labeled with the name, e.g.
+    /// 100:distance units
+    #[arg(long, value_parser = parse_scale_bar, value_name = "UNITS:NAME")]
+    scale_bar: Option<(f64, String)>,
+    /// Dimensions of the SVG: responsive (the width of its container), natural or <width>x<height>
+    #[arg(long, default_value = "responsive")]
+    svg_size: SvgSize,
+    /// Largest canvas width in pixels; wider drawings are scaled down as a whole
+    #[arg(long)]
+    max_width: Option<f64>,
+    /// Largest canvas height in pixels; taller drawings are scaled down as a whole
+    #[arg(long)]
+    max_height: Option<f64>,
+    /// Direction the ranks follow: top-down, left-right, right-left or bottom-up
+    #[arg(long, default_value_t = Orientation::LeftRight)]
+    orientation: Orientation,
+    /// Factor applied to the x coordinates of the layout
+    #[arg(long, default_value_t = 1.5)]
+    x_scale: f64,
+    /// Factor applied to the y coordinates of the layout
+    #[arg(long, default_value_t = 0.75)]
+    y_scale: f64,
+    /// Distance between neighbouring nodes and ranks before scaling
+    /// [default: largest node extent plus 100]
+    #[arg(long)]
+    vertex_spacing: Option<usize>,
+    /// Ranks an edge spans at least
+    #[arg(long, default_value_t = 1)]
+    min_length: u32,
+    /// Keep the node sizes of `--input` instead of fitting the boxes to their text, reporting
+    /// the nodes whose text overflows
+    #[arg(long)]
+    fixed_node_sizes: bool,
+    /// Break node text wider than the box at spaces and underscores, making the box taller
+    /// instead of wider
+    #[arg(long)]
+    wrap_node_text: bool,
+    /// Scale the node boxes by this field, the largest value by `--max-node-scale`
+    #[arg(long, value_enum)]
+    size_by: Option<NodeField>,
+    /// Factor `--size-by` scales the box of the node with the largest value by
+    #[arg(long, default_value_t = 2.0)]
+    max_node_scale: f64,
+    /// Edge field that weighs edges in ranking and an extra crossing refinement: cost, volume,
+    /// distance, profit, or uniform to count every edge once
+    #[arg(long)]
+    weight_by: Option<WeightBy>,
+    /// Skip the transpose refinement of the engine's crossing minimization, e.g. for previews
+    #[arg(long)]
+    quick_crossing_reduction: bool,
+    /// Cut edges spanning several ranks straight across instead of routing them between the
+    /// nodes in between, saving a second engine run
+    #[arg(long)]
+    straight_long_edges: bool,
+    /// Bend edges around the boxes of nodes they would otherwise run through
+    #[arg(long)]
+    avoid_nodes: bool,
+    /// Attach edges to the node faces toward the neighbouring ranks, spreading the edges that
+    /// share a face along it
+    #[arg(long)]
+    ports: bool,
+    /// Side every edge leaves its source by, entering its target by the opposite one, e.g.
+    /// bottom for flows drawn beneath left to right ranks
+    #[arg(long, value_enum)]
+    exit_side: Option<Side>,
+    /// Area in the coordinates of the SVG that edges are routed around, e.g. where a page puts
+    /// a title block over the drawing; may be given several times
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT")]
+    obstacle: Vec<Rect>,
+    /// Edges whose ends the layout didn't position: routed to the coordinates the nodes of
+    /// `--input` come with, or only reported
+    #[arg(long, value_enum, default_value_t = UnpositionedEdges::UseLastKnown)]
+    unpositioned_edges: UnpositionedEdges,
+    /// Sweeps of the crossing refinement after the engine, which runs with `--weight-by` or
+    /// either of these two options [default: until no swap helps]
+    #[arg(long)]
+    max_iterations: Option<usize>,
+    /// Stop the crossing refinement after the engine once a sweep removes less than this share
+    /// of the crossings before it; 0 never stops early
+    #[arg(long, default_value_t = 0.0)]
+    improvement_epsilon: f64,
+    /// Start crossing minimization from the nodes of each rank ordered by this field, smaller
+    /// values first
+    #[arg(long, value_enum)]
+    initial_order: Option<NodeField>,
+    /// Reorder the nodes of each rank by this field after crossing minimization
+    #[arg(long, value_enum)]
+    secondary_order: Option<NodeField>,
+    /// How `--secondary-order` reorders: swapping neighbours only where that adds no crossings
+    /// (tie-break), or keeping nodes of equal values together (group)
+    #[arg(long, value_enum, default_value_t = SecondaryOrderMode::TieBreak)]
+    secondary_order_mode: SecondaryOrderMode,
+    /// Line up the inner nodes of simple chains (one edge in, one out) between the chain's ends
+    #[arg(long)]
+    straighten_chains: bool,
+    /// Pull nodes across their rank toward the median of their neighbours to shorten edges,
+    /// weighing edges by `--weight-by` if given
+    #[arg(long)]
+    minimize_edge_length: bool,
+    /// Drop edges implied by a longer path before the layout, e.g. A→C next to A→B→C
+    #[arg(long)]
+    transitive_reduction: bool,
+    /// Waypoint types the ranks must follow regardless of the edges, e.g.
+    /// RAW_MATERIAL,REFINED,INDUSTRIAL; fails if the edges contradict the order
+    #[arg(long, value_delimiter = ',', value_name = "TYPES")]
+    rank_order: Vec<String>,
+    /// Draw the goods traded at this waypoint as one summary node, rerouting and merging their
+    /// edges; may be given several times
+    #[arg(long, value_name = "WAYPOINT")]
+    collapse_waypoint: Vec<String>,
+    /// Lay out every weakly connected component on its own and place them side by side
+    #[arg(long)]
+    split_components: bool,
+    /// Extra gap between disconnected components
+    #[arg(long, default_value_t = 0.0)]
+    component_gutter: f64,
+    /// Swap x and y of the computed layout, e.g. turning left-right into top-down without laying
+    /// it out again
+    #[arg(long)]
+    transpose: bool,
+    /// Print how long the layout phases and rendering took, and counts of the layout
+    #[arg(long)]
+    metrics: bool,
+    /// Seed of the random values of the sample graph, to reproduce an earlier run
+    /// [default: random]
+    #[arg(long)]
+    seed: Option<u64>,
 }
 
-// Display implementation for easy formatting
-impl fmt::Display for ColorString {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Svg,
+    Png,
+    Html,
+    Dot,
+    Geojson,
 }
 
-impl TechNode {
-    pub(crate) fn supply_color(&self) -> ColorString {
-        get_supply_color(&self.supply)
-    }
-
-    pub(crate) fn activity_color(&self) -> ColorString {
-        get_activity_color(&self.activity)
+impl OutputFormat {
+    // Name in messages
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "SVG",
+            OutputFormat::Png => "PNG",
+            OutputFormat::Html => "HTML",
+            OutputFormat::Dot => "DOT",
+            OutputFormat::Geojson => "GeoJSON",
+        }
     }
-}
 
-fn get_activity_color(activity: &ActivityLevel) -> ColorString {
-    match activity {
-        ActivityLevel::Strong => "#22c55e",     // green-500
-        ActivityLevel::Growing => "#86efac",    // green-300
-        ActivityLevel::Weak => "#eab308",       // yellow-500
-        ActivityLevel::Restricted => "#ef4444", // red-500
+    // File of `--output` when none is given
+    fn default_output(self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "sugiyama.svg",
+            OutputFormat::Png => "sugiyama.png",
+            OutputFormat::Html => "sugiyama.html",
+            OutputFormat::Dot => "sugiyama.dot",
+            OutputFormat::Geojson => "sugiyama.geojson",
+        }
     }
-        .to_string().into()
 }
 
-fn get_supply_color(supply: &SupplyLevel) -> ColorString {
-    match supply {
-        SupplyLevel::Abundant => "#22c55e", // green-500
-        SupplyLevel::High => "#86efac",     // green-300
-        SupplyLevel::Moderate => "#fde047", // yellow-300
-        SupplyLevel::Limited => "#f97316",  // orange-500
-        SupplyLevel::Scarce => "#ef4444",   // red-500
-    }
-        .to_string().into()
+// Node field ordering or sizing nodes on the command line, levels counting from the scarcest
+// supply and the weakest activity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum NodeField {
+    Supply,
+    Activity,
+    Cost,
+    Volume,
 }
 
-
-impl TechEdge {
-    pub(crate) fn supply_color(&self) -> ColorString {
-        get_supply_color(&self.supply)
+impl NodeField {
+    // Key of `SecondaryOrder` and of `LayoutOptions::initial_order`
+    fn key(self) -> fn(&TechNode) -> i64 {
+        match self {
+            NodeField::Supply => |node| node.supply.amount() as i64,
+            NodeField::Activity => |node| node.activity.strength() as i64,
+            NodeField::Cost => |node| node.cost as i64,
+            NodeField::Volume => |node| node.volume as i64,
+        }
     }
 
-    pub(crate) fn activity_color(&self) -> ColorString {
-        get_activity_color(&self.activity)
+    // `size_fn` of `NodeSizing`
+    fn size(self) -> fn(&TechNode) -> f64 {
+        match self {
+            NodeField::Supply => |node| node.supply.amount() as f64,
+            NodeField::Activity => |node| node.activity.strength() as f64,
+            NodeField::Cost => |node| node.cost as f64,
+            NodeField::Volume => |node| node.volume as f64,
+        }
     }
 }
 
-enum Orientation {
-    TopDown,
-    LeftRight,
-}
+// Failures are reported on stderr with a non-zero exit status
+fn main() -> ExitCode {
+    let args = Args::parse();
 
-fn main() {
-    let (nodes, edges) = create_full_supply_chain();
+    let (nodes, edges) = match args.input {
+        Some(ref path) => match load_graph(path) {
+            Ok(graph) => graph,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => {
+            // Node and edge values are random; `--seed <n>` reproduces those of an earlier run
+            let seed = args.seed.unwrap_or_else(rand::random);
+            println!("Seed: {} (pass --seed {} to reproduce this run)", seed, seed);
+            create_full_supply_chain(&mut StdRng::seed_from_u64(seed))
+        }
+    };
+    let previous = match args.previous {
+        Some(ref path) => match load_graph(path) {
+            Ok((nodes, edges)) => Some((path, LayoutResult::last_known(&nodes, &edges))),
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    let focus = match args.focus {
+        Some(ref id) if nodes.iter().all(|node| node.id != *id) => {
+            eprintln!("Error focusing on '{}': unknown node", id);
+            return ExitCode::FAILURE;
+        }
+        Some(ref id) => {
+            let mut focus = ancestors(&nodes, &edges, id);
+            focus.extend(descendants(&nodes, &edges, id));
+            focus.insert(id.clone());
+            Some(focus)
+        }
+        None => None,
+    };
+    if let Some(symbol) = args.collapse_waypoint.iter().find(|symbol| nodes.iter().all(|node| node.waypoint_symbol != **symbol)) {
+        eprintln!("Error collapsing '{}': no goods are traded there", symbol);
+        return ExitCode::FAILURE;
+    }
+    let groups = args
+        .collapse_waypoint
+        .iter()
+        .map(|symbol| NodeGroup {
+            id: symbol.clone(),
+            name: symbol.clone(),
+            members: nodes.iter().filter(|node| node.waypoint_symbol == *symbol).map(|node| node.id.clone()).collect(),
+            collapsed: true,
+        })
+        .collect();
+
+    let supply = (args.supply_at_or_below.as_ref().map(SupplyLevel::at_or_below))
+        .or_else(|| args.supply_at_or_above.as_ref().map(SupplyLevel::at_or_above));
+    let activity = (args.activity_at_or_below.as_ref().map(ActivityLevel::at_or_below))
+        .or_else(|| args.activity_at_or_above.as_ref().map(ActivityLevel::at_or_above));
+    let render_config = RenderConfig {
+        font_family: args.font_family.clone(),
+        id_prefix: args.id_prefix.clone(),
+        level_filter: (supply.is_some() || activity.is_some()).then(|| LevelFilter {
+            supply: supply.unwrap_or_default(),
+            activity: activity.unwrap_or_default(),
+        }),
+        focus,
+        theme: Theme::named(args.theme),
+        depth_coloring: args.depth_coloring,
+        badges: args.badges.map(supply_badges_at),
+        corner_style: args.corners,
+        title_emphasis: args.title_emphasis,
+        duplicate_names: args.duplicate_names,
+        node_text_align: args.node_text_align,
+        label_placement: args.label_placement,
+        include_legend: args.legend,
+        park_unpositioned: args.park_unpositioned,
+        accessibility: args.accessibility,
+        title: args.title.clone(),
+        rank_bands: args.rank_bands,
+        rank_guides: args.rank_guides,
+        group_ranks: args.group_ranks,
+        frame: args.frame.then(Frame::default),
+        simplify_tolerance: args.simplify_tolerance,
+        relative_paths: args.relative_paths,
+        label_leaders: args.label_leaders,
+        label_merge: args.merge_parallel_labels.then_some(sum_parallel_edges as LabelMergeFn),
+        line_cap: args.line_cap,
+        line_join: args.line_join,
+        zoom_metadata: args.zoom_hints,
+        svg_size: args.svg_size,
+        max_width: args.max_width,
+        max_height: args.max_height,
+        fixed_node_sizes: args.fixed_node_sizes,
+        wrap_node_text: args.wrap_node_text,
+        size_fn: args.size_by.map(|field| NodeSizing {
+            size_fn: field.size(),
+            min_scale: 1.0,
+            max_scale: args.max_node_scale,
+        }),
+        ..RenderConfig::default()
+    };
+    let nodes = apply_node_sizes(&fit_nodes_to_text(&nodes, &render_config), &render_config);
 
     // Run the layout
-    let orientation = Orientation::LeftRight;
-    let x_scale = 1.5;
-    let y_scale = 0.75;
-    let (layout_nodes, layout_edges) = build_supply_chain_layout(&nodes, &edges, orientation, x_scale, y_scale);
+    let options = LayoutOptions {
+        vertex_spacing: args.vertex_spacing,
+        minimum_length: Some(args.min_length),
+        weight_by: args.weight_by,
+        quick_crossing_reduction: args.quick_crossing_reduction,
+        straight_long_edges: args.straight_long_edges,
+        avoid_nodes: args.avoid_nodes,
+        ports: args.ports,
+        preferred_sides: args.exit_side.map(sides_leaving_by),
+        obstacles: args.obstacle.clone(),
+        unpositioned_edges: args.unpositioned_edges,
+        max_iterations: args.max_iterations,
+        improvement_epsilon: args.improvement_epsilon,
+        initial_order: args.initial_order.map(NodeField::key),
+        secondary_order: args.secondary_order.map(|field| SecondaryOrder {
+            key: field.key(),
+            mode: args.secondary_order_mode,
+        }),
+        straighten_chains: args.straighten_chains,
+        minimize_edge_length: args.minimize_edge_length,
+        transitive_reduction: args.transitive_reduction,
+        groups,
+        rank_constraints: (!args.rank_order.is_empty()).then(|| RankConstraints {
+            category: |node| node.waypoint_type.clone(),
+            order: args.rank_order.clone(),
+        }),
+        component_gutter: args.component_gutter,
+        // The SVG always shows edge labels
+        fit_edge_labels: true,
+        label_placement: args.label_placement,
+        collect_metrics: args.metrics,
+        ..LayoutOptions::default()
+    };
+    let layout = if args.split_components {
+        build_component_layouts(&nodes, &edges, args.orientation, args.x_scale, args.y_scale, &options)
+    } else {
+        build_supply_chain_layout(&nodes, &edges, args.orientation, args.x_scale, args.y_scale, &options)
+    };
+    let mut layout = match layout {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("Error computing layout: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.transpose {
+        let (nodes, edges) = layout.graph(&nodes, &edges);
+        let transposed = transpose_layout(nodes, edges, &layout.result);
+        layout.result = transposed;
+        (layout.x_scale, layout.y_scale) = (layout.y_scale, layout.x_scale);
+    }
+    let render_config = RenderConfig {
+        scale_bar: args.scale_bar.as_ref().map(|(units, unit_name)| layout.scale_bar(*units, unit_name)),
+        ..render_config
+    };
+    let mut metrics = layout.metrics.take();
+    let (nodes, edges) = layout.graph(&nodes, &edges);
 
     // Print the results
     println!("Node Layout:");
-    for node in &layout_nodes {
-        println!("Node '{}': x={:?}, y={:?}", node.name, node.x, node.y);
+    for node in nodes {
+        let position = layout.result.positions.get(&node.id);
+        println!("Node '{}': x={:?}, y={:?}", node.name, position.map(|p| p.0), position.map(|p| p.1));
     }
 
+    println!("\nRank positions: {:?}", layout.rank_positions);
+
     println!("\nEdge Routing:");
-    for edge in &layout_edges {
+    for (edge, path) in edges.iter().zip(layout.result.routes(edges)) {
         println!(
             "Edge '{}' -> '{}': points={:?}",
-            edge.source, edge.target, edge.points
+            edge.source, edge.target, path.map(|path| &path.points)
         );
     }
 
-    let svg = output_svg(&layout_nodes, &layout_edges);
-
-    // Write SVG to file
-    use std::fs::File;
-    use std::io::Write;
-
-    match File::create("sugiyama.svg") {
-        Ok(mut file) => match file.write_all(svg.as_bytes()) {
-            Ok(_) => println!("SVG successfully written to sugiyama.svg"),
-            Err(e) => println!("Error writing to file: {}", e),
-        },
-        Err(e) => println!("Error creating file: {}", e),
+    for unrouted in &layout.unrouted_edges {
+        println!(
+            "Edge '{}' -> '{}' could not be routed: {}",
+            unrouted.source, unrouted.target, unrouted.reason
+        );
     }
-}
-
-fn create_full_supply_chain() -> (Vec<TechNode>, Vec<TechEdge>) {
-    // Create all the nodes from the mermaid diagram
-    let nodes = vec![
-        create_node(
-            "advanced_circuitry_at_x_1_ad_75_d_44",
-            "ADVANCED_CIRCUITRY",
-            "X1-AD75-D44",
-            "ADVANCED",
-        ),
-        create_node(
-            "aluminum_at_x_1_ad_75_h_51",
-            "ALUMINUM",
-            "X1-AD75-H51",
-            "REFINED",
-        ),
-        create_node(
-            "aluminum_ore_at_x_1_ad_75_xd_5_a",
-            "ALUMINUM_ORE",
-            "X1-AD75-XD5A",
-            "RAW_MATERIAL",
-        ),
-        create_node(
-            "clothing_at_x_1_ad_75_k_81",
-            "CLOTHING",
-            "X1-AD75-K81",
-            "CONSUMER",
-        ),
-        create_node(
-            "copper_at_x_1_ad_75_h_51",
-            "COPPER",
-            "X1-AD75-H51",
-            "REFINED",
-        ),
-        create_node(
-            "copper_ore_at_x_1_ad_75_xd_5_a",
-            "COPPER_ORE",
-            "X1-AD75-XD5A",
-            "RAW_MATERIAL",
-        ),
-        create_node(
-            "electronics_at_x_1_ad_75_f_49",
-            "ELECTRONICS",
-            "X1-AD75-F49",
-            "INDUSTRIAL",
-        ),
-        create_node(
-            "equipment_at_x_1_ad_75_k_81",
-            "EQUIPMENT",
-            "X1-AD75-K81",
-            "INDUSTRIAL",
-        ),
-        create_node(
-            "fabrics_at_x_1_ad_75_e_46",
-            "FABRICS",
-            "X1-AD75-E46",
-            "INDUSTRIAL",
-        ),
-        create_node(
-            "fab_mats_at_x_1_ad_75_f_49",
-            "FAB_MATS",
-            "X1-AD75-F49",
-            "INDUSTRIAL",
-        ),
-        create_node(
-            "fertilizers_at_x_1_ad_75_g_50",
-            "FERTILIZERS",
-            "X1-AD75-G50",
-            "INDUSTRIAL",
-        ),
-        create_node("iron_at_x_1_ad_75_h_51", "IRON", "X1-AD75-H51", "REFINED"),
-        create_node(
-            "iron_ore_at_x_1_ad_75_xd_5_a",
-            "IRON_ORE",
-            "X1-AD75-XD5A",
-            "RAW_MATERIAL",
-        ),
-        create_node(
-            "liquid_hydrogen_at_x_1_ad_75_c_40",
-            "LIQUID_HYDROGEN",
-            "X1-AD75-C40",
-            "REFINED",
-        ),
-        create_node(
-            "liquid_hydrogen_at_x_1_ad_75_c_41",
-            "LIQUID_HYDROGEN",
-            "X1-AD75-C41",
-            "REFINED",
-        ),
-        create_node(
-            "liquid_nitrogen_at_x_1_ad_75_c_40",
-            "LIQUID_NITROGEN",
-            "X1-AD75-C40",
-            "REFINED",
-        ),
-        create_node(
-            "liquid_nitrogen_at_x_1_ad_75_c_41",
-            "LIQUID_NITROGEN",
-            "X1-AD75-C41",
-            "REFINED",
-        ),
-        create_node(
-            "machinery_at_x_1_ad_75_e_46",
-            "MACHINERY",
-            "X1-AD75-E46",
-            "INDUSTRIAL",
-        ),
-        create_node(
-            "microprocessors_at_x_1_ad_75_a_3",
-            "MICROPROCESSORS",
-            "X1-AD75-A3",
-            "ADVANCED",
-        ),
-        create_node(
-            "plastics_at_x_1_ad_75_g_50",
-            "PLASTICS",
-            "X1-AD75-G50",
-            "INDUSTRIAL",
-        ),
-        create_node(
-            "quartz_sand_at_x_1_ad_75_h_53",
-            "QUARTZ_SAND",
-            "X1-AD75-H53",
-            "REFINED",
-        ),
-        create_node(
-            "quartz_sand_at_x_1_ad_75_xd_5_a",
-            "QUARTZ_SAND",
-            "X1-AD75-XD5A",
-            "RAW_MATERIAL",
-        ),
-        create_node(
-            "ship_parts_at_x_1_ad_75_d_43",
-            "SHIP_PARTS",
-            "X1-AD75-D43",
-            "ADVANCED",
-        ),
-        create_node(
-            "ship_plating_at_x_1_ad_75_d_44",
-            "SHIP_PLATING",
-            "X1-AD75-D44",
-            "ADVANCED",
-        ),
-        create_node(
-            "silicon_crystals_at_x_1_ad_75_h_53",
-            "SILICON_CRYSTALS",
-            "X1-AD75-H53",
-            "REFINED",
-        ),
-        create_node(
-            "silicon_crystals_at_x_1_ad_75_xd_5_a",
-            "SILICON_CRYSTALS",
-            "X1-AD75-XD5A",
-            "RAW_MATERIAL",
-        ),
-    ];
-
-    // Create all the edges from the mermaid diagram
-    let edges = vec![
-        create_edge("iron_at_x_1_ad_75_h_51", "machinery_at_x_1_ad_75_e_46"),
-        create_edge(
-            "liquid_hydrogen_at_x_1_ad_75_c_41",
-            "plastics_at_x_1_ad_75_g_50",
-        ),
-        create_edge(
-            "silicon_crystals_at_x_1_ad_75_h_53",
-            "electronics_at_x_1_ad_75_f_49",
-        ),
-        create_edge("copper_at_x_1_ad_75_h_51", "electronics_at_x_1_ad_75_f_49"),
-        create_edge("aluminum_at_x_1_ad_75_h_51", "equipment_at_x_1_ad_75_k_81"),
-        create_edge("plastics_at_x_1_ad_75_g_50", "equipment_at_x_1_ad_75_k_81"),
-        create_edge(
-            "aluminum_at_x_1_ad_75_h_51",
-            "ship_plating_at_x_1_ad_75_d_44",
-        ),
-        create_edge(
-            "machinery_at_x_1_ad_75_e_46",
-            "ship_plating_at_x_1_ad_75_d_44",
-        ),
-        create_edge("iron_at_x_1_ad_75_h_51", "fab_mats_at_x_1_ad_75_f_49"),
-        create_edge(
-            "quartz_sand_at_x_1_ad_75_h_53",
-            "fab_mats_at_x_1_ad_75_f_49",
-        ),
-        create_edge(
-            "silicon_crystals_at_x_1_ad_75_h_53",
-            "microprocessors_at_x_1_ad_75_a_3",
-        ),
-        create_edge(
-            "copper_at_x_1_ad_75_h_51",
-            "microprocessors_at_x_1_ad_75_a_3",
-        ),
-        create_edge(
-            "liquid_nitrogen_at_x_1_ad_75_c_41",
-            "fertilizers_at_x_1_ad_75_g_50",
-        ),
-        create_edge(
-            "equipment_at_x_1_ad_75_k_81",
-            "ship_parts_at_x_1_ad_75_d_43",
-        ),
-        create_edge(
-            "electronics_at_x_1_ad_75_f_49",
-            "ship_parts_at_x_1_ad_75_d_43",
-        ),
-        create_edge("fertilizers_at_x_1_ad_75_g_50", "fabrics_at_x_1_ad_75_e_46"),
-        create_edge(
-            "electronics_at_x_1_ad_75_f_49",
-            "advanced_circuitry_at_x_1_ad_75_d_44",
-        ),
-        create_edge(
-            "microprocessors_at_x_1_ad_75_a_3",
-            "advanced_circuitry_at_x_1_ad_75_d_44",
-        ),
-        create_edge("fabrics_at_x_1_ad_75_e_46", "clothing_at_x_1_ad_75_k_81"),
-        create_edge(
-            "quartz_sand_at_x_1_ad_75_xd_5_a",
-            "quartz_sand_at_x_1_ad_75_h_53",
-        ),
-        create_edge(
-            "liquid_nitrogen_at_x_1_ad_75_c_40",
-            "liquid_nitrogen_at_x_1_ad_75_c_41",
-        ),
-        create_edge("copper_ore_at_x_1_ad_75_xd_5_a", "copper_at_x_1_ad_75_h_51"),
-        create_edge(
-            "liquid_hydrogen_at_x_1_ad_75_c_40",
-            "liquid_hydrogen_at_x_1_ad_75_c_41",
-        ),
-        create_edge("iron_ore_at_x_1_ad_75_xd_5_a", "iron_at_x_1_ad_75_h_51"),
-        create_edge(
-            "aluminum_ore_at_x_1_ad_75_xd_5_a",
-            "aluminum_at_x_1_ad_75_h_51",
-        ),
-        create_edge(
-            "silicon_crystals_at_x_1_ad_75_xd_5_a",
-            "silicon_crystals_at_x_1_ad_75_h_53",
-        ),
-    ];
 
-    (nodes, edges)
-}
-
-// Helper function to create nodes with random values
-fn create_node(id: &str, name: &str, waypoint: &str, node_type: &str) -> TechNode {
-    let mut rng = rand::thread_rng();
-
-    // Generate random supply level
-    let supplies: Vec<SupplyLevel> = SupplyLevel::iter().collect();
-    let random_supply = supplies[rng.random_range(0..supplies.len())].clone();
-
-    // Generate random activity level
-    let activities: Vec<ActivityLevel> = ActivityLevel::iter().collect();
-    let random_activity = activities[rng.random_range(0..activities.len())].clone();
-
-    // Random cost between 50 and 500
-    let random_cost = rng.random_range(50..=500);
-
-    // Random volume between 5 and 100
-    let random_volume = rng.random_range(5..=100);
-
-    TechNode {
-        id: id.to_string(),
-        name: name.to_string(),
-        waypoint_symbol: waypoint.to_string(),
-        waypoint_type: node_type.to_string(),
-        supply: random_supply,
-        activity: random_activity,
-        cost: random_cost,
-        volume: random_volume,
-        width: 200.0,
-        height: 165.0,
-        x: None,
-        y: None,
+    for &i in &layout.reversed_edges {
+        println!("Edge '{}' -> '{}' runs against the ranks to break a cycle", edges[i].source, edges[i].target);
     }
-}
-
-// Helper function to create edges with random activity and supply levels
-fn create_edge(source: &str, target: &str) -> TechEdge {
-    let mut rng = rand::thread_rng();
-
-    // Generate random activity level
-    let activities: Vec<ActivityLevel> = ActivityLevel::iter().collect();
-    let random_activity = activities[rng.gen_range(0..activities.len())].clone();
-
-    // Generate random supply level
-    let supplies: Vec<SupplyLevel> = SupplyLevel::iter().collect();
-    let random_supply = supplies[rng.gen_range(0..supplies.len())].clone();
-
-    // Random cost between 10 and 200
-    let random_cost = rng.gen_range(10..=200);
 
-    // Random volume between 1 and 50
-    let random_volume = rng.gen_range(1..=50);
-
-    // Random distance between 10 and 150
-    let random_distance = rng.gen_range(10..=150);
-
-    // Random profit between -50 and 250 (can be negative)
-    let random_profit = rng.gen_range(-50..=250);
-
-
-
-    TechEdge {
-        source: source.to_string(),
-        target: target.to_string(),
-        cost: random_cost,
-        activity: random_activity,
-        volume: random_volume,
-        supply: random_supply,
-        points: None,
-        curve_factor: None,
-        distance: Some(random_distance),
-        profit: Some(random_profit),
-    }
-}
-
-// Function to build the supply chain layout with separate x and y scaling
-fn build_supply_chain_layout(
-    nodes: &[TechNode],
-    edges: &[TechEdge],
-    orientation: Orientation,
-    x_scale: f64,  // Scaling factor for horizontal spacing
-    y_scale: f64,  // Scaling factor for vertical spacing
-) -> (Vec<TechNode>, Vec<TechEdge>) {
-    // Create a new directed graph
-    let mut graph: StableDiGraph<String, u32> = StableDiGraph::new();
-
-    // Create a mapping from node ID to NodeIndex
-    let mut node_indices: HashMap<String, NodeIndex> = HashMap::new();
-
-    // Add all nodes to the graph
-    for node in nodes {
-        let node_idx = graph.add_node(node.id.clone());
-        node_indices.insert(node.id.clone(), node_idx);
+    if let Some((path, ref previous)) = previous {
+        println!("\nMoved since {}:", path.display());
+        for (id, (dx, dy)) in diff_layouts(previous, &layout.result) {
+            println!("Node '{}': dx={:.2}, dy={:.2}", id, dx, dy);
+        }
     }
 
-    // Add all edges to the graph
-    for edge in edges {
-        if let (Some(source_idx), Some(target_idx)) = (
-            node_indices.get(&edge.source),
-            node_indices.get(&edge.target),
-        ) {
-            graph.add_edge(*source_idx, *target_idx, edge.cost);
+    if args.fixed_node_sizes {
+        let fit = check_label_fit(nodes, &layout.result, 10.0, &render_config);
+        if !fit.all_fit() {
+            println!(
+                "\nText overflows the boxes of {}; boxes holding it need a canvas of {:.0}x{:.0}",
+                fit.overflowing.join(", "),
+                fit.canvas.0,
+                fit.canvas.1
+            );
         }
     }
 
-    // Configure the layout algorithm
-    let config = Config {
-        minimum_length: 1, // Increase this from 0
-        vertex_spacing: 300,
-        dummy_vertices: true,                          // Enable dummy vertices
-        dummy_size: 150.0,                              // Give them a size
-        ranking_type: RankingType::MinimizeEdgeLength, // Change from Original
-        c_minimization: CrossingMinimization::Barycenter,
-        transpose: true,
-        // ..Default::default()
+    let svg = match metrics {
+        Some(ref mut metrics) => output_svg_with_metrics(nodes, edges, &layout.result, &render_config, metrics),
+        None => output_svg(nodes, edges, &layout.result, &render_config),
     };
 
-    // Run the layout algorithm
-    let layouts = from_graph(&graph).with_config(config);
-
-    // Process the layout results
-    let mut updated_nodes = nodes.to_vec();
-    let mut updated_edges = edges.to_vec();
-
-    // Create reverse lookup from NodeIndex to position in nodes array
-    let mut node_positions: HashMap<String, usize> = HashMap::new();
-    for (i, node) in nodes.iter().enumerate() {
-        node_positions.insert(node.id.clone(), i);
+    if let Some(ref metrics) = metrics {
+        println!(
+            "\nMetrics: {} nodes, {} edges, {} dummy vertices, {} crossings",
+            metrics.nodes, metrics.edges, metrics.dummy_vertices, metrics.crossings
+        );
+        println!(
+            "Graph construction {:?}, engine {:?}, post-processing {:?}, routing {:?}, render {:?}",
+            metrics.graph_construction,
+            metrics.engine,
+            metrics.post_processing,
+            metrics.routing,
+            metrics.render.unwrap_or_default()
+        );
     }
 
-    let built_layouts = layouts.build();
-
-    // Apply coordinates to nodes
-    if let Some((layout, width, height)) = built_layouts.first() {
-        for (node_idx, (x, y)) in layout.iter() {
-            let node_id = &graph[NodeIndex::from(*node_idx)];
-            if let Some(&pos) = node_positions.get(node_id) {
-                match orientation {
-                    Orientation::LeftRight => {
-                        // Update node coordinates and rotate 90 degrees (swap and invert as needed)
-                        // Also apply scaling factors
-                        updated_nodes[pos].x = Some(-*y as f64 * x_scale);
-                        updated_nodes[pos].y = Some(*x as f64 * y_scale);
-                    }
-                    Orientation::TopDown => {
-                        updated_nodes[pos].x = Some(*x as f64 * x_scale);
-                        updated_nodes[pos].y = Some(*y as f64 * y_scale);
-                    }
-                }
-            }
-        }
-
-        // Process edge routing with scaling
-        for edge in &mut updated_edges {
-            if let (Some(source_pos), Some(target_pos)) = (
-                node_positions.get(&edge.source),
-                node_positions.get(&edge.target),
-            ) {
-                let source_node = &updated_nodes[*source_pos];
-                let target_node = &updated_nodes[*target_pos];
-
-                if let (Some(sx), Some(sy), Some(tx), Some(ty)) =
-                    (source_node.x, source_node.y, target_node.x, target_node.y)
-                {
-                    // For curved edges with control points
-                    let mid_x = (sx + tx) / 2.0;
-                    let mid_y = (sy + ty) / 2.0;
-
-                    // Create a path with control points
-                    edge.points = Some(vec![
-                        (sx, sy),       // Start point
-                        (mid_x, mid_y), // Control point
-                        (tx, ty),       // End point
-                    ]);
-
-                    // Calculate curve factor based on distance
-                    let distance = ((tx - sx).powi(2) + (ty - sy).powi(2)).sqrt();
-                    edge.curve_factor = Some((distance / 500.0).min(0.5).max(0.1));
-                }
-            }
+    if let Some(ref path) = args.save_graph {
+        if let Err(e) = save_graph(path, nodes, edges, &layout.result) {
+            eprintln!("Error writing {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
         }
+        println!("Graph saved to {}", path.display());
     }
 
-    (updated_nodes, updated_edges)
-}
-
-fn output_svg(nodes: &[TechNode], edges: &[TechEdge]) -> String {
-    // Calculate SVG dimensions based on node positions
-    let margin = 50.0;
-    let mut min_x = f64::MAX;
-    let mut min_y = f64::MAX;
-    let mut max_x = f64::MIN;
-    let mut max_y = f64::MIN;
-
-    for node in nodes {
-        if let (Some(x), Some(y)) = (node.x, node.y) {
-            min_x = min_x.min(x - node.width / 2.0);
-            min_y = min_y.min(y - node.height / 2.0);
-            max_x = max_x.max(x + node.width / 2.0);
-            max_y = max_y.max(y + node.height / 2.0);
+    if let Some(ref path) = args.regions {
+        if let Err(e) = fs::write(path, to_regions_json(nodes, edges, &layout.result, &render_config)) {
+            eprintln!("Error writing {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
         }
+        println!("Regions written to {}", path.display());
     }
 
-    let svg_width = max_x - min_x + 2.0 * margin;
-    let svg_height = max_y - min_y + 2.0 * margin;
-
-    // SVG header
-    let mut svg = format!(
-        r#"<svg width="{}" height="{}" xmlns="http://www.w3.org/2000/svg">"#,
-        svg_width, svg_height
-    );
-
-    // Transform to adjust for margins and any negative coordinates
-    svg.push_str(&format!(
-        r#"<g transform="translate({},{})">"#,
-        margin - min_x,
-        margin - min_y
-    ));
-
-    // Draw edges
-    for edge in edges {
-        if let Some(ref points) = edge.points {
-            if points.len() >= 2 {
-                if points.len() == 2 {
-                    // Simple straight line
-                    svg.push_str(&format!(
-                        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="gray" stroke-width="2" />"#,
-                        points[0].0, points[0].1, points[1].0, points[1].1
-                    ));
-                } else {
-                    // Path with control points
-                    svg.push_str(&format!(
-                        r#"<path d="M{},{} Q{},{} {},{}" fill="none" stroke="gray" stroke-width="2" />"#,
-                        points[0].0, points[0].1,
-                        points[1].0, points[1].1,
-                        points[2].0, points[2].1
-                    ));
-
-                    // Add an arrow at the end
-                    svg.push_str(&format!(
-                        r#"<circle cx="{}" cy="{}" r="4" fill="black" />"#,
-                        points[2].0, points[2].1
-                    ));
+    let output = args.output.unwrap_or_else(|| PathBuf::from(args.format.default_output()));
+    let contents = match args.format {
+        OutputFormat::Png => {
+            return match render_png(&svg, &output, args.png_scale) {
+                Ok(()) => {
+                    println!("PNG successfully written to {}", output.display());
+                    ExitCode::SUCCESS
                 }
-            }
-        }
-    }
-
-    // Draw nodes using the new node generator
-    for node in nodes {
-        svg.push_str(&generate_node_svg(node));
-    }
-
-    // Add edge labels after nodes to ensure they're in the foreground
-    // But only for target nodes as per your update
-    for edge in edges {
-        if let Some(ref points) = edge.points {
-            if points.len() >= 2 {
-                // Get target node
-                let target_node = nodes.iter().find(|n| n.id == edge.target).unwrap();
-
-                if let (Some(tx), Some(ty)) = (target_node.x, target_node.y) {
-                    // For target label:
-                    // Calculate target node border intersection
-                    let (target_ix, target_iy) = calculate_node_border_intersection(
-                        tx, ty, target_node.width, target_node.height,
-                        points[points.len()-1].0, points[points.len()-1].1,
-                        points[points.len()-2].0, points[points.len()-2].1
-                    );
-
-                    // Calculate direction vector - pointing from node to edge (outward)
-                    let direction_x = points[points.len()-2].0 - tx;
-                    let direction_y = points[points.len()-2].1 - ty;
-
-                    // Add label with direction vector for proper positioning
-                    svg.push_str(&generate_edge_label_svg(target_ix, target_iy, edge, direction_x, direction_y));
+                Err(e) => {
+                    eprintln!("Error rendering PNG: {}", e);
+                    ExitCode::FAILURE
                 }
-            }
+            };
         }
-    }
-    // Close SVG
-    svg.push_str("</g></svg>");
-
-    svg
-}
-
-// A utility function to generate SVG multiline text with varying colors
-// Now with support for a font size multiplier for the first line
-fn generate_multiline_text_svg(
-    x: f64,                              // X position (anchor point)
-    y: f64,                              // Y position (top of first line)
-    lines: &[(String, ColorString)],     // Text content and colors
-    text_anchor: &str,                   // "start", "middle", or "end"
-    font_family: &str,                   // Font family
-    font_size: u32,                      // Base font size
-    line_height: f64,                    // Space between lines
-    dominant_baseline: Option<&str>,     // Optional baseline alignment
-    first_line_size_multiplier: Option<f64>, // Optional font size multiplier for the first line
-) -> String {
-    let baseline_attr = if let Some(baseline) = dominant_baseline {
-        format!(" dominant-baseline=\"{}\"", baseline)
-    } else {
-        String::new()
-    };
-
-    let mut svg = format!(
-        r#"<text x="{}" y="{}" font-family="{}" font-size="{}"{} text-anchor="{}">"#,
-        x, y, font_family, font_size, baseline_attr, text_anchor
-    );
-
-    for (i, (text, color)) in lines.iter().enumerate() {
-        let dy = if i == 0 { "0".to_string() } else { format!("{}", line_height) };
-
-        // Apply font size multiplier to first line if specified
-        let font_size_attr = if i == 0 && first_line_size_multiplier.is_some() {
-            let multiplier = first_line_size_multiplier.unwrap();
-            let adjusted_size = (font_size as f64 * multiplier).round() as u32;
-            format!(" font-size=\"{}\"", adjusted_size)
-        } else {
-            String::new()
-        };
-
-        svg.push_str(&format!(
-            r#"<tspan x="{}" dy="{}"{} fill="{}">{}</tspan>"#,
-            x, dy, font_size_attr, color.0, text
-        ));
-    }
-
-    svg.push_str("</text>");
-    svg
-}
-
-// Refactored node SVG generator with increased padding and first line font size multiplier
-fn generate_node_svg(node: &TechNode) -> String {
-    if let (Some(x), Some(y)) = (node.x, node.y) {
-        // Colors
-        let text_color = "#FFFFFF";
-        let bold_text_color = ColorString::from("#FFFFFF");
-        let normal_text_color = ColorString::from("#CCCCCC");
-
-        // Get activity color for border
-        let border_color = node.activity_color().0;
-
-        // Get color based on node type
-        let fill_color = match node.waypoint_type.as_str() {
-            "RAW_MATERIAL" => "#091c26",
-            "REFINED" => "#0a2533",
-            "INDUSTRIAL" => "#0c3040",
-            "ADVANCED" => "#0e3a4d",
-            "CONSUMER" => "#10425a",
-            _ => "#000000",
-        };
-
-        // Layout parameters
-        let node_x = x - node.width / 2.0;
-        let node_y = y - node.height / 2.0;
-        let text_right_x = x + node.width / 2.0 - 16.0;  // Increased padding from 10px to 16px
-        let line_height = 20.0;
-
-        // Text styling
-        let font_family = "Arial";
-        let normal_font_size = 10;
-        let title_font_size_multiplier = 1.3;  // Make first line 30% larger
-        let border_width = 4;
-        let corner_radius = 5;
-
-        // Prepare text lines with their colors
-        let text_lines = vec![
-            // Name (bold, title font)
-            (node.name.clone(), bold_text_color.clone()),
-            // Waypoint symbol
-            (node.waypoint_symbol.clone(), normal_text_color.clone()),
-            // Waypoint type
-            (node.waypoint_type.clone(), normal_text_color.clone()),
-            // Activity
-            (format!("A: {}", node.activity.to_string()), node.activity_color()),
-            // Supply
-            (format!("S: {}", node.supply.to_string()), node.supply_color()),
-            // Volume
-            (format!("v: {}", node.volume), normal_text_color.clone()),
-            // Costs
-            (format!("p: {}c", node.cost), normal_text_color.clone()),
-        ];
-
-        format!(
-            r#"<g>
-                <!-- Node background -->
-                <rect
-                    x="{node_x}"
-                    y="{node_y}"
-                    width="{}"
-                    height="{}"
-                    rx="{corner_radius}"
-                    ry="{corner_radius}"
-                    fill="{fill_color}"
-                    stroke="{border_color}"
-                    stroke-width="{border_width}"
-                />
-
-                <!-- Node text content (using multiline text) -->
-                {}
-            </g>"#,
-            node.width,
-            node.height,
-            generate_multiline_text_svg(
-                text_right_x,              // x position (right-aligned with increased padding)
-                node_y + 30.0,             // y position (starting from top with padding)
-                &text_lines,               // text content and colors
-                "end",                     // right-aligned text
-                font_family,               // font family
-                normal_font_size,          // font size
-                line_height,               // line spacing
-                None,                      // no special baseline alignment
-                Some(title_font_size_multiplier), // Increase size of first line
-            )
-        )
-    } else {
-        // Return empty string if node has no position
-        String::new()
-    }
-}
-
-// Refactored edge label SVG generator with increased padding
-fn generate_edge_label_svg(x: f64, y: f64, edge: &TechEdge, direction_x: f64, direction_y: f64) -> String {
-    // Label parameters
-    let label_width = 105.0;
-    let label_height = 60.0;  // Increased height from 55.0 to 60.0 for more padding
-    let padding = 8.0;        // Increased padding from 5.0 to 8.0
-
-    // Calculate offset distance to move label along direction vector
-    // Normalize direction vector
-    let direction_length = (direction_x * direction_x + direction_y * direction_y).sqrt();
-
-    // Prevent division by zero
-    if direction_length < 0.001 {
-        return String::new(); // Return empty string if direction vector is too small
-    }
-
-    let norm_dir_x = direction_x / direction_length;
-    let norm_dir_y = direction_y / direction_length;
-
-    // Move label out from the intersection point along the direction vector
-    let offset_distance = 30.0;
-    let offset_x = norm_dir_x * offset_distance;
-    let offset_y = norm_dir_y * offset_distance;
-
-    // Apply offset to position
-    let center_x = x + offset_x;
-    let center_y = y + offset_y;
-
-    // Calculate label corner position
-    let label_x = center_x - label_width / 2.0;
-    let label_y = center_y - label_height / 2.0;
-
-    // Text styling
-    let font_size = 10;
-    let font_family = "Arial";
-    let normal_text_color = ColorString::from("#eee");
-    let line_height = 18.0;
-
-    // Background styling
-    let background_fill = "#666";
-    let background_opacity = 1.0;
-    let border_color = "gray";
-    let border_width = 1;
-    let corner_radius = 4;
-
-    // Content from edge
-    let cost = edge.cost;
-    let volume = edge.volume;
-    let activity = &edge.activity;
-    let supply = &edge.supply;
-
-    // New fields
-    let distance = edge.distance.unwrap_or(0);
-    let profit = edge.profit.unwrap_or(0);
-
-    // Colors for activity and supply
-    let activity_color = edge.activity_color();
-    let supply_color = edge.supply_color();
-
-    // Profit color (green for positive, red for negative)
-    let profit_color = if profit >= 0 { "#22c55e" } else { "#ef4444" };
-
-    // Prepare left and right text content
-    let left_text_lines = vec![
-        (format!("d: {}", distance), normal_text_color.clone()),
-        (format!("v: {}", volume), normal_text_color.clone()),
-        (format!("p: {}c", cost), normal_text_color.clone()),
-    ];
-
-    let right_text_lines = vec![
-         (format!("A: {}", activity), activity_color),
-         (format!("S: {}", supply), supply_color),
-        (format!("{:+}", profit), ColorString::from(profit_color)),
-    ];
-
-    // Calculate vertical center position with adjustment for 3 lines of text
-    // For perfect vertical centering, we position the middle line at the center
-    // and adjust the first line position accordingly
-    let total_text_height = line_height * 2.0; // Height of 3 lines (with 2 line-height spaces)
-    let vertical_center = label_y + label_height / 2.0;
-    let row1_y = vertical_center - total_text_height / 2.0;
-
-    format!(
-        r#"<g>
-            <!-- Label background -->
-            <rect
-                x="{label_x}"
-                y="{label_y}"
-                width="{label_width}"
-                height="{label_height}"
-                rx="{corner_radius}"
-                ry="{corner_radius}"
-                fill="{background_fill}"
-                fill-opacity="{background_opacity}"
-                stroke="{border_color}"
-                stroke-width="{border_width}"
-            />
-
-            <!-- Left-aligned text (using multiline text) -->
-            {}
-
-            <!-- Right-aligned text (using multiline text) -->
-            {}
-        </g>"#,
-        generate_multiline_text_svg(
-            label_x + padding,      // x position (left side with increased padding)
-            row1_y,                 // y position (starting from top, adjusted for padding)
-            &left_text_lines,       // text content and colors
-            "start",                // left-aligned text
-            font_family,            // font family
-            font_size,              // font size
-            line_height,            // line spacing
-            Some("middle"),         // middle baseline alignment
-            None,                   // no font size multiplier for first line
-        ),
-        generate_multiline_text_svg(
-            label_x + label_width - padding,  // x position (right side with increased padding)
-            row1_y,                           // y position (starting from top, adjusted for padding)
-            &right_text_lines,                // text content and colors
-            "end",                            // right-aligned text
-            font_family,                      // font family
-            font_size,                        // font size
-            line_height,                      // line spacing
-            Some("middle"),                   // middle baseline alignment
-            None,                             // no font size multiplier for first line
-        )
-    )
-}
-
-
-// Helper function to calculate the intersection of a line with a node's rectangle border
-fn calculate_node_border_intersection(
-    node_x: f64,
-    node_y: f64,
-    node_width: f64,
-    node_height: f64,
-    line_x1: f64,
-    line_y1: f64,
-    line_x2: f64,
-    line_y2: f64,
-) -> (f64, f64) {
-    // Calculate node rectangle boundaries
-    let left = node_x - node_width / 2.0;
-    let right = node_x + node_width / 2.0;
-    let top = node_y - node_height / 2.0;
-    let bottom = node_y + node_height / 2.0;
-
-    // Direction vector of the line
-    let dx = line_x2 - line_x1;
-    let dy = line_y2 - line_y1;
-
-    // Parameters for intersection with each edge
-    let t_left = if dx != 0.0 {
-        (left - line_x1) / dx
-    } else {
-        f64::INFINITY
+        OutputFormat::Svg => svg,
+        OutputFormat::Html => output_html(&svg, &render_config.theme),
+        OutputFormat::Dot => to_dot(nodes, edges),
+        OutputFormat::Geojson => to_geojson(nodes, edges, &layout.result),
     };
-    let t_right = if dx != 0.0 {
-        (right - line_x1) / dx
-    } else {
-        f64::INFINITY
-    };
-    let t_top = if dy != 0.0 {
-        (top - line_y1) / dy
-    } else {
-        f64::INFINITY
-    };
-    let t_bottom = if dy != 0.0 {
-        (bottom - line_y1) / dy
-    } else {
-        f64::INFINITY
-    };
-
-    // Find valid intersections (0 <= t <= 1)
-    let mut valid_intersections = Vec::new();
-
-    if t_left >= 0.0 && t_left <= 1.0 {
-        let y = line_y1 + t_left * dy;
-        if y >= top && y <= bottom {
-            valid_intersections.push((t_left, left, y));
-        }
-    }
-
-    if t_right >= 0.0 && t_right <= 1.0 {
-        let y = line_y1 + t_right * dy;
-        if y >= top && y <= bottom {
-            valid_intersections.push((t_right, right, y));
-        }
-    }
 
-    if t_top >= 0.0 && t_top <= 1.0 {
-        let x = line_x1 + t_top * dx;
-        if x >= left && x <= right {
-            valid_intersections.push((t_top, x, top));
-        }
-    }
+    // Write the output file
+    use std::fs::File;
+    use std::io::Write;
 
-    if t_bottom >= 0.0 && t_bottom <= 1.0 {
-        let x = line_x1 + t_bottom * dx;
-        if x >= left && x <= right {
-            valid_intersections.push((t_bottom, x, bottom));
+    match File::create(&output) {
+        Ok(mut file) => match file.write_all(contents.as_bytes()) {
+            Ok(_) => {
+                println!("{} successfully written to {}", args.format.name(), output.display());
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Error writing to file: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        Err(e) => {
+            eprintln!("Error creating file: {}", e);
+            ExitCode::FAILURE
         }
     }
-
-    // Sort by parameter t and get the closest intersection
-    valid_intersections.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-
-    if valid_intersections.is_empty() {
-        // Fallback - if no intersection found, use the point on the node's center
-        (node_x, node_y)
-    } else {
-        // Return the first valid intersection (closest to line_x1, line_y1)
-        (valid_intersections[0].1, valid_intersections[0].2)
-    }
 }
@@ -0,0 +1,420 @@
+//! Hierarchical (Sugiyama style) layout of arbitrary directed graphs. Callers describe their
+//! nodes through [`LayoutNode`] and draw them through a [`NodeRenderer`]; nothing here knows
+//! what the nodes stand for. [`supply_chain`] builds supply chain drawings on top of it.
+
+use petgraph::stable_graph::{NodeIndex, StableDiGraph};
+use rust_sugiyama::from_graph;
+use std::fmt;
+use std::str::FromStr;
+
+/// Engine settings such as `vertex_spacing`, passed through to rust-sugiyama
+pub use rust_sugiyama::configure::Config;
+
+pub mod supply_chain;
+
+/// An (x, y) position
+pub type Point = (f64, f64);
+
+/// Affine map from layout coordinates to the pixel coordinates of a rendered drawing,
+/// `pixel = layout * scale + translation` per axis
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub tx: f64,
+    pub ty: f64,
+    pub sx: f64,
+    pub sy: f64,
+}
+
+impl Transform {
+    /// Layout coordinates to pixels
+    pub fn apply(&self, (x, y): Point) -> Point {
+        (x * self.sx + self.tx, y * self.sy + self.ty)
+    }
+
+    /// Pixels back to layout coordinates
+    pub fn invert(&self, (x, y): Point) -> Point {
+        ((x - self.tx) / self.sx, (y - self.ty) / self.sy)
+    }
+}
+
+/// Direction in which the ranks follow each other on screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    TopDown,
+    BottomUp,
+    LeftRight,
+    RightLeft,
+}
+
+impl Orientation {
+    /// Every orientation
+    pub const ALL: [Orientation; 4] = [Orientation::TopDown, Orientation::LeftRight, Orientation::RightLeft, Orientation::BottomUp];
+
+    /// Name used on command lines, e.g. `left-right`
+    pub fn name(self) -> &'static str {
+        match self {
+            Orientation::TopDown => "top-down",
+            Orientation::BottomUp => "bottom-up",
+            Orientation::LeftRight => "left-right",
+            Orientation::RightLeft => "right-left",
+        }
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Orientation::ALL.into_iter().find(|orientation| orientation.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Orientation::ALL.iter().map(|orientation| orientation.name()).collect();
+            format!("unknown orientation '{}', expected one of {}", name, names.join(", "))
+        })
+    }
+}
+
+/// A node the layout can place
+pub trait LayoutNode {
+    /// Identifier of the node, unique within a graph
+    fn id(&self) -> &str;
+    /// Width and height of the node's box in layout coordinates. The layout keeps at least
+    /// [`NODE_GAP`] between the boxes of neighbouring nodes and ranks.
+    fn size(&self) -> (f64, f64);
+}
+
+/// Smallest distance [`layout()`] leaves between two boxes of the same rank or of neighbouring
+/// ranks, where `vertex_spacing` alone would let them come closer
+pub const NODE_GAP: f64 = 20.0;
+
+/// Draws nodes for [`render_svg`]
+pub trait NodeRenderer<N> {
+    /// SVG fragment of `node` centered on (`x`, `y`)
+    fn render_svg(&self, node: &N, x: f64, y: f64) -> String;
+}
+
+/// Outcome of [`layout()`], indexed like the nodes passed in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayoutResult {
+    /// Center of each node; `None` for nodes the engine didn't place
+    pub positions: Vec<Option<Point>>,
+    /// Rank of each node, counted from 0
+    pub ranks: Vec<Option<usize>>,
+}
+
+/// Lay out `nodes` connected by `edges`, given as (source, target) indices into `nodes`.
+/// Coordinates are in engine units with y pointing down the screen: ranks follow each other
+/// `vertex_spacing` apart in the direction of `orientation`, as do neighbours within a rank.
+/// Larger boxes (see [`LayoutNode::size`]) push their neighbours and ranks further apart.
+pub fn layout<N: LayoutNode>(nodes: &[N], edges: &[(usize, usize)], config: Config, orientation: Orientation) -> LayoutResult {
+    layout_with_gutter(nodes, edges, config, orientation, 0.0)
+}
+
+/// Like [`layout()`], with an extra gap of `gutter` between disconnected components on top of
+/// the vertex spacing that already separates them
+pub fn layout_with_gutter<N: LayoutNode>(
+    nodes: &[N],
+    edges: &[(usize, usize)],
+    config: Config,
+    orientation: Orientation,
+    gutter: f64,
+) -> LayoutResult {
+    let mut graph: StableDiGraph<usize, ()> = StableDiGraph::new();
+    let indices: Vec<NodeIndex> = (0..nodes.len()).map(|i| graph.add_node(i)).collect();
+    for &(source, target) in edges {
+        if let (Some(&source_idx), Some(&target_idx)) = (indices.get(source), indices.get(target)) {
+            graph.add_edge(source_idx, target_idx, ());
+        }
+    }
+
+    let mut result = LayoutResult {
+        positions: vec![None; nodes.len()],
+        ranks: vec![None; nodes.len()],
+    };
+    let vertex_spacing = config.vertex_spacing;
+    // Extents of every box across and along the ranks
+    let extents: Vec<(f64, f64)> = nodes
+        .iter()
+        .map(|node| {
+            let (width, height) = node.size();
+            match orientation {
+                Orientation::TopDown | Orientation::BottomUp => (width, height),
+                Orientation::LeftRight | Orientation::RightLeft => (height, width),
+            }
+        })
+        .collect();
+
+    // The engine lays out every weakly connected component on its own, each starting at cross
+    // coordinate 0. Per component: its placed nodes and its extent.
+    let mut components: Vec<(Vec<Placed>, f64)> = Vec::new();
+    for (component, width, _height) in from_graph(&graph).with_config(config).build() {
+        let mut component_extent = width as f64 * vertex_spacing as f64;
+        let mut placed: Vec<Placed> = component
+            .into_iter()
+            .map(|(node_idx, (x, y))| {
+                // The engine places rank r at y = -(r * vertex_spacing)
+                (graph[node_idx], y.unsigned_abs() / vertex_spacing, x as f64)
+            })
+            .collect();
+        separate_within_ranks(&mut placed, &extents);
+        for &(_, _, cross) in &placed {
+            // `width` counts the nodes of the widest rank; coordinate assignment may still
+            // spread a component further, which must not overlap the next one
+            component_extent = component_extent.max(cross + vertex_spacing as f64);
+        }
+        components.push((placed, component_extent));
+    }
+
+    // Ranks are `vertex_spacing` apart unless the boxes of neighbouring ranks need more room
+    let rank_count = components.iter().flat_map(|(placed, _)| placed).map(|&(_, rank, _)| rank + 1).max().unwrap_or(0);
+    let mut rank_extents = vec![0.0f64; rank_count];
+    for &(i, rank, _) in components.iter().flat_map(|(placed, _)| placed) {
+        rank_extents[rank] = rank_extents[rank].max(extents[i].1);
+    }
+    let mut rank_along = vec![0.0; rank_count];
+    for rank in 1..rank_count {
+        let needed = (rank_extents[rank - 1] + rank_extents[rank]) / 2.0 + NODE_GAP;
+        rank_along[rank] = rank_along[rank - 1] + needed.max(vertex_spacing as f64);
+    }
+
+    // Tile the components side by side across the ranks, their boxes at least `NODE_GAP` apart
+    let box_span = |placed: &[Placed]| {
+        placed.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(left, right), &(i, _, cross)| {
+            (left.min(cross - extents[i].0 / 2.0), right.max(cross + extents[i].0 / 2.0))
+        })
+    };
+    let mut component_offset: f64 = 0.0;
+    let mut previous_right: Option<f64> = None;
+    for (placed, component_extent) in components {
+        let (left, right) = box_span(&placed);
+        if let Some(previous_right) = previous_right {
+            component_offset = component_offset.max(previous_right + NODE_GAP + gutter - left);
+        }
+        for (i, rank, cross) in placed {
+            let x = cross + component_offset;
+            result.ranks[i] = Some(rank);
+            let along = rank_along[rank];
+            result.positions[i] = Some(match orientation {
+                Orientation::TopDown => (x, along),
+                Orientation::BottomUp => (x, -along),
+                Orientation::LeftRight => (along, x),
+                Orientation::RightLeft => (-along, x),
+            });
+        }
+        previous_right = Some(right + component_offset);
+        component_offset += component_extent + gutter;
+    }
+    result
+}
+
+// (node, rank, cross coordinate) of a node the engine placed
+type Placed = (usize, usize, f64);
+
+// Move neighbours of each rank apart until their boxes are `NODE_GAP` apart. Pushing every
+// node away from its left neighbours and, separately, from its right neighbours yields two
+// placements that both keep the gaps; their average does too, without drifting to either side.
+// Nodes with enough room keep their coordinates.
+fn separate_within_ranks(placed: &mut [Placed], extents: &[(f64, f64)]) {
+    let mut by_rank: Vec<Vec<usize>> = Vec::new();
+    for (k, &(_, rank, _)) in placed.iter().enumerate() {
+        if by_rank.len() <= rank {
+            by_rank.resize(rank + 1, Vec::new());
+        }
+        by_rank[rank].push(k);
+    }
+
+    for mut rank in by_rank {
+        rank.sort_by(|&a, &b| placed[a].2.total_cmp(&placed[b].2));
+        let separation = |a: usize, b: usize| (extents[placed[a].0].0 + extents[placed[b].0].0) / 2.0 + NODE_GAP;
+        let mut rightwards: Vec<f64> = rank.iter().map(|&k| placed[k].2).collect();
+        let mut leftwards = rightwards.clone();
+        for j in 1..rank.len() {
+            rightwards[j] = rightwards[j].max(rightwards[j - 1] + separation(rank[j - 1], rank[j]));
+        }
+        for j in (0..rank.len().saturating_sub(1)).rev() {
+            leftwards[j] = leftwards[j].min(leftwards[j + 1] - separation(rank[j], rank[j + 1]));
+        }
+        for (j, &k) in rank.iter().enumerate() {
+            placed[k].2 = (rightwards[j] + leftwards[j]) / 2.0;
+        }
+    }
+}
+
+/// Minimal SVG of a laid out graph: straight edges between node centers beneath the nodes
+/// drawn by `renderer`. Nodes and edges without a position are left out.
+pub fn render_svg<N: LayoutNode>(
+    nodes: &[N],
+    edges: &[(usize, usize)],
+    result: &LayoutResult,
+    renderer: &impl NodeRenderer<N>,
+) -> String {
+    let margin = 20.0;
+    let placed = || {
+        nodes
+            .iter()
+            .zip(&result.positions)
+            .filter_map(|(node, position)| position.map(|position| (node, position)))
+    };
+    let (min_x, min_y, max_x, max_y) = placed().fold(
+        (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        |(min_x, min_y, max_x, max_y), (node, (x, y))| {
+            let (width, height) = node.size();
+            (
+                min_x.min(x - width / 2.0),
+                min_y.min(y - height / 2.0),
+                max_x.max(x + width / 2.0),
+                max_y.max(y + height / 2.0),
+            )
+        },
+    );
+    let (min_x, min_y, max_x, max_y) = if min_x.is_finite() { (min_x, min_y, max_x, max_y) } else { (0.0, 0.0, 0.0, 0.0) };
+    let (dx, dy) = (margin - min_x, margin - min_y);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        max_x - min_x + 2.0 * margin,
+        max_y - min_y + 2.0 * margin
+    );
+    for &(source, target) in edges {
+        if let (Some(Some((sx, sy))), Some(Some((tx, ty)))) = (result.positions.get(source), result.positions.get(target)) {
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#888888\" stroke-width=\"2\"/>\n",
+                sx + dx,
+                sy + dy,
+                tx + dx,
+                ty + dy
+            ));
+        }
+    }
+    for (node, (x, y)) in placed() {
+        svg.push_str(&renderer.render_svg(node, x + dx, y + dy));
+        svg.push('\n');
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Node(&'static str);
+
+    impl LayoutNode for Node {
+        fn id(&self) -> &str {
+            self.0
+        }
+
+        fn size(&self) -> (f64, f64) {
+            (40.0, 20.0)
+        }
+    }
+
+    fn config() -> Config {
+        Config { vertex_spacing: 100, ..Config::default() }
+    }
+
+    #[test]
+    fn ranks_follow_the_edges_in_every_orientation() {
+        // a -> b -> c plus a shortcut a -> c
+        let nodes = [Node("a"), Node("b"), Node("c")];
+        let edges = [(0, 1), (1, 2), (0, 2)];
+        for orientation in Orientation::ALL {
+            let result = layout(&nodes, &edges, config(), orientation);
+            assert_eq!(result.ranks, vec![Some(0), Some(1), Some(2)], "{}", orientation);
+
+            let along = |i: usize| {
+                let (x, y) = result.positions[i].unwrap();
+                match orientation {
+                    Orientation::TopDown => y,
+                    Orientation::BottomUp => -y,
+                    Orientation::LeftRight => x,
+                    Orientation::RightLeft => -x,
+                }
+            };
+            assert_eq!(along(1) - along(0), 100.0, "{}", orientation);
+            assert_eq!(along(2) - along(1), 100.0, "{}", orientation);
+        }
+    }
+
+    #[test]
+    fn gutter_separates_components() {
+        // Two single edges, laid out side by side
+        let nodes = [Node("a"), Node("b"), Node("c"), Node("d")];
+        let edges = [(0, 1), (2, 3)];
+        let cross = |result: &LayoutResult, i: usize| result.positions[i].unwrap().0;
+
+        let tight = layout(&nodes, &edges, config(), Orientation::TopDown);
+        let spread = layout_with_gutter(&nodes, &edges, config(), Orientation::TopDown, 250.0);
+        let gap = |result: &LayoutResult| (cross(result, 2) - cross(result, 0)).abs();
+        assert!(gap(&tight) >= 100.0);
+        assert_eq!(gap(&spread) - gap(&tight), 250.0);
+        assert_eq!(spread.ranks, vec![Some(0), Some(1), Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn differently_sized_boxes_keep_apart() {
+        struct Sized(&'static str, f64, f64);
+        impl LayoutNode for Sized {
+            fn id(&self) -> &str {
+                self.0
+            }
+
+            fn size(&self) -> (f64, f64) {
+                (self.1, self.2)
+            }
+        }
+
+        // A wide and a tall node next to each other, both far larger than the vertex spacing
+        let nodes = [Sized("a", 40.0, 20.0), Sized("b", 300.0, 40.0), Sized("c", 40.0, 250.0), Sized("d", 40.0, 20.0)];
+        let edges = [(0, 1), (0, 2), (3, 2)];
+        for orientation in Orientation::ALL {
+            let result = layout(&nodes, &edges, config(), orientation);
+            let rect = |i: usize| {
+                let (x, y) = result.positions[i].unwrap();
+                (x - nodes[i].1 / 2.0, y - nodes[i].2 / 2.0, x + nodes[i].1 / 2.0, y + nodes[i].2 / 2.0)
+            };
+            for a in 0..nodes.len() {
+                for b in a + 1..nodes.len() {
+                    let (ra, rb) = (rect(a), rect(b));
+                    let apart = ra.2 <= rb.0 || rb.2 <= ra.0 || ra.3 <= rb.1 || rb.3 <= ra.1;
+                    assert!(apart, "{} {} and {} overlap: {:?} {:?}", orientation, nodes[a].0, nodes[b].0, ra, rb);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transform_maps_points_there_and_back() {
+        let transform = Transform { tx: 50.0, ty: -20.0, sx: 0.5, sy: 2.0 };
+        assert_eq!(transform.apply((100.0, 10.0)), (100.0, 0.0));
+        assert_eq!(transform.invert((100.0, 0.0)), (100.0, 10.0));
+    }
+
+    #[test]
+    fn renders_placed_nodes_and_edges() {
+        struct Boxes;
+        impl NodeRenderer<Node> for Boxes {
+            fn render_svg(&self, node: &Node, x: f64, y: f64) -> String {
+                format!(r#"<rect id="{}" x="{}" y="{}"/>"#, node.0, x, y)
+            }
+        }
+
+        let nodes = [Node("a"), Node("b")];
+        let edges = [(0, 1)];
+        let mut result = layout(&nodes, &edges, config(), Orientation::TopDown);
+        let svg = render_svg(&nodes, &edges, &result, &Boxes);
+        assert_eq!(svg.matches("<line").count(), 1);
+        assert!(svg.contains(r#"id="a""#) && svg.contains(r#"id="b""#));
+
+        // Unplaced nodes and their edges are left out
+        result.positions[1] = None;
+        let svg = render_svg(&nodes, &edges, &result, &Boxes);
+        assert_eq!(svg.matches("<line").count(), 0);
+        assert!(!svg.contains(r#"id="b""#));
+    }
+}
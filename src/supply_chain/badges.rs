@@ -0,0 +1,100 @@
+//! Badges drawn on the corners of nodes
+
+use std::collections::HashMap;
+
+use super::*;
+
+/// Resolves the badges of one node
+pub type BadgeFn = fn(&TechNode, &Theme) -> Vec<Badge>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Badge {
+    pub shape: BadgeShape,
+    pub color: ColorString,
+    pub corner: Corner,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BadgeShape {
+    Dot,
+    Star,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+pub const BADGE_RADIUS: f64 = 7.0;
+
+/// Badges centered on the corners of the node box, so they stay clear of the padded text.
+/// Further badges of the same corner line up along the horizontal border toward the center.
+pub fn generate_badges_svg(node: &PlacedNode, badges: &[Badge], theme: &Theme) -> String {
+    let (Some(x), Some(y)) = (node.x, node.y) else {
+        return String::new();
+    };
+    let spacing = 2.5 * BADGE_RADIUS;
+    let mut per_corner: HashMap<Corner, usize> = HashMap::new();
+    let mut svg = String::new();
+
+    for badge in badges {
+        let slot = per_corner.entry(badge.corner).or_default();
+        let (side_x, side_y) = match badge.corner {
+            Corner::TopLeft => (-1.0, -1.0),
+            Corner::TopRight => (1.0, -1.0),
+            Corner::BottomLeft => (-1.0, 1.0),
+            Corner::BottomRight => (1.0, 1.0),
+        };
+        let center_x = x + side_x * (node.width / 2.0 - *slot as f64 * spacing);
+        let center_y = y + side_y * node.height / 2.0;
+        *slot += 1;
+
+        let paint = format!(r#"fill="{}" stroke="{}" stroke-width="1""#, badge.color, theme.badge_outline);
+        svg.push_str(&match badge.shape {
+            BadgeShape::Dot => format!(
+                r#"<circle class="badge" cx="{:.2}" cy="{:.2}" r="{}" {paint} />"#,
+                center_x, center_y, BADGE_RADIUS
+            ),
+            BadgeShape::Star => {
+                // Five points alternating between the outer and the inner radius, tip up
+                let points = (0..10u32)
+                    .map(|i| {
+                        let radius = if i.is_multiple_of(2) { BADGE_RADIUS * 1.2 } else { BADGE_RADIUS * 0.5 };
+                        let angle = std::f64::consts::PI * (i as f64 / 5.0 - 0.5);
+                        format!("{:.2},{:.2}", center_x + radius * angle.cos(), center_y + radius * angle.sin())
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!(r#"<polygon class="badge" points="{points}" {paint} />"#)
+            }
+        });
+    }
+
+    svg
+}
+
+/// Badges of `--badges`: a star on goods with abundant supply and a dot on all other ones the
+/// theme has a badge color for, by default only those with scarce supply
+pub fn supply_badges(node: &TechNode, theme: &Theme, corner: Corner) -> Vec<Badge> {
+    let Some(color) = theme.badges.get(&node.supply) else {
+        return Vec::new();
+    };
+    let shape = match node.supply {
+        SupplyLevel::Abundant => BadgeShape::Star,
+        _ => BadgeShape::Dot,
+    };
+    vec![Badge { shape, color: color.clone(), corner }]
+}
+
+/// `supply_badges` placed on `corner`
+pub fn supply_badges_at(corner: Corner) -> BadgeFn {
+    match corner {
+        Corner::TopLeft => |node, theme| supply_badges(node, theme, Corner::TopLeft),
+        Corner::TopRight => |node, theme| supply_badges(node, theme, Corner::TopRight),
+        Corner::BottomLeft => |node, theme| supply_badges(node, theme, Corner::BottomLeft),
+        Corner::BottomRight => |node, theme| supply_badges(node, theme, Corner::BottomRight),
+    }
+}
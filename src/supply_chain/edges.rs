@@ -0,0 +1,295 @@
+//! Edge arrows and labels
+
+use crate::Point;
+use std::borrow::Cow;
+use strum::IntoEnumIterator;
+
+use super::*;
+
+/// Points of `edge` running from its source to its target, so arrows and labels end up at the
+/// target. Routes of reversed edges that were laid out in rank order (starting in the target's
+/// box and ending in the source's) are flipped back. Edges without `points` between two
+/// positioned nodes fall back to a straight line from border to border.
+pub fn logical_points<'a>(edge: &'a PlacedEdge, nodes: &[PlacedNode]) -> Option<Cow<'a, [Point]>> {
+    let Some(points) = edge.points.as_deref() else {
+        return straight_border_line(edge, nodes).map(|line| Cow::Owned(line.to_vec()));
+    };
+    if !edge.reversed {
+        return Some(Cow::Borrowed(points));
+    }
+    let contains = |id: &str, (px, py): Point| {
+        nodes.iter().find(|node| node.id == id).is_some_and(|node| match (node.x, node.y) {
+            (Some(x), Some(y)) => (px - x).abs() <= node.width / 2.0 && (py - y).abs() <= node.height / 2.0,
+            _ => false,
+        })
+    };
+    match (points.first(), points.last()) {
+        (Some(&first), Some(&last))
+            if contains(&edge.target, first) && contains(&edge.source, last) && !contains(&edge.source, first) =>
+        {
+            Some(Cow::Owned(points.iter().rev().copied().collect()))
+        }
+        _ => Some(Cow::Borrowed(points)),
+    }
+}
+
+/// Line between the borders of both endpoints of `edge`, on the line through their centers
+pub(crate) fn straight_border_line(edge: &TechEdge, nodes: &[PlacedNode]) -> Option<[Point; 2]> {
+    let find = |id: &str| nodes.iter().find(|node| node.id == id);
+    let (source, target) = (find(&edge.source)?, find(&edge.target)?);
+    let ((sx, sy), (tx, ty)) = ((source.x?, source.y?), (target.x?, target.y?));
+    if (sx, sy) == (tx, ty) {
+        return None;
+    }
+    Some([
+        calculate_node_border_intersection(sx, sy, source.width, source.height, sx, sy, tx, ty),
+        calculate_node_border_intersection(tx, ty, target.width, target.height, tx, ty, sx, sy),
+    ])
+}
+
+/// Where an edge enters its target: the crossing of the last path segment with the target's
+/// border, plus the outward direction from the target center back along the edge
+pub(crate) fn edge_arrow_tip(target: &PlacedNode, points: &[Point]) -> Option<(Point, Point)> {
+    let (tx, ty) = (target.x?, target.y?);
+    let &[.., (prev_x, prev_y), (end_x, end_y)] = points else {
+        return None;
+    };
+    let tip = calculate_node_border_intersection(tx, ty, target.width, target.height, end_x, end_y, prev_x, prev_y);
+    Some((tip, (prev_x - tx, prev_y - ty)))
+}
+
+/// Size of the edge label box
+pub(crate) const EDGE_LABEL_WIDTH: f64 = 105.0;
+pub(crate) const EDGE_LABEL_HEIGHT: f64 = 60.0;
+
+/// Box of an edge label anchored at `anchor` on the target's border. It is moved out along
+/// `direction` (at least 30px) until it no longer overlaps the target node.
+pub(crate) fn edge_label_rect(anchor: Point, direction: Point, target: &PlacedNode) -> Option<Rect> {
+    let length = (direction.0 * direction.0 + direction.1 * direction.1).sqrt();
+    // Prevent division by zero
+    if length < 0.001 {
+        return None;
+    }
+    let (dir_x, dir_y) = (direction.0 / length, direction.1 / length);
+    let (tx, ty) = (target.x?, target.y?);
+
+    // Distance along the direction after which the label clears the target on one axis
+    let clear_distance = |anchor: f64, center: f64, dir: f64, extent: f64| {
+        if dir.abs() < 1e-9 {
+            f64::INFINITY
+        } else {
+            ((center - anchor) / dir + extent / dir.abs()).max(0.0)
+        }
+    };
+    let clear_x = clear_distance(anchor.0, tx, dir_x, (EDGE_LABEL_WIDTH + target.width) / 2.0);
+    let clear_y = clear_distance(anchor.1, ty, dir_y, (EDGE_LABEL_HEIGHT + target.height) / 2.0);
+    let offset_distance = clear_x.min(clear_y).max(30.0);
+
+    Some(Rect {
+        x: anchor.0 + dir_x * offset_distance - EDGE_LABEL_WIDTH / 2.0,
+        y: anchor.1 + dir_y * offset_distance - EDGE_LABEL_HEIGHT / 2.0,
+        width: EDGE_LABEL_WIDTH,
+        height: EDGE_LABEL_HEIGHT,
+    })
+}
+
+/// Anchor on `edge` (running along `points` from source to target) and box of its label.
+/// Labels at an end sit outside that node, moved out along the path's first or last
+/// segment; `Midpoint` labels are centered on the middle of the drawn path.
+pub(crate) fn edge_label_position(edge: &PlacedEdge, points: &[Point], nodes: &[PlacedNode], placement: LabelPlacement) -> Option<(Point, Rect)> {
+    let find = |id: &str| nodes.iter().find(|node| node.id == id);
+    match placement {
+        LabelPlacement::Target => {
+            let target = find(&edge.target)?;
+            let (anchor, direction) = edge_arrow_tip(target, points)?;
+            Some((anchor, edge_label_rect(anchor, direction, target)?))
+        }
+        LabelPlacement::Source => {
+            let source = find(&edge.source)?;
+            let reversed: Vec<Point> = points.iter().rev().copied().collect();
+            let (anchor, direction) = edge_arrow_tip(source, &reversed)?;
+            Some((anchor, edge_label_rect(anchor, direction, source)?))
+        }
+        LabelPlacement::Midpoint => {
+            let anchor = path_midpoint(&drawn_path(points, edge.path_style))?;
+            let label = Rect {
+                x: anchor.0 - EDGE_LABEL_WIDTH / 2.0,
+                y: anchor.1 - EDGE_LABEL_HEIGHT / 2.0,
+                width: EDGE_LABEL_WIDTH,
+                height: EDGE_LABEL_HEIGHT,
+            };
+            Some((anchor, label))
+        }
+    }
+}
+
+/// Point halfway along a polyline
+pub(crate) fn path_midpoint(path: &[Point]) -> Option<Point> {
+    let length = |(a, b): (Point, Point)| ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+    let segments = || path.windows(2).map(|pair| (pair[0], pair[1]));
+    let mut remaining = segments().map(length).sum::<f64>() / 2.0;
+    for (a, b) in segments() {
+        let segment = length((a, b));
+        if remaining <= segment && segment > 0.0 {
+            let t = remaining / segment;
+            return Some((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t));
+        }
+        remaining -= segment;
+    }
+    path.first().copied()
+}
+
+/// Id of the arrowhead marker of edges with this activity level
+pub(crate) fn arrow_marker_id(activity: &ActivityLevel, config: &RenderConfig) -> String {
+    config.id(&format!("arrow-{}", activity).to_lowercase())
+}
+
+/// One triangular arrowhead marker per activity level, colored like the level. `marker-end`
+/// places its tip on the last point of a path, pointing along the final segment.
+pub fn generate_arrow_markers_svg(config: &RenderConfig) -> String {
+    let mut svg = String::from("<defs>");
+    for activity in ActivityLevel::iter() {
+        svg.push_str(&format!(
+            r#"<marker id="{}" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse"><path d="M0,0 L10,5 L0,10 z" fill="{}" /></marker>"#,
+            arrow_marker_id(&activity, config),
+            config.theme.activity_color(&activity)
+        ));
+    }
+    svg.push_str("</defs>");
+    svg
+}
+
+/// Refactored edge label SVG generator with increased padding
+/// `description` becomes the tooltip of the label, see `edge_description`
+pub fn generate_edge_label_svg(anchor: Point, label: Rect, edge: &TechEdge, description: &str, config: &RenderConfig) -> String {
+    let padding = 8.0;        // Increased padding from 5.0 to 8.0
+    let (x, y) = anchor;
+    let Rect { x: label_x, y: label_y, width: label_width, height: label_height } = label;
+    let (center_x, center_y) = (label_x + label_width / 2.0, label_y + label_height / 2.0);
+
+    // Text styling
+    let font_size = 10;
+    let font_family = config.font_family();
+    let theme = &config.theme;
+    let normal_text_color = theme.label_text.clone();
+    let line_height = 18.0;
+
+    // Background styling
+    let LabelBoxStyle { background, fill_opacity, border_width, corner_radius } = config.label_box;
+    let (background_fill, border_color) = (&theme.label_background, &theme.label_border);
+    let background = if background {
+        format!(
+            r#"<rect
+                x="{label_x:.2}"
+                y="{label_y:.2}"
+                width="{label_width:.2}"
+                height="{label_height:.2}"
+                rx="{corner_radius}"
+                ry="{corner_radius}"
+                fill="{background_fill}"
+                fill-opacity="{fill_opacity}"
+                stroke="{border_color}"
+                stroke-width="{border_width}"
+            />"#
+        )
+    } else {
+        String::new()
+    };
+
+    // Content from edge
+    let cost = edge.cost;
+    let volume = edge.volume;
+    let activity = &edge.activity;
+    let supply = &edge.supply;
+
+    // New fields
+    let distance = edge.distance.unwrap_or(0);
+    let profit = edge.profit.unwrap_or(0);
+
+    // Colors for activity and supply
+    let activity_color = edge.activity_color(theme);
+    let supply_color = edge.supply_color(theme);
+
+    // Profit color (green for positive, red for negative, optionally neutral close to zero)
+    let profit_color = theme.profit.color(profit, config.profit_neutral_band);
+
+    // Caller supplied resolvers take precedence over the defaults above
+    let colors = &config.label_colors;
+    let resolve = |resolver: Option<LabelColorFn>, default: ColorString| resolver.map_or(default, |resolve| resolve(edge));
+
+    // Prepare left and right text content
+    let left_text_lines = vec![
+        (format!("d: {}", distance), resolve(colors.distance, normal_text_color.clone())),
+        (format!("v: {}", volume), resolve(colors.volume, normal_text_color.clone())),
+        (format!("p: {}c", cost), resolve(colors.cost, normal_text_color.clone())),
+    ];
+
+    let right_text_lines = vec![
+         (format!("A: {}", activity), resolve(colors.activity, activity_color)),
+         (format!("S: {}", supply), resolve(colors.supply, supply_color)),
+        (format!("{:+}", profit), resolve(colors.profit, profit_color)),
+    ];
+
+    // Calculate vertical center position with adjustment for 3 lines of text
+    // For perfect vertical centering, we position the middle line at the center
+    // and adjust the first line position accordingly
+    let total_text_height = line_height * 2.0; // Height of 3 lines (with 2 line-height spaces)
+    let vertical_center = label_y + label_height / 2.0;
+    let row1_y = vertical_center - total_text_height / 2.0;
+
+    // Leader line from the label box back to its anchor on the edge, if the anchor lies outside the box
+    let anchor_outside = x < label_x || x > label_x + label_width || y < label_y || y > label_y + label_height;
+    let leader = if config.label_leaders && anchor_outside {
+        let (leader_x, leader_y) = calculate_node_border_intersection(
+            center_x, center_y, label_width, label_height,
+            x, y,
+            center_x, center_y,
+        );
+        format!(
+            r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}" stroke-width="1" stroke-dasharray="2,2" /><circle cx="{:.2}" cy="{:.2}" r="2" fill="{}" />"#,
+            leader_x, leader_y, x, y, border_color, x, y, border_color
+        )
+    } else {
+        String::new()
+    };
+
+    let description = escape_xml(description);
+    format!(
+        r#"<g aria-label="{description}">
+            <title>{description}</title>
+            {leader}
+            <!-- Label background -->
+            {background}
+
+            <!-- Left-aligned text (using multiline text) -->
+            {}
+
+            <!-- Right-aligned text (using multiline text) -->
+            {}
+        </g>"#,
+        generate_multiline_text_svg(
+            label_x + padding,      // x position (left side with increased padding)
+            row1_y,                 // y position (starting from top, adjusted for padding)
+            &left_text_lines,       // text content and colors
+            "start",                // left-aligned text
+            font_family,            // font family
+            font_size,              // font size
+            line_height,            // line spacing
+            Some("middle"),         // middle baseline alignment
+            None,                   // no font size multiplier for first line
+            None,                   // no wrapping
+        ),
+        generate_multiline_text_svg(
+            label_x + label_width - padding,  // x position (right side with increased padding)
+            row1_y,                           // y position (starting from top, adjusted for padding)
+            &right_text_lines,                // text content and colors
+            "end",                            // right-aligned text
+            font_family,                      // font family
+            font_size,                        // font size
+            line_height,                      // line spacing
+            Some("middle"),                   // middle baseline alignment
+            None,                             // no font size multiplier for first line
+            None,                             // no wrapping
+        )
+    )
+}
@@ -0,0 +1,340 @@
+//! Exporters besides SVG: HTML, PNG, DOT, GeoJSON, label regions and the JSON graph document
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::*;
+
+/// Self-contained HTML page showing `svg` with mouse drag panning and wheel zooming.
+/// Zooming honours the `data-min-zoom`/`data-max-zoom` hints of the SVG when present. The page
+/// takes the `page_background` of the `theme` the SVG was drawn with.
+pub fn output_html(svg: &str, theme: &Theme) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Supply chain</title>
+<style>
+  html, body {{ margin: 0; height: 100%; overflow: hidden; background: {}; }}
+  #viewport {{ width: 100%; height: 100%; cursor: grab; }}
+  #viewport.dragging {{ cursor: grabbing; }}
+  #canvas {{ transform-origin: 0 0; }}
+</style>
+</head>
+<body>
+<div id="viewport"><div id="canvas">{}</div></div>
+<script>
+(function () {{
+  const viewport = document.getElementById("viewport");
+  const canvas = document.getElementById("canvas");
+  const svg = canvas.querySelector("svg");
+  const minZoom = parseFloat(svg && svg.dataset.minZoom) || 0.05;
+  const maxZoom = parseFloat(svg && svg.dataset.maxZoom) || 20;
+  let scale = 1, x = 0, y = 0, drag = null;
+
+  function apply() {{
+    canvas.style.transform = "translate(" + x + "px," + y + "px) scale(" + scale + ")";
+  }}
+
+  viewport.addEventListener("mousedown", function (e) {{
+    drag = {{ x: e.clientX - x, y: e.clientY - y }};
+    viewport.classList.add("dragging");
+  }});
+  window.addEventListener("mousemove", function (e) {{
+    if (!drag) return;
+    x = e.clientX - drag.x;
+    y = e.clientY - drag.y;
+    apply();
+  }});
+  window.addEventListener("mouseup", function () {{
+    drag = null;
+    viewport.classList.remove("dragging");
+  }});
+  viewport.addEventListener("wheel", function (e) {{
+    e.preventDefault();
+    const next = Math.min(maxZoom, Math.max(minZoom, scale * Math.exp(-e.deltaY * 0.001)));
+    // Keep the point under the cursor in place
+    x = e.clientX - (e.clientX - x) * next / scale;
+    y = e.clientY - (e.clientY - y) * next / scale;
+    scale = next;
+    apply();
+  }}, {{ passive: false }});
+}})();
+</script>
+</body>
+</html>
+"#,
+        theme.page_background, svg
+    )
+}
+
+/// Errors reported by `render_png`
+#[derive(Debug)]
+pub enum PngError {
+    Svg(resvg::usvg::Error),
+    /// None of the fonts a text asks for (`families`) is installed, so it would be left out
+    MissingFont { families: String },
+    /// `scale` is not positive and finite, or the bitmap is empty or too large to allocate
+    InvalidSize { width: f32, height: f32, scale: f32 },
+    Io(io::Error),
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::Svg(e) => write!(f, "invalid SVG: {}", e),
+            PngError::MissingFont { families } => {
+                write!(f, "no installed font matches '{}', see `RenderConfig::font_family`", families)
+            }
+            PngError::InvalidSize { width, height, scale } => {
+                write!(f, "can't rasterize a {}x{} drawing at scale {}", width, height, scale)
+            }
+            PngError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PngError {}
+
+/// Rasterize an SVG from `output_svg` into a PNG file at `path`. `scale` is the number of
+/// pixels per SVG unit, 1.0 matching 96 DPI. Texts are drawn with the system fonts; a text
+/// whose font family isn't installed is an error rather than missing from the image.
+pub fn render_png(svg: &str, path: &Path, scale: f32) -> Result<(), PngError> {
+    use resvg::{tiny_skia, usvg};
+
+    // usvg falls back to the serif family and leaves out texts without any font, so note the
+    // first font stack none of whose families is installed
+    let missing_font: Mutex<Option<String>> = Mutex::new(None);
+    let select_font = usvg::FontResolver::default_font_selector();
+    let mut options = usvg::Options {
+        font_resolver: usvg::FontResolver {
+            select_font: Box::new(|font, fontdb| {
+                let installed = font.families().iter().any(|family| {
+                    let family = match family {
+                        usvg::FontFamily::Serif => usvg::fontdb::Family::Serif,
+                        usvg::FontFamily::SansSerif => usvg::fontdb::Family::SansSerif,
+                        usvg::FontFamily::Cursive => usvg::fontdb::Family::Cursive,
+                        usvg::FontFamily::Fantasy => usvg::fontdb::Family::Fantasy,
+                        usvg::FontFamily::Monospace => usvg::fontdb::Family::Monospace,
+                        usvg::FontFamily::Named(name) => usvg::fontdb::Family::Name(name),
+                    };
+                    fontdb.query(&usvg::fontdb::Query { families: &[family], ..usvg::fontdb::Query::default() }).is_some()
+                });
+                let id = select_font(font, fontdb);
+                if id.is_none() || !installed {
+                    let families: Vec<String> = font.families().iter().map(|family| family.to_string()).collect();
+                    missing_font.lock().unwrap().get_or_insert(families.join(", "));
+                }
+                id
+            }),
+            ..usvg::FontResolver::default()
+        },
+        ..usvg::Options::default()
+    };
+    options.fontdb_mut().load_system_fonts();
+    set_generic_families(options.fontdb_mut());
+    let tree = usvg::Tree::from_str(svg, &options).map_err(PngError::Svg)?;
+    drop(options);
+    if let Some(families) = missing_font.into_inner().unwrap() {
+        return Err(PngError::MissingFont { families });
+    }
+
+    let size = tree.size();
+    let invalid_size = || PngError::InvalidSize { width: size.width(), height: size.height(), scale };
+    if !(scale.is_finite() && scale > 0.0) {
+        return Err(invalid_size());
+    }
+    let (width, height) = ((size.width() * scale).ceil(), (size.height() * scale).ceil());
+    let mut pixmap = tiny_skia::Pixmap::new(width as u32, height as u32).ok_or_else(invalid_size)?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+
+    let png = pixmap.encode_png().map_err(|e| PngError::Io(io::Error::other(e)))?;
+    fs::write(path, png).map_err(PngError::Io)
+}
+
+/// fontdb maps the generic families to fonts of Windows and macOS ("Arial", "Times New Roman",
+/// "Courier New"). Point each at the first installed one of some common fonts, or else at any
+/// installed face, so the generic fallback of a font stack finds a font on other systems, too.
+pub(crate) fn set_generic_families(fontdb: &mut resvg::usvg::fontdb::Database) {
+    let installed: Vec<(String, bool)> =
+        fontdb.faces().filter_map(|face| Some((face.families.first()?.0.clone(), face.monospaced))).collect();
+    let pick = |common: &[&str], monospaced: bool| {
+        common
+            .iter()
+            .find(|family| installed.iter().any(|(name, _)| name == *family))
+            .map(|family| family.to_string())
+            .or_else(|| installed.iter().find(|(_, mono)| *mono == monospaced).or(installed.first()).map(|(name, _)| name.clone()))
+    };
+
+    if let Some(family) = pick(&["Arial", "Helvetica", "Liberation Sans", "DejaVu Sans", "Noto Sans"], false) {
+        fontdb.set_sans_serif_family(family);
+    }
+    if let Some(family) = pick(&["Times New Roman", "Liberation Serif", "DejaVu Serif", "Noto Serif"], false) {
+        fontdb.set_serif_family(family);
+    }
+    if let Some(family) = pick(&["Courier New", "Liberation Mono", "DejaVu Sans Mono", "Noto Sans Mono"], true) {
+        fontdb.set_monospace_family(family);
+    }
+}
+
+/// File format of `load_graph` and `save_graph`
+#[derive(Serialize, Deserialize)]
+pub(crate) struct GraphDocument {
+    pub(crate) nodes: Vec<TechNode>,
+    pub(crate) edges: Vec<TechEdge>,
+}
+
+/// Read a `{ "nodes": [...], "edges": [...] }` document. Malformed JSON and edges between
+/// unknown nodes are reported as `InvalidData`.
+pub fn load_graph(path: &Path) -> io::Result<(Vec<TechNode>, Vec<TechEdge>)> {
+    let json = fs::read_to_string(path)?;
+    let document: GraphDocument = serde_json::from_str(&json)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+
+    let ids: HashSet<&str> = document.nodes.iter().map(|node| node.id.as_str()).collect();
+    for edge in &document.edges {
+        if let Some(missing) = [&edge.source, &edge.target].into_iter().find(|id| !ids.contains(id.as_str())) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: edge '{}' -> '{}' refers to unknown node '{}'", path.display(), edge.source, edge.target, missing),
+            ));
+        }
+    }
+
+    Ok((document.nodes, document.edges))
+}
+
+/// Write the graph as read by `load_graph`, with the positions and routed points of `layout` as
+/// last known ones, so a laid out graph can be rendered again without recomputing the layout
+pub fn save_graph(path: &Path, nodes: &[TechNode], edges: &[TechEdge], layout: &LayoutResult) -> io::Result<()> {
+    let nodes = place_nodes(nodes, layout)
+        .into_iter()
+        .map(|node| TechNode { x: node.x, y: node.y, ..node.node.clone() })
+        .collect();
+    let edges = place_edges(edges, layout)
+        .into_iter()
+        .map(|edge| TechEdge { points: edge.points, path_style: edge.path_style, ..edge.edge.clone() })
+        .collect();
+    let document = GraphDocument { nodes, edges };
+    let json = serde_json::to_string_pretty(&document).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+/// GraphViz `digraph` of the graph, e.g. for the `layout_engine` binary: nodes labeled with
+/// name and waypoint and filled by waypoint type, edges labeled with their cost. Fills come
+/// from the light theme as DOT renderers draw black text by default.
+pub fn to_dot(nodes: &[TechNode], edges: &[TechEdge]) -> String {
+    let theme = Theme::light();
+    let mut dot = String::from("digraph G {\n    rankdir=LR;\n    node [shape=box, style=filled];\n");
+    for node in nodes {
+        dot.push_str(&format!(
+            "    {} [label={}, fillcolor={}];\n",
+            dot_string(&node.id),
+            dot_string(&format!("{}\n{}", node.name, node.waypoint_symbol)),
+            dot_string(&theme.node_fill(&node.waypoint_type).to_string())
+        ));
+    }
+    for edge in edges {
+        dot.push_str(&format!(
+            "    {} -> {} [label={}];\n",
+            dot_string(&edge.source),
+            dot_string(&edge.target),
+            dot_string(&edge.cost.to_string())
+        ));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Quoted DOT string; line breaks become `\n` escapes
+pub(crate) fn dot_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// GeoJSON FeatureCollection of `layout`: nodes as `Point`s and edges as `LineString`s through
+/// their routed points (curves flattened), both carrying their attributes as properties.
+/// Coordinates are layout coordinates in a planar CRS, with y growing downwards as in the SVG.
+/// Features without coordinates keep a `null` geometry.
+pub fn to_geojson(nodes: &[TechNode], edges: &[TechEdge], layout: &LayoutResult) -> String {
+    use serde_json::{Value, json};
+
+    let properties = |value: Value, omit: &[&str]| match value {
+        Value::Object(mut map) => {
+            for key in omit {
+                map.remove(*key);
+            }
+            Value::Object(map)
+        }
+        other => other,
+    };
+
+    let (nodes, edges) = (place_nodes(nodes, layout), place_edges(edges, layout));
+    let node_features = nodes.iter().map(|node| {
+        let geometry = match (node.x, node.y) {
+            (Some(x), Some(y)) => json!({ "type": "Point", "coordinates": [x, y] }),
+            _ => Value::Null,
+        };
+        json!({
+            "type": "Feature",
+            "id": node.id,
+            "geometry": geometry,
+            "properties": properties(json!(node.node), &["x", "y"]),
+        })
+    });
+    let edge_features = edges.iter().map(|edge| {
+        let geometry = match edge.points {
+            Some(ref points) if points.len() >= 2 => {
+                let coordinates: Vec<[f64; 2]> =
+                    drawn_path(points, edge.path_style).into_iter().map(|(x, y)| [x, y]).collect();
+                json!({ "type": "LineString", "coordinates": coordinates })
+            }
+            _ => Value::Null,
+        };
+        json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": properties(json!(edge.edge), &["points", "path_style"]),
+        })
+    });
+
+    json!({
+        "type": "FeatureCollection",
+        "features": node_features.chain(edge_features).collect::<Vec<_>>(),
+    })
+    .to_string()
+}
+
+/// JSON document of `svg_node_rects` and `svg_edge_rects`, to lay tooltips over the SVG drawn
+/// with `config`
+pub fn to_regions_json(nodes: &[TechNode], edges: &[TechEdge], layout: &LayoutResult, config: &RenderConfig) -> String {
+    use serde_json::json;
+
+    let rect = |rect: Rect| json!({ "x": rect.x, "y": rect.y, "width": rect.width, "height": rect.height });
+    let node_regions: Vec<_> = svg_node_rects(nodes, edges, layout, config)
+        .into_iter()
+        .map(|(id, region)| json!({ "id": id, "rect": rect(region) }))
+        .collect();
+    let edge_regions: Vec<_> = svg_edge_rects(nodes, edges, layout, config)
+        .into_iter()
+        .map(|((source, target), region)| json!({ "source": source, "target": target, "rect": rect(region) }))
+        .collect();
+
+    json!({ "nodes": node_regions, "edges": edge_regions }).to_string()
+}
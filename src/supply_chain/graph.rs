@@ -0,0 +1,416 @@
+//! Graph analysis on the nodes and edges before layout: ranks, cycles, depths, reduction and components
+
+use crate::Point;
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+/// Mirror a computed layout of `nodes` and `edges` along its diagonal by swapping x and y of all
+/// positions and routes, then recompute the bounds. Turns a LeftRight layout into a TopDown one
+/// (and back) without running the layout again.
+pub fn transpose_layout(nodes: &[TechNode], edges: &[TechEdge], layout: &LayoutResult) -> LayoutResult {
+    let swap = |&(x, y): &Point| (y, x);
+    let mut transposed = LayoutResult {
+        positions: layout.positions.iter().map(|(id, position)| (id.clone(), swap(position))).collect(),
+        ranks: layout.ranks.clone(),
+        edge_paths: layout
+            .edge_paths
+            .iter()
+            .map(|(pair, paths)| {
+                let paths = paths
+                    .iter()
+                    .map(|path| EdgePath { points: path.points.iter().map(swap).collect(), ..path.clone() })
+                    .collect();
+                (pair.clone(), paths)
+            })
+            .collect(),
+        bounds: (0.0, 0.0, 0.0, 0.0),
+    };
+    transposed.bounds = LayoutResult::new(&place_nodes(nodes, &transposed), &place_edges(edges, &transposed)).bounds;
+    transposed
+}
+
+/// Distance range of a node from the sources (nodes without incoming edges), counted in edges
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DepthRange {
+    /// Fewest steps from any source
+    pub(crate) min: usize,
+    /// Most steps along any path from a source
+    pub(crate) max: usize,
+}
+
+/// Ranks for the nodes of a DAG given as weighted (from, to) edges that keep the total of weight
+/// times rank span small, so heavy edges stay short at the expense of light ones. Starts from
+/// longest path ranks and moves one node at a time to the end of its feasible range (one rank
+/// past its predecessors, one before its successors) that its heavier side pulls it to, until no
+/// move shortens the total. None if the edges contain a cycle.
+pub(crate) fn weighted_ranks(node_count: usize, edges: &[(usize, usize, f64)]) -> Option<Vec<usize>> {
+    let mut incoming: Vec<Vec<(usize, f64)>> = vec![Vec::new(); node_count];
+    let mut outgoing: Vec<Vec<(usize, f64)>> = vec![Vec::new(); node_count];
+    for &(from, to, weight) in edges {
+        outgoing[from].push((to, weight));
+        incoming[to].push((from, weight));
+    }
+
+    // Topological order (Kahn)
+    let mut pending: Vec<usize> = incoming.iter().map(Vec::len).collect();
+    let mut order: Vec<usize> = (0..node_count).filter(|&v| pending[v] == 0).collect();
+    let mut next = 0;
+    while next < order.len() {
+        for &(to, _) in &outgoing[order[next]] {
+            pending[to] -= 1;
+            if pending[to] == 0 {
+                order.push(to);
+            }
+        }
+        next += 1;
+    }
+    if order.len() < node_count {
+        return None;
+    }
+
+    let mut ranks = vec![0; node_count];
+    for &v in &order {
+        ranks[v] = incoming[v].iter().map(|&(from, _)| ranks[from] + 1).max().unwrap_or(0);
+    }
+
+    // Each move shortens the weighted total by at least one unit of the pulling weight
+    let mut moved = true;
+    while moved {
+        moved = false;
+        for &v in &order {
+            let pull_back: f64 = incoming[v].iter().map(|&(_, weight)| weight).sum();
+            let pull_ahead: f64 = outgoing[v].iter().map(|&(_, weight)| weight).sum();
+            let rank = if pull_back > pull_ahead {
+                incoming[v].iter().map(|&(from, _)| ranks[from] + 1).max()
+            } else if pull_ahead > pull_back {
+                outgoing[v].iter().map(|&(to, _)| ranks[to] - 1).min()
+            } else {
+                None
+            };
+            if let Some(rank) = rank
+                && rank != ranks[v]
+            {
+                ranks[v] = rank;
+                moved = true;
+            }
+        }
+    }
+    Some(ranks)
+}
+
+/// Edges closing a cycle, found by a depth-first search in node order.
+/// Ignoring them leaves an acyclic graph in which every node is reachable from a source.
+pub(crate) fn find_back_edges(nodes: &[TechNode], edges: &[TechEdge]) -> HashSet<usize> {
+    let index: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, node)| (node.id.as_str(), i)).collect();
+    let mut outgoing: Vec<Vec<(usize, usize)>> = vec![Vec::new(); nodes.len()];
+    for (edge_index, edge) in edges.iter().enumerate() {
+        if let (Some(&source), Some(&target)) = (index.get(edge.source.as_str()), index.get(edge.target.as_str())) {
+            outgoing[source].push((edge_index, target));
+        }
+    }
+
+    // 0 = unvisited, 1 = on the current path, 2 = finished
+    let mut state = vec![0u8; nodes.len()];
+    let mut back_edges = HashSet::new();
+
+    for root in 0..nodes.len() {
+        if state[root] != 0 {
+            continue;
+        }
+        state[root] = 1;
+        let mut stack = vec![(root, 0)];
+
+        while let Some((node, next)) = stack.last_mut() {
+            if let Some(&(edge_index, target)) = outgoing[*node].get(*next) {
+                *next += 1;
+                match state[target] {
+                    0 => {
+                        state[target] = 1;
+                        stack.push((target, 0));
+                    }
+                    1 => {
+                        back_edges.insert(edge_index);
+                    }
+                    _ => {}
+                }
+            } else {
+                state[*node] = 2;
+                stack.pop();
+            }
+        }
+    }
+
+    back_edges
+}
+
+/// Minimum and maximum distance of every node from any source.
+/// Edges closing a cycle are ignored, so nodes on a cycle count from where the cycle is entered.
+pub(crate) fn source_depths(nodes: &[TechNode], edges: &[TechEdge]) -> HashMap<String, DepthRange> {
+    let index: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, node)| (node.id.as_str(), i)).collect();
+    let back_edges = find_back_edges(nodes, edges);
+
+    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    let mut in_degree = vec![0usize; nodes.len()];
+    for (edge_index, edge) in edges.iter().enumerate() {
+        if back_edges.contains(&edge_index) {
+            continue;
+        }
+        if let (Some(&source), Some(&target)) = (index.get(edge.source.as_str()), index.get(edge.target.as_str())) {
+            outgoing[source].push(target);
+            in_degree[target] += 1;
+        }
+    }
+
+    // Relax depths in topological order: the first arrival is the shortest path, the last the longest
+    let mut depths: Vec<Option<DepthRange>> = in_degree
+        .iter()
+        .map(|&degree| (degree == 0).then_some(DepthRange { min: 0, max: 0 }))
+        .collect();
+    let mut queue: std::collections::VecDeque<usize> = (0..nodes.len()).filter(|&i| in_degree[i] == 0).collect();
+
+    while let Some(node) = queue.pop_front() {
+        let depth = depths[node].expect("queued nodes have a depth");
+        for &target in &outgoing[node] {
+            let candidate = DepthRange { min: depth.min + 1, max: depth.max + 1 };
+            depths[target] = Some(match depths[target] {
+                Some(existing) => DepthRange {
+                    min: existing.min.min(candidate.min),
+                    max: existing.max.max(candidate.max),
+                },
+                None => candidate,
+            });
+            in_degree[target] -= 1;
+            if in_degree[target] == 0 {
+                queue.push_back(target);
+            }
+        }
+    }
+
+    nodes
+        .iter()
+        .zip(depths)
+        .filter_map(|(node, depth)| depth.map(|depth| (node.id.clone(), depth)))
+        .collect()
+}
+
+/// Drops edges that are implied by a longer path (A→C when A→B→C exists).
+/// Meant for DAGs: edges closing a cycle are left alone and do not count as paths.
+/// Parallel edges between the same two nodes are all kept.
+pub fn transitive_reduction(nodes: &[TechNode], edges: &[TechEdge]) -> (Vec<TechNode>, Vec<TechEdge>) {
+    let index: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, node)| (node.id.as_str(), i)).collect();
+    let back_edges = find_back_edges(nodes, edges);
+
+    let mut outgoing: Vec<HashSet<usize>> = vec![HashSet::new(); nodes.len()];
+    let mut in_degree = vec![0usize; nodes.len()];
+    for (edge_index, edge) in edges.iter().enumerate() {
+        if back_edges.contains(&edge_index) {
+            continue;
+        }
+        if let (Some(&source), Some(&target)) = (index.get(edge.source.as_str()), index.get(edge.target.as_str()))
+            && outgoing[source].insert(target)
+        {
+            in_degree[target] += 1;
+        }
+    }
+
+    let mut topological = Vec::with_capacity(nodes.len());
+    let mut queue: std::collections::VecDeque<usize> = (0..nodes.len()).filter(|&i| in_degree[i] == 0).collect();
+    while let Some(node) = queue.pop_front() {
+        topological.push(node);
+        for &target in &outgoing[node] {
+            in_degree[target] -= 1;
+            if in_degree[target] == 0 {
+                queue.push_back(target);
+            }
+        }
+    }
+
+    // Everything reachable from a node, filled sinks first
+    let mut reachable: Vec<HashSet<usize>> = vec![HashSet::new(); nodes.len()];
+    for &node in topological.iter().rev() {
+        let mut reach = HashSet::new();
+        for &target in &outgoing[node] {
+            reach.insert(target);
+            reach.extend(reachable[target].iter().copied());
+        }
+        reachable[node] = reach;
+    }
+
+    let reduced_edges = edges
+        .iter()
+        .enumerate()
+        .filter(|(edge_index, edge)| {
+            if back_edges.contains(edge_index) {
+                return true;
+            }
+            let (Some(&source), Some(&target)) = (index.get(edge.source.as_str()), index.get(edge.target.as_str())) else {
+                return true;
+            };
+            !outgoing[source]
+                .iter()
+                .any(|&via| via != target && reachable[via].contains(&target))
+        })
+        .map(|(_, edge)| edge.clone())
+        .collect();
+
+    (nodes.to_vec(), reduced_edges)
+}
+
+/// Replaces the members of every collapsed group with one summary node carrying the group's id.
+/// Edges between a member and the outside are rerouted to the summary node and merged with
+/// `sum_parallel_edges` where they end up parallel; edges inside the group are dropped.
+/// Expanded groups leave their members untouched. Fails if the id of a collapsed group is
+/// already the id of a node, which the summary node would silently merge with.
+pub(crate) fn collapse_groups(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    groups: &[NodeGroup],
+) -> Result<(Vec<TechNode>, Vec<TechEdge>), LayoutError> {
+    let mut group_of: HashMap<&str, &NodeGroup> = HashMap::new();
+    for group in groups.iter().filter(|group| group.collapsed) {
+        if nodes.iter().any(|node| node.id == group.id) {
+            return Err(LayoutError::GroupIdTaken(group.id.clone()));
+        }
+        for member in &group.members {
+            group_of.entry(member.as_str()).or_insert(group);
+        }
+    }
+
+    let mut collapsed_nodes = Vec::new();
+    let mut summarized: HashSet<&str> = HashSet::new();
+    for node in nodes {
+        let Some(group) = group_of.get(node.id.as_str()) else {
+            collapsed_nodes.push(node.clone());
+            continue;
+        };
+        if !summarized.insert(group.id.as_str()) {
+            continue;
+        }
+        // The first member found stands in for the box size and levels of the group
+        let members: Vec<&TechNode> = nodes
+            .iter()
+            .filter(|member| group_of.get(member.id.as_str()).is_some_and(|g| g.id == group.id))
+            .collect();
+        collapsed_nodes.push(TechNode {
+            id: group.id.clone(),
+            name: group.name.clone(),
+            cost: (members.iter().map(|member| member.cost as u64).sum::<u64>() / members.len() as u64) as u32,
+            volume: members.iter().fold(0u32, |total, member| total.saturating_add(member.volume)),
+            x: None,
+            y: None,
+            label: Some(format!("{}\n{} goods", group.name, members.len())),
+            icon_href: None,
+            ..node.clone()
+        });
+    }
+
+    let endpoint = |id: &str| group_of.get(id).map_or(id.to_string(), |group| group.id.clone());
+    let mut rerouted: Vec<((String, String), Vec<&TechEdge>)> = Vec::new();
+    for edge in edges {
+        let pair = (endpoint(&edge.source), endpoint(&edge.target));
+        if pair.0 == pair.1 && group_of.contains_key(edge.source.as_str()) {
+            continue;
+        }
+        match rerouted.iter_mut().find(|(seen, _)| *seen == pair) {
+            Some((_, parallel)) => parallel.push(edge),
+            None => rerouted.push((pair, vec![edge])),
+        }
+    }
+
+    let collapsed_edges = rerouted
+        .into_iter()
+        .flat_map(|((source, target), parallel)| {
+            let touches_group = [&source, &target].iter().any(|id| summarized.contains(id.as_str()));
+            let aggregated: Vec<TechEdge> = if touches_group {
+                vec![sum_parallel_edges(&parallel)]
+            } else {
+                parallel.into_iter().cloned().collect()
+            };
+            aggregated.into_iter().map(move |edge| TechEdge {
+                source: source.clone(),
+                target: target.clone(),
+                points: None,
+                ..edge
+            })
+        })
+        .collect();
+
+    Ok((collapsed_nodes, collapsed_edges))
+}
+
+/// Ids of all nodes `id` transitively depends on, i.e. everything upstream of it
+pub fn ancestors(nodes: &[TechNode], edges: &[TechEdge], id: &str) -> HashSet<String> {
+    reachable_from(nodes, edges, id, |edge| (edge.target.as_str(), edge.source.as_str()))
+}
+
+/// Ids of all nodes transitively depending on `id`, i.e. everything downstream of it
+pub fn descendants(nodes: &[TechNode], edges: &[TechEdge], id: &str) -> HashSet<String> {
+    reachable_from(nodes, edges, id, |edge| (edge.source.as_str(), edge.target.as_str()))
+}
+
+/// Nodes reachable from `start` following edges read as `(from, to)` by `direction`.
+/// `start` itself only belongs to the result when it lies on a cycle.
+pub(crate) fn reachable_from<'a>(
+    nodes: &[TechNode],
+    edges: &'a [TechEdge],
+    start: &str,
+    direction: fn(&'a TechEdge) -> (&'a str, &'a str),
+) -> HashSet<String> {
+    let known: HashSet<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+    let mut next: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (from, to) in edges.iter().map(direction) {
+        if known.contains(from) && known.contains(to) {
+            next.entry(from).or_default().push(to);
+        }
+    }
+
+    let mut reached = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(current) = stack.pop() {
+        for &to in next.get(current).into_iter().flatten() {
+            if reached.insert(to.to_string()) {
+                stack.push(to);
+            }
+        }
+    }
+    reached
+}
+
+/// Weakly connected component of every node, keyed by node id.
+/// Components are numbered 0.. in the order their first node appears in `nodes`.
+pub fn components(nodes: &[TechNode], edges: &[TechEdge]) -> HashMap<String, usize> {
+    let index: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, node)| (node.id.as_str(), i)).collect();
+    let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    for edge in edges {
+        if let (Some(&source), Some(&target)) = (index.get(edge.source.as_str()), index.get(edge.target.as_str())) {
+            neighbours[source].push(target);
+            neighbours[target].push(source);
+        }
+    }
+
+    let mut component_of: Vec<Option<usize>> = vec![None; nodes.len()];
+    let mut count = 0;
+    for root in 0..nodes.len() {
+        if component_of[root].is_some() {
+            continue;
+        }
+        component_of[root] = Some(count);
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            for &neighbour in &neighbours[node] {
+                if component_of[neighbour].is_none() {
+                    component_of[neighbour] = Some(count);
+                    stack.push(neighbour);
+                }
+            }
+        }
+        count += 1;
+    }
+
+    nodes
+        .iter()
+        .zip(component_of)
+        .map(|(node, component)| (node.id.clone(), component.expect("every node is visited")))
+        .collect()
+}
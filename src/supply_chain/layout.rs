@@ -0,0 +1,1334 @@
+//! Options, results and the pipeline laying out a supply chain with rust-sugiyama
+
+use crate::{layout, layout_with_gutter, Config, LayoutNode, Orientation, Point};
+use rust_sugiyama::configure::{CrossingMinimization, RankingType};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::*;
+
+/// What the layout engine places: an input node, the members of a same rank group placed as
+/// one node side by side across the rank axis, or one of the waypoints long edges are routed through
+pub(crate) enum EngineNode<'a> {
+    Node(&'a TechNode),
+    Group(Vec<&'a TechNode>, RankAxis),
+    Waypoint,
+}
+
+impl LayoutNode for EngineNode<'_> {
+    fn id(&self) -> &str {
+        match self {
+            EngineNode::Node(node) => node.id(),
+            EngineNode::Group(members, _) => members.first().map_or("", |node| node.id()),
+            EngineNode::Waypoint => "",
+        }
+    }
+
+    fn size(&self) -> (f64, f64) {
+        match self {
+            EngineNode::Node(node) => node.size(),
+            EngineNode::Group(members, axis) => {
+                let gaps = members.len().saturating_sub(1) as f64 * GROUP_GAP;
+                let (across, along) = members
+                    .iter()
+                    .map(|node| cross_and_rank_extent(node, *axis))
+                    .fold((gaps, 0.0), |(across, along), (a, b)| (across + a, f64::max(along, b)));
+                match axis {
+                    RankAxis::X => (along, across),
+                    RankAxis::Y => (across, along),
+                }
+            }
+            EngineNode::Waypoint => (0.0, 0.0),
+        }
+    }
+}
+
+/// Gap between the boxes of neighbouring members of a same rank group
+pub(crate) const GROUP_GAP: f64 = 100.0;
+
+/// Extent of the box of `node` across the ranks and along them
+pub(crate) fn cross_and_rank_extent(node: &TechNode, axis: RankAxis) -> (f64, f64) {
+    match axis {
+        RankAxis::X => (node.height, node.width),
+        RankAxis::Y => (node.width, node.height),
+    }
+}
+
+/// An engine node measured in engine units: its box divided by the (x, y) scaling applied to the
+/// engine's coordinates afterwards, so the boxes the engine keeps apart are the drawn ones
+pub(crate) struct EngineBox<'a>(&'a EngineNode<'a>, Point);
+
+impl LayoutNode for EngineBox<'_> {
+    fn id(&self) -> &str {
+        self.0.id()
+    }
+
+    fn size(&self) -> (f64, f64) {
+        let (width, height) = self.0.size();
+        (width / self.1.0, height / self.1.1)
+    }
+}
+
+/// What to do with edges whose endpoints didn't get coordinates from the layout run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum UnpositionedEdges {
+    /// Route to coordinates already present on the input node (e.g. from a previous run)
+    #[default]
+    UseLastKnown,
+    /// Only route between freshly positioned nodes; clear the points of all other edges
+    Report,
+}
+
+/// How `build_supply_chain_layout` connects the end nodes of an edge
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EdgeRouting {
+    /// A curve bent through one control point, or through the waypoints of long edges
+    #[default]
+    Curved,
+    /// Right angles only: one bend between adjacent ranks, long edges run along the channel
+    /// the layout reserves for them (see `orthogonal_route`)
+    Orthogonal,
+    /// Right angles through a lane of its own in the gap between two ranks for every edge
+    /// (see `route_edges_in_channels`); edges within a rank stay curved
+    Channels,
+}
+
+/// Edge field that weighs an edge in ranking, crossing minimization and edge shortening, so heavy
+/// trade routes stay short and straight at the expense of light ones. rust-sugiyama itself ranks
+/// and orders with a unit weight per edge and ignores it: ranking honours the weight only through
+/// the pre-ranking in `weighted_ranks`, ordering only through `reduce_weighted_crossings`.
+#[derive(Clone, Copy, Debug)]
+pub enum WeightBy {
+    Cost,
+    Volume,
+    Distance,
+    /// Losses weigh like the smallest profit
+    Profit,
+    /// Layout weight computed by the caller, independent of the displayed `cost`, e.g. a constant
+    Custom(fn(&TechEdge) -> u32),
+}
+
+impl WeightBy {
+    /// At least 1, so edges missing the field still count
+    pub fn weight(self, edge: &TechEdge) -> f64 {
+        let weight = match self {
+            WeightBy::Cost => edge.cost as f64,
+            WeightBy::Volume => edge.volume as f64,
+            WeightBy::Distance => edge.distance.unwrap_or(0) as f64,
+            WeightBy::Profit => edge.profit.unwrap_or(0) as f64,
+            WeightBy::Custom(weight) => weight(edge) as f64,
+        };
+        weight.max(1.0)
+    }
+}
+
+/// Command line names; `uniform` weighs every edge 1, refining crossings without preferring any
+impl FromStr for WeightBy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "cost" => Ok(WeightBy::Cost),
+            "volume" => Ok(WeightBy::Volume),
+            "distance" => Ok(WeightBy::Distance),
+            "profit" => Ok(WeightBy::Profit),
+            "uniform" => Ok(WeightBy::Custom(|_| 1)),
+            _ => Err(format!("unknown edge weight '{}', expected one of cost, volume, distance, profit, uniform", name)),
+        }
+    }
+}
+
+/// Options for `build_supply_chain_layout` beyond orientation and scaling
+#[derive(Clone, Debug, Default)]
+pub struct LayoutOptions {
+    pub unpositioned_edges: UnpositionedEdges,
+    pub edge_routing: EdgeRouting,
+    /// Reorder nodes within their rank by a caller supplied key after crossing minimization
+    pub secondary_order: Option<SecondaryOrder>,
+    /// Line up the inner nodes of simple chains (one edge in, one out) between the chain's ends
+    pub straighten_chains: bool,
+    /// Pull nodes across their rank toward the median of their neighbours to shorten edges
+    pub minimize_edge_length: bool,
+    /// Weigh edges by this field instead of counting every edge once: in ranking before the
+    /// engine (see `weighted_ranks`), in a crossing refinement after it (see
+    /// `reduce_weighted_crossings`) and, if enabled, in `minimize_edge_length`
+    pub weight_by: Option<WeightBy>,
+    /// Insert nodes into the layout graph sorted by this key, which biases the initial
+    /// within-rank order (smaller keys first) before crossing minimization refines it
+    pub initial_order: Option<fn(&TechNode) -> i64>,
+    /// Areas edges are routed around, e.g. a legend or title block, in the coordinate space
+    /// of the SVG emitted by `output_svg` (see `svg_node_rects`)
+    pub obstacles: Vec<Rect>,
+    /// `RenderConfig::label_placement` of that SVG, whose labels move its coordinate space and
+    /// decide which gaps `fit_edge_labels` widens
+    pub label_placement: LabelPlacement,
+    /// Sides of a node edges should enter and leave it by, overriding the direct connection.
+    /// Applies to the default curved routing; contradicting preferences still yield a
+    /// (more strongly bent) curve.
+    pub preferred_sides: Option<fn(&TechNode) -> PreferredSides>,
+    /// Measure phase durations and collect counts into `SupplyChainLayout::metrics`
+    pub collect_metrics: bool,
+    /// Lay out (and return) only the essential edges, see `transitive_reduction`.
+    /// Off by default, which retains redundant edges.
+    pub transitive_reduction: bool,
+    /// Node groups, of which the collapsed ones are laid out as one summary node (see `collapse_groups`)
+    pub groups: Vec<NodeGroup>,
+    /// Distance between neighbouring nodes and ranks before scaling; defaults to the largest
+    /// node extent plus a gap of 100
+    pub vertex_spacing: Option<usize>,
+    /// Ranks an edge spans at least; defaults to 1
+    pub minimum_length: Option<u32>,
+    /// Skip the transpose refinement of crossing minimization, e.g. for interactive previews
+    pub quick_crossing_reduction: bool,
+    /// Sweeps of the crossing refinement after the engine (see `reduce_weighted_crossings`),
+    /// which also runs without `weight_by` once this or `improvement_epsilon` is set; defaults
+    /// to sweeping until no swap helps
+    pub max_iterations: Option<usize>,
+    /// Stop that refinement once a sweep removes less than this share of the weighted crossings
+    /// before it (0.0 = never stop early)
+    pub improvement_epsilon: f64,
+    /// Widen the gaps between ranks where `output_svg` places edge labels so the labels fit
+    pub fit_edge_labels: bool,
+    /// Cut edges spanning several ranks straight across, possibly through the nodes in between,
+    /// instead of routing them through the channel the layout reserves for them. Saves the
+    /// second engine run that routing costs, e.g. for interactive previews.
+    pub straight_long_edges: bool,
+    /// Rank order node categories must keep regardless of the edges, see `RankConstraints`
+    pub rank_constraints: Option<RankConstraints>,
+    /// Ids of nodes that must share a rank, e.g. all raw material sources. Each group is
+    /// ranked as one node behind all predecessors of its members and then spread across the
+    /// rank. A node belongs to the first group listing it; no member may reach another one.
+    pub same_rank: Vec<Vec<String>>,
+    /// Extra gap between disconnected components before scaling, on top of the vertex spacing
+    /// that already separates them
+    pub component_gutter: f64,
+    /// Bend edges around the boxes of nodes they would otherwise cross, see `detour_around_nodes`
+    pub avoid_nodes: bool,
+    /// Leave sources by the face toward the next rank and enter targets by the opposite one,
+    /// spreading the edges sharing a face evenly along it. `preferred_sides` takes precedence.
+    pub ports: bool,
+}
+
+/// Required order of node categories along the rank axis, e.g. raw materials strictly before
+/// refined goods strictly before industrial goods. Nodes whose category isn't listed are
+/// ranked by their edges alone.
+#[derive(Clone, Debug)]
+pub struct RankConstraints {
+    pub category: fn(&TechNode) -> String,
+    /// Categories from the first rank onwards; a category listed twice keeps its first place
+    pub order: Vec<String>,
+}
+
+impl RankConstraints {
+    /// Place of the node's category in `order`, if listed
+    pub fn position(&self, node: &TechNode) -> Option<usize> {
+        let category = (self.category)(node);
+        self.order.iter().position(|listed| *listed == category)
+    }
+}
+
+/// Set of nodes that can be shown as a single summary node, e.g. all goods of one system
+#[derive(Clone, Debug)]
+pub struct NodeGroup {
+    /// Id of the summary node, must not clash with a node id
+    pub id: String,
+    /// Title of the summary node
+    pub name: String,
+    /// Ids of the nodes in the group; a node belongs to the first collapsed group listing it
+    pub members: Vec<String>,
+    pub collapsed: bool,
+}
+
+/// How the secondary key reorders nodes within a rank
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SecondaryOrderMode {
+    /// Swap neighbours whose keys are out of order, but only if that adds no crossings
+    TieBreak,
+    /// Make nodes with equal keys contiguous, groups ordered by their average position.
+    /// This is a constraint and may add crossings.
+    Group,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SecondaryOrder {
+    pub key: fn(&TechNode) -> i64,
+    pub mode: SecondaryOrderMode,
+}
+
+/// Why an edge did not receive routing points
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnroutedReason {
+    /// `source`/`target` doesn't refer to any node id
+    UnknownNode(String),
+    /// The node exists but has no usable coordinates
+    MissingPosition(String),
+}
+
+impl fmt::Display for UnroutedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnroutedReason::UnknownNode(id) => write!(f, "unknown node '{}'", id),
+            UnroutedReason::MissingPosition(id) => write!(f, "node '{}' has no position", id),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UnroutedEdge {
+    pub source: String,
+    pub target: String,
+    pub reason: UnroutedReason,
+}
+
+/// Result of `build_supply_chain_layout`
+#[derive(Clone, Debug)]
+pub struct SupplyChainLayout {
+    /// Positions and routes of the graph as laid out, see `graph`
+    pub result: LayoutResult,
+    /// The graph as laid out when collapsed groups or `transitive_reduction` changed it, or
+    /// `build_component_layouts` reordered it
+    pub laid_out: Option<(Vec<TechNode>, Vec<TechEdge>)>,
+    /// Edges that couldn't be routed and therefore won't be rendered
+    pub unrouted_edges: Vec<UnroutedEdge>,
+    /// Where each rank sits along the rank axis, by rank (see `rank_positions`)
+    pub rank_positions: Vec<f64>,
+    /// Only collected with `LayoutOptions::collect_metrics`
+    pub metrics: Option<LayoutMetrics>,
+    /// Indices into the edges of `graph` of the ones laid out against their direction to
+    /// break cycles, whose routes are marked `reversed`
+    pub reversed_edges: Vec<usize>,
+    /// Factors the layout's coordinates were scaled by, i.e. pixels per layout unit
+    pub x_scale: f64,
+    pub y_scale: f64,
+}
+
+impl SupplyChainLayout {
+    /// Scale bar `units` layout units long, drawn to the scale of this layout
+    pub fn scale_bar(&self, units: f64, unit_name: &str) -> ScaleBar {
+        ScaleBar { pixels_per_unit: self.x_scale, units, unit_name: unit_name.to_string() }
+    }
+
+    /// The graph `result` belongs to, to be passed to `output_svg` along with it: `nodes` and
+    /// `edges` as given to `build_supply_chain_layout`, unless the layout changed them
+    pub fn graph<'a>(&'a self, nodes: &'a [TechNode], edges: &'a [TechEdge]) -> (&'a [TechNode], &'a [TechEdge]) {
+        match self.laid_out {
+            Some((ref nodes, ref edges)) => (nodes, edges),
+            None => (nodes, edges),
+        }
+    }
+}
+
+/// Layout of a graph keyed by node id, kept apart from the `TechNode`s and `TechEdge`s it was
+/// computed for, so several layouts of the same graph can exist side by side
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayoutResult {
+    /// Center of every positioned node
+    pub positions: HashMap<String, Point>,
+    /// Rank of every ranked node, counted from 0
+    pub ranks: HashMap<String, usize>,
+    /// Routes by (source, target), one per parallel edge in the order of the edges
+    pub edge_paths: HashMap<(String, String), Vec<EdgePath>>,
+    /// (min_x, min_y, max_x, max_y) of everything `output_svg` draws with the default label
+    /// placement, see `drawing_bounds`
+    pub bounds: Bounds,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EdgePath {
+    /// Route as in `TechEdge::points`
+    pub points: Vec<Point>,
+    pub style: PathStyle,
+    /// Laid out against its direction because cycle breaking turned it around for ranking.
+    /// Its data direction stays source -> target.
+    pub reversed: bool,
+}
+
+impl LayoutResult {
+    pub fn new(nodes: &[PlacedNode], edges: &[PlacedEdge]) -> Self {
+        let mut edge_paths: HashMap<(String, String), Vec<EdgePath>> = HashMap::new();
+        for edge in edges {
+            if let Some(ref points) = edge.points {
+                edge_paths.entry((edge.source.clone(), edge.target.clone())).or_default().push(EdgePath {
+                    points: points.clone(),
+                    style: edge.path_style,
+                    reversed: edge.reversed,
+                });
+            }
+        }
+
+        LayoutResult {
+            positions: nodes.iter().filter_map(|node| Some((node.id.clone(), (node.x?, node.y?)))).collect(),
+            ranks: nodes.iter().filter_map(|node| Some((node.id.clone(), node.rank?))).collect(),
+            edge_paths,
+            bounds: drawing_bounds(nodes, edges, LabelPlacement::default()).unwrap_or((0.0, 0.0, 0.0, 0.0)),
+        }
+    }
+
+    /// The last known positions and routes of a graph, e.g. one written by `save_graph`
+    pub fn last_known(nodes: &[TechNode], edges: &[TechEdge]) -> Self {
+        let nodes: Vec<PlacedNode> = nodes.iter().map(|node| PlacedNode { node, rank: None, x: node.x, y: node.y }).collect();
+        let edges: Vec<PlacedEdge> = edges
+            .iter()
+            .map(|edge| PlacedEdge {
+                edge,
+                points: edge.points.clone(),
+                path_style: edge.path_style,
+                reversed: false,
+                curve_factor: None,
+            })
+            .collect();
+        LayoutResult::new(&nodes, &edges)
+    }
+
+    /// The route of every edge of `edges`, the k-th of several parallel edges taking the k-th
+    /// path between their endpoints
+    pub fn routes<'a>(&'a self, edges: &[TechEdge]) -> Vec<Option<&'a EdgePath>> {
+        let mut taken: HashMap<(&str, &str), usize> = HashMap::new();
+        edges
+            .iter()
+            .map(|edge| {
+                let count = taken.entry((edge.source.as_str(), edge.target.as_str())).or_default();
+                let path = self
+                    .edge_paths
+                    .get(&(edge.source.clone(), edge.target.clone()))
+                    .and_then(|paths| paths.get(*count));
+                *count += 1;
+                path
+            })
+            .collect()
+    }
+}
+
+/// A node while it is laid out or drawn: the input node with the rank and center the layout
+/// gave it. Reads through to the `TechNode` for everything else.
+#[derive(Clone, Debug)]
+pub struct PlacedNode<'a> {
+    pub node: &'a TechNode,
+    pub rank: Option<usize>,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+}
+
+impl std::ops::Deref for PlacedNode<'_> {
+    type Target = TechNode;
+
+    fn deref(&self) -> &TechNode {
+        self.node
+    }
+}
+
+/// An edge while it is routed or drawn: the input edge with the route the layout gave it
+#[derive(Clone, Debug)]
+pub struct PlacedEdge<'a> {
+    pub edge: &'a TechEdge,
+    pub points: Option<Vec<Point>>,
+    pub path_style: PathStyle,
+    /// See `EdgePath::reversed`
+    pub reversed: bool,
+    /// Spread of parallel arcs relative to their length, see `fan_out_parallel_edges`
+    pub curve_factor: Option<f64>,
+}
+
+impl std::ops::Deref for PlacedEdge<'_> {
+    type Target = TechEdge;
+
+    fn deref(&self) -> &TechEdge {
+        self.edge
+    }
+}
+
+/// `nodes` at their positions and ranks in `layout`; nodes it doesn't cover stay unpositioned
+pub fn place_nodes<'a>(nodes: &'a [TechNode], layout: &LayoutResult) -> Vec<PlacedNode<'a>> {
+    nodes
+        .iter()
+        .map(|node| {
+            let position = layout.positions.get(&node.id);
+            PlacedNode {
+                node,
+                rank: layout.ranks.get(&node.id).copied(),
+                x: position.map(|&(x, _)| x),
+                y: position.map(|&(_, y)| y),
+            }
+        })
+        .collect()
+}
+
+/// `edges` along their routes in `layout`; edges it doesn't cover stay unrouted
+pub fn place_edges<'a>(edges: &'a [TechEdge], layout: &LayoutResult) -> Vec<PlacedEdge<'a>> {
+    edges
+        .iter()
+        .zip(layout.routes(edges))
+        .map(|(edge, path)| PlacedEdge {
+            edge,
+            points: path.map(|path| path.points.clone()),
+            path_style: path.map_or(edge.path_style, |path| path.style),
+            reversed: path.is_some_and(|path| path.reversed),
+            curve_factor: None,
+        })
+        .collect()
+}
+
+/// Durations and sizes of a layout run
+#[derive(Clone, Debug, Default)]
+pub struct LayoutMetrics {
+    pub graph_construction: Duration,
+    /// Ranking, crossing minimization and coordinate assignment, which rust-sugiyama runs in one call
+    pub engine: Duration,
+    /// Reordering and coordinate passes after the engine
+    pub post_processing: Duration,
+    pub routing: Duration,
+    /// Set by `output_svg_with_metrics`
+    pub render: Option<Duration>,
+    pub nodes: usize,
+    pub edges: usize,
+    /// Virtual vertices needed to split edges spanning several ranks into single-rank segments
+    pub dummy_vertices: usize,
+    pub crossings: usize,
+}
+
+impl LayoutMetrics {
+    /// Add the durations and counts of another run, e.g. of another component
+    pub fn add(&mut self, other: &LayoutMetrics) {
+        self.graph_construction += other.graph_construction;
+        self.engine += other.engine;
+        self.post_processing += other.post_processing;
+        self.routing += other.routing;
+        self.render = match (self.render, other.render) {
+            (None, None) => None,
+            (render, other) => Some(render.unwrap_or_default() + other.unwrap_or_default()),
+        };
+        self.nodes += other.nodes;
+        self.edges += other.edges;
+        self.dummy_vertices += other.dummy_vertices;
+        self.crossings += other.crossings;
+    }
+}
+
+/// Function to build the supply chain layout with separate x and y scaling.
+/// Inputs, options and results are all `Send + Sync` (checked by `layout_types_are_send_and_sync`),
+/// so layouts can run on worker threads.
+pub fn build_supply_chain_layout(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    orientation: Orientation,
+    x_scale: f64,  // Scaling factor for horizontal spacing
+    y_scale: f64,  // Scaling factor for vertical spacing
+    options: &LayoutOptions,
+) -> Result<SupplyChainLayout, LayoutError> {
+    // Zero, negative or non-finite sizes would produce invisible boxes and corrupt the bounds
+    for node in nodes {
+        let valid = |v: f64| v.is_finite() && v > 0.0 && v <= MAX_NODE_EXTENT;
+        if !valid(node.width) || !valid(node.height) {
+            return Err(LayoutError::InvalidNodeSize {
+                id: node.id.clone(),
+                width: node.width,
+                height: node.height,
+            });
+        }
+    }
+    for (axis, scale) in [("x", x_scale), ("y", y_scale)] {
+        if !(scale.is_finite() && scale > 0.0 && scale <= MAX_SCALE) {
+            return Err(LayoutError::InvalidScale { axis, scale });
+        }
+    }
+    if let Some(spacing) = options.vertex_spacing
+        && !(1..=MAX_VERTEX_SPACING).contains(&spacing)
+    {
+        return Err(LayoutError::InvalidVertexSpacing(spacing));
+    }
+    let minimum_length = options.minimum_length.unwrap_or(1);
+    if !(1..=MAX_MINIMUM_LENGTH).contains(&minimum_length) {
+        return Err(LayoutError::InvalidMinimumLength(minimum_length));
+    }
+
+    let collapsed = match options.groups.iter().any(|group| group.collapsed) {
+        true => Some(collapse_groups(nodes, edges, &options.groups)?),
+        false => None,
+    };
+    let (nodes, edges) = collapsed.as_ref().map_or((nodes, edges), |(nodes, edges)| (nodes.as_slice(), edges.as_slice()));
+
+    let reduced = options.transitive_reduction.then(|| transitive_reduction(nodes, edges));
+    let (nodes, edges) = reduced.as_ref().map_or((nodes, edges), |(nodes, edges)| (nodes.as_slice(), edges.as_slice()));
+
+    if let Some(ref constraints) = options.rank_constraints {
+        check_rank_constraints(nodes, edges, constraints)?;
+    }
+    let same_rank = same_rank_groups(nodes, &options.same_rank);
+    check_same_rank_groups(nodes, edges, &same_rank)?;
+
+    // Only read the clock when asked to
+    let mut phase_start = options.collect_metrics.then(Instant::now);
+    let mut lap = || {
+        phase_start
+            .replace(Instant::now())
+            .map_or(Duration::ZERO, |start| start.elapsed())
+    };
+
+    let rank_axis = match orientation {
+        Orientation::LeftRight | Orientation::RightLeft => RankAxis::X,
+        Orientation::TopDown | Orientation::BottomUp => RankAxis::Y,
+    };
+
+    // Nodes and edges as handed to the layout engine: the input nodes in insertion order,
+    // followed by any waypoints added below
+    let mut engine_nodes: Vec<EngineNode> = Vec::new();
+
+    // Create a mapping from node ID to its index in `engine_nodes`
+    let mut node_indices: HashMap<String, usize> = HashMap::new();
+
+    // Add all nodes
+    let mut insertion_order: Vec<&TechNode> = nodes.iter().collect();
+    if let Some(key) = options.initial_order {
+        insertion_order.sort_by_key(|node| key(node));
+    }
+    // Members of a same rank group share the engine node of the first one inserted
+    let mut group_indices: HashMap<usize, usize> = HashMap::new();
+    for node in insertion_order {
+        match same_rank.get(&node.id) {
+            Some(&group) => match group_indices.get(&group) {
+                Some(&engine_idx) => {
+                    if let EngineNode::Group(members, _) = &mut engine_nodes[engine_idx] {
+                        members.push(node);
+                    }
+                    node_indices.insert(node.id.clone(), engine_idx);
+                }
+                None => {
+                    group_indices.insert(group, engine_nodes.len());
+                    node_indices.insert(node.id.clone(), engine_nodes.len());
+                    engine_nodes.push(EngineNode::Group(vec![node], rank_axis));
+                }
+            },
+            None => {
+                node_indices.insert(node.id.clone(), engine_nodes.len());
+                engine_nodes.push(EngineNode::Node(node));
+            }
+        }
+    }
+
+    // Add all edges. Note: rust-sugiyama 0.3 orders with a unit weight per edge, so `weight_by`
+    // refines crossings afterwards (see `reduce_weighted_crossings`).
+    // Edges closing a cycle go in reversed, so the engine ranks a DAG
+    let back_edges = find_back_edges(nodes, edges);
+    let mut engine_edges: Vec<(usize, usize)> = Vec::new();
+    let mut edge_indices: Vec<Option<usize>> = Vec::with_capacity(edges.len());
+    for (i, edge) in edges.iter().enumerate() {
+        if let (Some(&source_idx), Some(&target_idx)) = (
+            node_indices.get(&edge.source),
+            node_indices.get(&edge.target),
+        ) {
+            let (from, to) = if back_edges.contains(&i) { (target_idx, source_idx) } else { (source_idx, target_idx) };
+            edge_indices.push(Some(engine_edges.len()));
+            engine_edges.push((from, to));
+        } else {
+            edge_indices.push(None);
+        }
+    }
+
+    // Enforce the category order with edges from every node of a category to every node of the
+    // next one present. These only reach the engine, which ranks their targets at least one rank
+    // further; transitively each category ends up strictly behind all earlier ones.
+    if let Some(ref constraints) = options.rank_constraints {
+        let mut by_category: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for node in nodes {
+            if let Some(position) = constraints.position(node) {
+                by_category.entry(position).or_default().push(node_indices[&node.id]);
+            }
+        }
+        let mut present: HashSet<(usize, usize)> = engine_edges.iter().copied().collect();
+        let categories: Vec<&Vec<usize>> = by_category.values().collect();
+        for pair in categories.windows(2) {
+            for &source_idx in pair[0] {
+                for &target_idx in pair[1] {
+                    if source_idx != target_idx && present.insert((source_idx, target_idx)) {
+                        engine_edges.push((source_idx, target_idx));
+                    }
+                }
+            }
+        }
+    }
+
+    // Keep the default gap of 100 between the largest boxes
+    let largest_box = nodes.iter().map(|node| node.width.max(node.height)).fold(200.0, f64::max);
+
+    // Configure the layout algorithm. rust-sugiyama 0.3 leaves the ranks skipped by edges of
+    // exactly `minimum_length` empty and then fails on them, so the engine ranks with unit
+    // length and `stretch_ranks` spreads the ranks out afterwards.
+    let config = Config {
+        minimum_length: 1,
+        vertex_spacing: options.vertex_spacing.unwrap_or((largest_box + 100.0).ceil() as usize),
+        dummy_vertices: true,                          // Enable dummy vertices
+        dummy_size: 0.5, // Share of `vertex_spacing` a dummy vertex takes up
+        ranking_type: RankingType::MinimizeEdgeLength, // Change from Original
+        // rust-sugiyama 0.3 sweeps until 4 sweeps in a row bring no improvement; neither the
+        // sweep limit nor a threshold is configurable, so `transpose` is its only effort knob;
+        // `max_iterations` and `improvement_epsilon` bound the refinement afterwards
+        c_minimization: CrossingMinimization::Barycenter,
+        transpose: !options.quick_crossing_reduction,
+        // ..Default::default()
+    };
+
+    // rust-sugiyama 0.3 ranks with a unit weight per edge. With `weight_by`, rank beforehand by
+    // weighted edge length (see `weighted_ranks`) and make the engine keep those ranks by
+    // replacing every edge longer than one rank with a chain of waypoints, one per rank it
+    // skips: the engine then ranks every edge at its minimum length.
+    let weighted = options.weight_by.and_then(|weight_by| {
+        let mut weighted_edges: Vec<(usize, usize, f64)> = engine_edges.iter().map(|&(from, to)| (from, to, 0.0)).collect();
+        for (edge, edge_idx) in edges.iter().zip(&edge_indices) {
+            if let Some(edge_idx) = *edge_idx {
+                weighted_edges[edge_idx].2 += weight_by.weight(edge);
+            }
+        }
+        weighted_ranks(engine_nodes.len(), &weighted_edges)
+    });
+
+    // The engine keeps the positions of its dummy vertices to itself. To bend long edges along
+    // the channels it reserves, replace every edge spanning several ranks in a first run by a
+    // chain of anonymous waypoint nodes, which the second run positions like any other node.
+    let mut waypoint_chains: HashMap<usize, Vec<usize>> = HashMap::new();
+    if weighted.is_some()
+        || !options.straight_long_edges
+        || options.edge_routing == EdgeRouting::Orthogonal
+        || options.edge_routing == EdgeRouting::Channels
+    {
+        let first_ranks = match weighted {
+            Some(ref ranks) => ranks.iter().copied().map(Some).collect(),
+            None => {
+                let boxes: Vec<EngineBox> = engine_nodes.iter().map(|node| EngineBox(node, (x_scale, y_scale))).collect();
+                layout(&boxes, &engine_edges, config, orientation).ranks
+            }
+        };
+        let input_edges: HashMap<usize, usize> =
+            edge_indices.iter().enumerate().filter_map(|(i, edge_idx)| edge_idx.map(|edge_idx| (edge_idx, i))).collect();
+        for edge_idx in 0..engine_edges.len() {
+            // Weighted ranks hold only if rank constraint edges keep their length, too
+            let input_edge = input_edges.get(&edge_idx).copied();
+            if input_edge.is_none() && weighted.is_none() {
+                continue;
+            }
+            let (source_idx, target_idx) = engine_edges[edge_idx];
+            let (Some(source_rank), Some(target_rank)) = (first_ranks[source_idx], first_ranks[target_idx]) else {
+                continue;
+            };
+            if target_rank > source_rank + 1 {
+                let chain: Vec<usize> = (source_rank + 1..target_rank)
+                    .map(|_| {
+                        engine_nodes.push(EngineNode::Waypoint);
+                        engine_nodes.len() - 1
+                    })
+                    .collect();
+                engine_edges[edge_idx] = (source_idx, chain[0]);
+                for pair in chain.windows(2) {
+                    engine_edges.push((pair[0], pair[1]));
+                }
+                engine_edges.push((chain[chain.len() - 1], target_idx));
+                if let Some(i) = input_edge {
+                    waypoint_chains.insert(i, chain);
+                }
+            }
+        }
+    }
+    let graph_construction = lap();
+
+    // Process the layout results. Nodes and edges start out where they were last known.
+    let mut updated_nodes: Vec<PlacedNode> =
+        nodes.iter().map(|node| PlacedNode { node, rank: None, x: node.x, y: node.y }).collect();
+    let mut updated_edges: Vec<PlacedEdge> = edges
+        .iter()
+        .enumerate()
+        .map(|(i, edge)| PlacedEdge {
+            edge,
+            points: edge.points.clone(),
+            path_style: edge.path_style,
+            reversed: back_edges.contains(&i),
+            curve_factor: None,
+        })
+        .collect();
+
+    // Create reverse lookup from node ID to position in nodes array
+    let mut node_positions: HashMap<String, usize> = HashMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        node_positions.insert(node.id.clone(), i);
+    }
+
+    // Run the layout algorithm
+    let boxes: Vec<EngineBox> = engine_nodes.iter().map(|node| EngineBox(node, (x_scale, y_scale))).collect();
+    let result = layout_with_gutter(&boxes, &engine_edges, config, orientation, options.component_gutter);
+    let engine = lap();
+
+    // Indices into `updated_nodes` that received coordinates from this layout run
+    let mut positioned: HashSet<usize> = HashSet::new();
+
+    // Apply coordinates to nodes, also applying the scaling factors
+    let mut waypoint_positions: HashMap<usize, (usize, Point)> = HashMap::new();
+    for (engine_idx, engine_node) in engine_nodes.iter().enumerate() {
+        let (Some((x, y)), Some(rank)) = (result.positions[engine_idx], result.ranks[engine_idx]) else {
+            continue;
+        };
+        let point = (x * x_scale, y * y_scale);
+        match engine_node {
+            EngineNode::Node(node) => {
+                let pos = node_positions[&node.id];
+                updated_nodes[pos].rank = Some(rank);
+                updated_nodes[pos].x = Some(point.0);
+                updated_nodes[pos].y = Some(point.1);
+                positioned.insert(pos);
+            }
+            EngineNode::Group(members, _) => {
+                // Members fill the box the engine kept free for the group, `GROUP_GAP` apart
+                let (width, height) = engine_node.size();
+                let mut cross = match rank_axis {
+                    RankAxis::X => point.1 - height / 2.0,
+                    RankAxis::Y => point.0 - width / 2.0,
+                };
+                for member in members {
+                    let (extent, _) = cross_and_rank_extent(member, rank_axis);
+                    cross += extent / 2.0;
+                    let pos = node_positions[&member.id];
+                    updated_nodes[pos].rank = Some(rank);
+                    (updated_nodes[pos].x, updated_nodes[pos].y) = match rank_axis {
+                        RankAxis::X => (Some(point.0), Some(cross)),
+                        RankAxis::Y => (Some(cross), Some(point.1)),
+                    };
+                    positioned.insert(pos);
+                    cross += extent / 2.0 + GROUP_GAP;
+                }
+            }
+            EngineNode::Waypoint => {
+                waypoint_positions.insert(engine_idx, (rank, point));
+            }
+        }
+    }
+
+    if minimum_length > 1 {
+        stretch_ranks(&mut updated_nodes, waypoint_positions.values_mut(), rank_axis, minimum_length as usize);
+    }
+
+    // Cycle breaking may still turn constraint edges around where the input has cycles
+    if let Some(ref constraints) = options.rank_constraints {
+        check_constrained_ranks(&updated_nodes, constraints)?;
+    }
+
+    if options.weight_by.is_some() || options.max_iterations.is_some() || options.improvement_epsilon > 0.0 {
+        let weight_by = options.weight_by.unwrap_or(WeightBy::Custom(|_| 1));
+        let max_iterations = options.max_iterations.unwrap_or(nodes.len());
+        reduce_weighted_crossings(&mut updated_nodes, edges, rank_axis, weight_by, max_iterations, options.improvement_epsilon);
+    }
+
+    if let Some(ref secondary_order) = options.secondary_order {
+        apply_secondary_order(&mut updated_nodes, edges, secondary_order, rank_axis);
+    }
+
+    if options.minimize_edge_length {
+        minimize_edge_length(&mut updated_nodes, edges, rank_axis, options.weight_by);
+    }
+
+    if options.straighten_chains {
+        straighten_chains(&mut updated_nodes, edges, rank_axis);
+    }
+
+    if options.fit_edge_labels {
+        fit_edge_label_gaps(&mut updated_nodes, edges, rank_axis, options.label_placement);
+    }
+
+    // Post-processing may have moved ranks along the rank axis; waypoints follow their rank
+    if let Some((_, ranks)) = rank_coordinates(&updated_nodes) {
+        let ranks: HashMap<usize, f64> = ranks.into_iter().collect();
+        for (rank, point) in waypoint_positions.values_mut() {
+            match (rank_axis, ranks.get(rank)) {
+                (RankAxis::X, Some(&coordinate)) => point.0 = coordinate,
+                (RankAxis::Y, Some(&coordinate)) => point.1 = coordinate,
+                (_, None) => {}
+            }
+        }
+    }
+
+    let post_processing = lap();
+
+    let endpoint =
+        |id: &str| routing_endpoint(&updated_nodes, &node_positions, &positioned, options.unpositioned_edges, id);
+
+    // Sides each edge leaves its source by and enters its target by, if any
+    let edge_sides: Vec<(Option<Side>, Option<Side>)> = updated_edges
+        .iter()
+        .map(|edge| {
+            let (Some(&source), Some(&target)) = (node_positions.get(&edge.source), node_positions.get(&edge.target)) else {
+                return (None, None);
+            };
+            let (source, target) = (&updated_nodes[source], &updated_nodes[target]);
+            let (mut exit, mut entry) = match options.preferred_sides {
+                Some(preferred_sides) => (preferred_sides(source).exit, preferred_sides(target).entry),
+                None => (None, None),
+            };
+            // Edges running against the ranks leave backwards and come in from the front, edges
+            // within a rank keep their direct connection
+            let exit_side = match (source.rank, target.rank) {
+                (Some(source), Some(target)) if options.ports && source < target => Some(Side::forward(orientation)),
+                (Some(source), Some(target)) if options.ports && source > target => Some(Side::forward(orientation).opposite()),
+                _ => None,
+            };
+            exit = exit.or(exit_side);
+            entry = entry.or(exit_side.map(Side::opposite));
+            (exit, entry)
+        })
+        .collect();
+
+    // Ports of the edges sharing a face are spread along it, ordered by where the other end
+    // of each edge lies so they don't cross in front of the face. Keyed by (edge, is exit).
+    let mut port_fractions: HashMap<(usize, bool), f64> = HashMap::new();
+    if options.ports {
+        // Where the other end lies across the face, the edge and whether it leaves by the face
+        type FacePort = (f64, usize, bool);
+        let mut faces: HashMap<(&str, Side), Vec<FacePort>> = HashMap::new();
+        for (i, (edge, &(exit, entry))) in updated_edges.iter().zip(&edge_sides).enumerate() {
+            for (id, other, side, is_exit) in [(&edge.source, &edge.target, exit, true), (&edge.target, &edge.source, entry, false)] {
+                let (Some(side), Some(&other)) = (side, node_positions.get(other)) else {
+                    continue;
+                };
+                let other = &updated_nodes[other];
+                let across = match side {
+                    Side::Left | Side::Right => other.y.unwrap_or_default(),
+                    Side::Top | Side::Bottom => other.x.unwrap_or_default(),
+                };
+                faces.entry((id.as_str(), side)).or_default().push((across, i, is_exit));
+            }
+        }
+        for mut ports in faces.into_values() {
+            ports.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            let count = ports.len() as f64;
+            for (k, (_, i, is_exit)) in ports.into_iter().enumerate() {
+                port_fractions.insert((i, is_exit), (k as f64 + 1.0) / (count + 1.0));
+            }
+        }
+    }
+
+    // Process edge routing with scaling
+    let mut unrouted_edges = Vec::new();
+    // Waypoints of long edges, which the channel router splits them at
+    let mut channel_waypoints: HashMap<usize, Vec<Point>> = HashMap::new();
+    for (i, edge) in updated_edges.iter_mut().enumerate() {
+        match (endpoint(&edge.source), endpoint(&edge.target)) {
+            (Ok((sx, sy)), Ok((tx, ty))) => {
+                // For curved edges with control points
+                let mid_x = (sx + tx) / 2.0;
+                let mid_y = (sy + ty) / 2.0;
+
+                // Preferred sides and ports move the ends onto those sides and bend the curve out of them
+                let (exit, entry) = edge_sides[i];
+                let ((sx, sy), (tx, ty), (mid_x, mid_y)) = if exit.is_some() || entry.is_some() {
+                    let port = |center: Point, id: &str, side: Option<Side>, is_exit: bool| {
+                        side.map_or(center, |side| {
+                            let node = &updated_nodes[node_positions[id]];
+                            match port_fractions.get(&(i, is_exit)) {
+                                Some(&fraction) => side.port_at(node, fraction),
+                                None => side.port(node),
+                            }
+                        })
+                    };
+                    let start = port((sx, sy), &edge.source, exit, true);
+                    let end = port((tx, ty), &edge.target, entry, false);
+                    let reach = ((end.0 - start.0).powi(2) + (end.1 - start.1).powi(2)).sqrt() / 2.0;
+                    let pull = |point: Point, side: Option<Side>| {
+                        side.map_or((mid_x, mid_y), |side| {
+                            let (nx, ny) = side.normal();
+                            (point.0 + nx * reach, point.1 + ny * reach)
+                        })
+                    };
+                    let (a, b) = (pull(start, exit), pull(end, entry));
+                    (start, end, ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0))
+                } else {
+                    ((sx, sy), (tx, ty), (mid_x, mid_y))
+                };
+
+                // Long edges pass through their waypoints, all others get one control point
+                let waypoints: Option<Vec<Point>> = waypoint_chains.get(&i).and_then(|chain| {
+                    let mut points: Vec<Point> =
+                        chain.iter().map(|idx| waypoint_positions.get(idx).map(|&(_, point)| point)).collect::<Option<_>>()?;
+                    // The chain of a reversed edge runs from its target to its source
+                    if edge.reversed {
+                        points.reverse();
+                    }
+                    Some(points)
+                });
+                if options.edge_routing == EdgeRouting::Channels
+                    && let Some(waypoints) = &waypoints
+                {
+                    channel_waypoints.insert(i, waypoints.clone());
+                }
+                edge.points = Some(match (options.edge_routing, waypoints) {
+                    (EdgeRouting::Orthogonal, waypoints) => {
+                        edge.path_style = PathStyle::Straight;
+                        let points = [vec![(sx, sy)], waypoints.unwrap_or_default(), vec![(tx, ty)]].concat();
+                        orthogonal_route(&points, rank_axis)
+                    }
+                    (EdgeRouting::Curved | EdgeRouting::Channels, Some(waypoints)) => {
+                        curve_through_waypoints((sx, sy), waypoints, (tx, ty))
+                    }
+                    (EdgeRouting::Curved | EdgeRouting::Channels, None) => vec![
+                        (sx, sy),       // Start point
+                        (mid_x, mid_y), // Control point
+                        (tx, ty),       // End point
+                    ],
+                });
+
+                // Calculate curve factor based on distance
+                let distance = ((tx - sx).powi(2) + (ty - sy).powi(2)).sqrt();
+                edge.curve_factor = Some((distance / 500.0).clamp(0.1, 0.5));
+            }
+            (Err(reason), _) | (_, Err(reason)) => {
+                // Don't keep stale routing around for edges we were told to report
+                if options.unpositioned_edges == UnpositionedEdges::Report {
+                    edge.points = None;
+                }
+                unrouted_edges.push(UnroutedEdge {
+                    source: edge.source.clone(),
+                    target: edge.target.clone(),
+                    reason,
+                });
+            }
+        }
+    }
+
+    fan_out_parallel_edges(&mut updated_edges);
+
+    if options.edge_routing == EdgeRouting::Channels {
+        route_edges_in_channels(&updated_nodes, &mut updated_edges, &channel_waypoints, rank_axis);
+    }
+
+    if options.avoid_nodes {
+        detour_around_nodes(&updated_nodes, &mut updated_edges);
+    }
+
+    if !options.obstacles.is_empty() {
+        route_around_svg_obstacles(&updated_nodes, &mut updated_edges, &options.obstacles, options.label_placement);
+    }
+
+    let routing = lap();
+
+    if let Some((min_x, min_y, max_x, max_y)) = layout_bounds(&updated_nodes) {
+        let (width, height) = (max_x - min_x, max_y - min_y);
+        if width > MAX_CANVAS_EXTENT || height > MAX_CANVAS_EXTENT {
+            return Err(LayoutError::CanvasTooLarge { width, height });
+        }
+    }
+
+    let rank_positions = rank_positions(&updated_nodes);
+    let metrics = options.collect_metrics.then(|| LayoutMetrics {
+        graph_construction,
+        engine,
+        post_processing,
+        routing,
+        render: None,
+        nodes: updated_nodes.len(),
+        edges: updated_edges.len(),
+        dummy_vertices: count_dummy_vertices(&updated_nodes, edges),
+        crossings: count_crossings(&updated_nodes, edges),
+    });
+    let result = LayoutResult::new(&updated_nodes, &updated_edges);
+
+    let mut reversed_edges: Vec<usize> = back_edges.into_iter().collect();
+    reversed_edges.sort_unstable();
+
+    Ok(SupplyChainLayout {
+        result,
+        laid_out: reduced.or(collapsed),
+        unrouted_edges,
+        rank_positions,
+        metrics,
+        reversed_edges,
+        x_scale,
+        y_scale,
+    })
+}
+
+/// `build_supply_chain_layout` of every weakly connected component on its own, the layouts
+/// placed side by side by `merge_layouts`, `SVG_MARGIN` plus `component_gutter` apart. The
+/// graph of the result lists nodes and edges component by component, edges between unknown
+/// nodes going with the first one. Metrics add up those of the components, counting placing
+/// them side by side as post-processing; `rank_positions` averages the ranks of all components.
+pub fn build_component_layouts(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    orientation: Orientation,
+    x_scale: f64,
+    y_scale: f64,
+    options: &LayoutOptions,
+) -> Result<SupplyChainLayout, LayoutError> {
+    let component_of = components(nodes, edges);
+    let count = component_of.values().max().map_or(0, |last| last + 1);
+    if count <= 1 {
+        return build_supply_chain_layout(nodes, edges, orientation, x_scale, y_scale, options);
+    }
+    let component = |id: &str| component_of.get(id).copied();
+
+    let mut metrics = options.collect_metrics.then(LayoutMetrics::default);
+    let (mut laid_out_nodes, mut laid_out_edges) = (Vec::new(), Vec::new());
+    let (mut results, mut unrouted_edges, mut reversed_edges) = (Vec::new(), Vec::new(), Vec::new());
+    for c in 0..count {
+        let nodes: Vec<TechNode> = nodes.iter().filter(|node| component(&node.id) == Some(c)).cloned().collect();
+        let edges: Vec<TechEdge> = edges
+            .iter()
+            .filter(|edge| component(&edge.source).or(component(&edge.target)).unwrap_or(0) == c)
+            .cloned()
+            .collect();
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, x_scale, y_scale, options)?;
+        if let (Some(total), Some(component)) = (metrics.as_mut(), layout.metrics.as_ref()) {
+            total.add(component);
+        }
+        let (nodes, edges) = layout.graph(&nodes, &edges);
+        reversed_edges.extend(layout.reversed_edges.iter().map(|i| i + laid_out_edges.len()));
+        laid_out_nodes.extend_from_slice(nodes);
+        laid_out_edges.extend_from_slice(edges);
+        unrouted_edges.extend(layout.unrouted_edges);
+        results.push(layout.result);
+    }
+
+    let merge_start = Instant::now();
+    let result = merge_layouts(results, SVG_MARGIN + options.component_gutter);
+    if let Some(ref mut metrics) = metrics {
+        metrics.post_processing += merge_start.elapsed();
+    }
+    Ok(SupplyChainLayout {
+        rank_positions: rank_positions(&place_nodes(&laid_out_nodes, &result)),
+        result,
+        laid_out: Some((laid_out_nodes, laid_out_edges)),
+        unrouted_edges,
+        metrics,
+        reversed_edges,
+        x_scale,
+        y_scale,
+    })
+}
+
+/// Bounding box (min_x, min_y, max_x, max_y) of all positioned node rectangles
+pub fn layout_bounds(nodes: &[PlacedNode]) -> Option<Bounds> {
+    let mut bounds: Option<Bounds> = None;
+
+    for node in nodes {
+        if let (Some(x), Some(y)) = (node.x, node.y) {
+            let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((f64::MAX, f64::MAX, f64::MIN, f64::MIN));
+            bounds = Some((
+                min_x.min(x - node.width / 2.0),
+                min_y.min(y - node.height / 2.0),
+                max_x.max(x + node.width / 2.0),
+                max_y.max(y + node.height / 2.0),
+            ));
+        }
+    }
+
+    bounds
+}
+
+/// `layout_bounds` grown to everything `output_svg` draws for the edges: their points, which
+/// contain any curve they control, and their labels at `placement`
+pub fn drawing_bounds(nodes: &[PlacedNode], edges: &[PlacedEdge], placement: LabelPlacement) -> Option<Bounds> {
+    let mut bounds = layout_bounds(nodes)?;
+    let mut include = |(x, y): Point| {
+        bounds = (bounds.0.min(x), bounds.1.min(y), bounds.2.max(x), bounds.3.max(y));
+    };
+
+    for edge in edges {
+        let Some(points) = logical_points(edge, nodes) else {
+            continue;
+        };
+        for &point in points.iter() {
+            include(point);
+        }
+        if let Some((_, label)) = edge_label_position(edge, &points, nodes, placement) {
+            include((label.x, label.y));
+            include((label.x + label.width, label.y + label.height));
+        }
+    }
+
+    Some(bounds)
+}
+
+/// Displacement (dx, dy) from `a` to `b` of every node that moved between two layout runs,
+/// sorted by node id. Nodes missing a position in either are skipped.
+pub fn diff_layouts(a: &LayoutResult, b: &LayoutResult) -> Vec<(String, (f64, f64))> {
+    let mut moved: Vec<(String, (f64, f64))> = a
+        .positions
+        .iter()
+        .filter_map(|(id, &(x, y))| {
+            let &(new_x, new_y) = b.positions.get(id)?;
+            let displacement = (new_x - x, new_y - y);
+            (displacement != (0.0, 0.0)).then(|| (id.clone(), displacement))
+        })
+        .collect();
+    moved.sort_by(|a, b| a.0.cmp(&b.0));
+    moved
+}
+
+/// Move all positions, routes and the bounds of a layout by (dx, dy)
+pub fn translate_layout(layout: &mut LayoutResult, dx: f64, dy: f64) {
+    let shift = |point: &mut Point| *point = (point.0 + dx, point.1 + dy);
+    layout.positions.values_mut().for_each(shift);
+    layout.edge_paths.values_mut().flatten().flat_map(|path| path.points.iter_mut()).for_each(shift);
+    let (min_x, min_y, max_x, max_y) = layout.bounds;
+    layout.bounds = (min_x + dx, min_y + dy, max_x + dx, max_y + dy);
+}
+
+/// Combine separately computed layouts of graphs with distinct node ids into one canvas
+/// without laying them out again. Layouts are placed left to right in the given order,
+/// `gutter` apart, with their top edges aligned to the first one. Layouts without positioned
+/// nodes are added unchanged.
+pub fn merge_layouts(layouts: Vec<LayoutResult>, gutter: f64) -> LayoutResult {
+    let mut merged = LayoutResult::default();
+    // Right border of the canvas so far and the top all layouts are aligned to
+    let mut cursor: Option<(f64, f64)> = None;
+
+    for mut layout in layouts {
+        if !layout.positions.is_empty() {
+            let (min_x, min_y, max_x, _) = layout.bounds;
+            let (dx, dy) = match cursor {
+                Some((right, top)) => (right + gutter - min_x, top - min_y),
+                None => (0.0, 0.0),
+            };
+            translate_layout(&mut layout, dx, dy);
+            merged.bounds = match cursor {
+                Some(_) => {
+                    let (a, b) = (merged.bounds, layout.bounds);
+                    (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+                }
+                None => layout.bounds,
+            };
+            cursor = Some((max_x + dx, cursor.map_or(min_y, |(_, top)| top)));
+        }
+        merged.positions.extend(layout.positions);
+        merged.ranks.extend(layout.ranks);
+        for (pair, paths) in layout.edge_paths {
+            merged.edge_paths.entry(pair).or_default().extend(paths);
+        }
+    }
+
+    merged
+}
+
+/// Place every node without coordinates in a row below the positioned ones, starting at their
+/// left border so the rest of the layout doesn't move
+pub(crate) fn park_unpositioned_nodes(nodes: &mut [PlacedNode]) {
+    let gap = 50.0;
+    let (min_x, _, _, max_y) = layout_bounds(nodes).unwrap_or((0.0, -gap, 0.0, -gap));
+    let mut cursor_x = min_x;
+
+    for node in nodes.iter_mut().filter(|node| node.x.is_none() || node.y.is_none()) {
+        (node.x, node.y) = (Some(cursor_x + node.width / 2.0), Some(max_y + gap + node.height / 2.0));
+        cursor_x += node.width + gap;
+    }
+}
+
+/// Which coordinate encodes the rank of a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RankAxis {
+    /// Ranks are columns (LeftRight)
+    X,
+    /// Ranks are rows (TopDown)
+    Y,
+}
+
+/// Coordinate of every rank along the rank axis, sorted by rank, plus the axis itself.
+/// The axis is inferred from the positions so it stays correct for transformed layouts.
+pub fn rank_coordinates(nodes: &[PlacedNode]) -> Option<(RankAxis, Vec<(usize, f64)>)> {
+    let mut by_rank: BTreeMap<usize, Vec<Point>> = BTreeMap::new();
+    for node in nodes {
+        if let (Some(rank), Some(x), Some(y)) = (node.rank, node.x, node.y) {
+            by_rank.entry(rank).or_default().push((x, y));
+        }
+    }
+
+    if by_rank.is_empty() {
+        return None;
+    }
+
+    let spread = |points: &[Point], coord: fn(&Point) -> f64| {
+        let (min, max) = points
+            .iter()
+            .map(coord)
+            .fold((f64::MAX, f64::MIN), |(min, max), v| (min.min(v), max.max(v)));
+        max - min
+    };
+
+    // Nodes of the same rank share the rank coordinate, so it has the smaller spread within ranks
+    let within_x: f64 = by_rank.values().map(|points| spread(points, |p| p.0)).sum();
+    let within_y: f64 = by_rank.values().map(|points| spread(points, |p| p.1)).sum();
+    let axis = if within_x < within_y {
+        RankAxis::X
+    } else if within_y < within_x {
+        RankAxis::Y
+    } else {
+        // Only single-node ranks: the ranks advance along the axis with the larger overall spread
+        let firsts: Vec<Point> = by_rank.values().map(|points| points[0]).collect();
+        if spread(&firsts, |p| p.0) >= spread(&firsts, |p| p.1) {
+            RankAxis::X
+        } else {
+            RankAxis::Y
+        }
+    };
+
+    let coordinates = by_rank
+        .into_iter()
+        .map(|(rank, points)| {
+            let sum: f64 = points
+                .iter()
+                .map(|p| match axis {
+                    RankAxis::X => p.0,
+                    RankAxis::Y => p.1,
+                })
+                .sum();
+            (rank, sum / points.len() as f64)
+        })
+        .collect();
+
+    Some((axis, coordinates))
+}
+
+/// Spread the ranks of nodes and waypoints `factor` times as far apart, as if every edge had to
+/// span at least `factor` ranks: with a uniform minimum length, the ranking minimizing total edge
+/// length is the unit one scaled up. The lowest rank stays in place.
+pub(crate) fn stretch_ranks<'a>(
+    nodes: &mut [PlacedNode],
+    waypoints: impl Iterator<Item = &'a mut (usize, Point)>,
+    rank_axis: RankAxis,
+    factor: usize,
+) {
+    let along = |point: Point| match rank_axis {
+        RankAxis::X => point.0,
+        RankAxis::Y => point.1,
+    };
+    let Some(origin) = nodes
+        .iter()
+        .filter_map(|node| Some((node.rank?, along((node.x?, node.y?)))))
+        .min_by_key(|&(rank, _)| rank)
+        .map(|(_, coordinate)| coordinate)
+    else {
+        return;
+    };
+    let stretch = |rank: &mut usize, point: &mut Point| {
+        *rank *= factor;
+        let coordinate = match rank_axis {
+            RankAxis::X => &mut point.0,
+            RankAxis::Y => &mut point.1,
+        };
+        *coordinate = origin + (*coordinate - origin) * factor as f64;
+    };
+
+    for node in nodes {
+        if let (Some(rank), Some(x), Some(y)) = (node.rank.as_mut(), node.x.as_mut(), node.y.as_mut()) {
+            let mut point = (*x, *y);
+            stretch(rank, &mut point);
+            (*x, *y) = point;
+        }
+    }
+    for (rank, point) in waypoints {
+        stretch(rank, point);
+    }
+}
+
+/// Coordinate of every rank along the rank axis, ordered by rank. Evenly spaced ranks have
+/// equal differences between neighbouring entries.
+pub fn rank_positions(nodes: &[PlacedNode]) -> Vec<f64> {
+    rank_coordinates(nodes)
+        .map(|(_, ranks)| ranks.into_iter().map(|(_, coordinate)| coordinate).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,36 @@
+//! Layout and rendering of supply chains: trade goods produced at waypoints and the goods flowing
+//! between them, laid out with [`crate::layout`] and drawn as SVG, HTML or PNG. The `sugiyama`
+//! binary is a command line front end to this module.
+
+pub mod model;
+pub mod sample;
+pub mod theme;
+pub mod layout;
+pub mod validation;
+pub mod graph;
+pub mod placement;
+pub mod routing;
+pub mod render;
+pub mod nodes;
+pub mod edges;
+pub mod text;
+pub mod badges;
+pub mod export;
+
+pub use model::*;
+pub use sample::*;
+pub use theme::*;
+pub use layout::*;
+pub use validation::*;
+pub use graph::*;
+pub use placement::*;
+pub use routing::*;
+pub use render::*;
+pub use nodes::*;
+pub use edges::*;
+pub use text::*;
+pub use badges::*;
+pub use export::*;
+
+#[cfg(test)]
+mod tests;
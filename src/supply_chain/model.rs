@@ -0,0 +1,207 @@
+//! Nodes and edges of a supply chain: trade goods at waypoints and the goods flowing between them
+
+use crate::{LayoutNode, Point};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use strum::{Display, EnumIter, IntoEnumIterator};
+
+use super::*;
+
+pub type TradeGoodSymbol = String;
+pub type WaypointSymbol = String;
+pub type TradeGoodType = String;
+
+
+#[derive(
+    Serialize, Deserialize, Clone, Debug, Display, EnumIter, Eq, PartialEq, Hash, Ord, PartialOrd, clap::ValueEnum,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SupplyLevel {
+    Abundant,
+    High,
+    Moderate,
+    Limited,
+    Scarce,
+}
+
+#[derive(
+    Serialize, Deserialize, Clone, Debug, Display, EnumIter, Eq, PartialEq, Hash, Ord, PartialOrd, clap::ValueEnum,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ActivityLevel {
+    Weak,
+    Growing,
+    Strong,
+    Restricted,
+}
+
+impl SupplyLevel {
+    /// Amount of supply, from 0 for `Scarce` up to `Abundant`
+    pub fn amount(&self) -> u8 {
+        match self {
+            SupplyLevel::Scarce => 0,
+            SupplyLevel::Limited => 1,
+            SupplyLevel::Moderate => 2,
+            SupplyLevel::High => 3,
+            SupplyLevel::Abundant => 4,
+        }
+    }
+
+    /// `self` and every scarcer level, e.g. `Limited` and `Scarce` for `Limited`
+    pub fn at_or_below(&self) -> HashSet<SupplyLevel> {
+        SupplyLevel::iter().filter(|level| level.amount() <= self.amount()).collect()
+    }
+
+    /// `self` and every more abundant level
+    pub fn at_or_above(&self) -> HashSet<SupplyLevel> {
+        SupplyLevel::iter().filter(|level| level.amount() >= self.amount()).collect()
+    }
+}
+
+impl ActivityLevel {
+    /// Strength of the activity, from 0 for `Restricted` up to `Strong`; independent of the
+    /// declaration order, which isn't sorted
+    pub fn strength(&self) -> u8 {
+        match self {
+            ActivityLevel::Restricted => 0,
+            ActivityLevel::Weak => 1,
+            ActivityLevel::Growing => 2,
+            ActivityLevel::Strong => 3,
+        }
+    }
+
+    /// `self` and every weaker level, e.g. `Restricted`, `Weak` and `Growing` for `Growing`
+    pub fn at_or_below(&self) -> HashSet<ActivityLevel> {
+        ActivityLevel::iter().filter(|level| level.strength() <= self.strength()).collect()
+    }
+
+    /// `self` and every stronger level
+    pub fn at_or_above(&self) -> HashSet<ActivityLevel> {
+        ActivityLevel::iter().filter(|level| level.strength() >= self.strength()).collect()
+    }
+}
+
+/// (min_x, min_y, max_x, max_y)
+pub type Bounds = (f64, f64, f64, f64);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TechNode {
+    pub id: String,
+    pub name: TradeGoodSymbol,
+    pub waypoint_symbol: WaypointSymbol,
+    pub waypoint_type: TradeGoodType,
+    pub supply: SupplyLevel,
+    pub activity: ActivityLevel,
+    pub cost: u32,
+    pub volume: u32,
+    pub width: f64,
+    pub height: f64,
+    /// Last known center, e.g. from an earlier run. The layout keeps it for nodes it doesn't
+    /// position, see `UnpositionedEdges::UseLastKnown`; its own positions go to `LayoutResult`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+    /// Pre-formatted label (lines separated by `\n` or `<br/>`), rendered instead of the stats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Image shown in the top left corner of the node box
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_href: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TechEdge {
+    pub source: String,
+    pub target: String,
+    pub cost: u32,
+    pub activity: ActivityLevel,
+    pub volume: u32,
+    pub supply: SupplyLevel,
+    /// Last known route, kept like `TechNode::x`/`y` for edges the layout can't route
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points: Option<Vec<Point>>,
+    /// New fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profit: Option<i32>,  // Can be negative
+    /// How `points` should be drawn
+    #[serde(default)]
+    pub path_style: PathStyle,
+}
+
+/// Curved edges treat a single inner point as control point and pass smoothly through several,
+/// straight ones use the inner points as polyline corners
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PathStyle {
+    #[default]
+    Curved,
+    Straight,
+}
+
+/// ColorString newtype using Cow for efficiency
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ColorString(pub Cow<'static, str>);
+
+impl ColorString {
+    pub fn new(color: &str) -> Self {
+        Self(Cow::Owned(color.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for ColorString {
+    fn from(value: String) -> Self {
+        Self(Cow::Owned(value))
+    }
+}
+
+impl From<&'static str> for ColorString {
+    fn from(value: &'static str) -> Self {
+        Self(Cow::Borrowed(value))
+    }
+}
+
+/// Display implementation for easy formatting
+impl fmt::Display for ColorString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TechNode {
+    pub fn supply_color(&self, theme: &Theme) -> ColorString {
+        theme.supply_color(&self.supply)
+    }
+
+    pub fn activity_color(&self, theme: &Theme) -> ColorString {
+        theme.activity_color(&self.activity)
+    }
+}
+
+impl LayoutNode for TechNode {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn size(&self) -> (f64, f64) {
+        (self.width, self.height)
+    }
+}
+
+impl TechEdge {
+    pub fn supply_color(&self, theme: &Theme) -> ColorString {
+        theme.supply_color(&self.supply)
+    }
+
+    pub fn activity_color(&self, theme: &Theme) -> ColorString {
+        theme.activity_color(&self.activity)
+    }
+}
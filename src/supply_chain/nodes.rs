@@ -0,0 +1,295 @@
+//! Node boxes with their text, icons and fills
+
+use crate::NodeRenderer;
+use std::collections::HashMap;
+
+use super::*;
+
+/// Fill colors on a ramp between the theme's `depth_ramp` colors, from the sources to the deepest node
+pub(crate) fn depth_fill_colors(nodes: &[TechNode], edges: &[TechEdge], metric: DepthMetric, theme: &Theme) -> HashMap<String, ColorString> {
+    let depths = source_depths(nodes, edges);
+    let depth_of = |range: &DepthRange| match metric {
+        DepthMetric::Min => range.min,
+        DepthMetric::Max => range.max,
+    };
+    let deepest = depths.values().map(depth_of).max().unwrap_or(0).max(1);
+
+    let (from, to) = theme.depth_ramp;
+    let mix = |a: u8, b: u8, t: f64| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+    depths
+        .iter()
+        .map(|(id, range)| {
+            let t = depth_of(range) as f64 / deepest as f64;
+            let color = format!("#{:02x}{:02x}{:02x}", mix(from.0, to.0, t), mix(from.1, to.1, t), mix(from.2, to.2, t));
+            (id.clone(), ColorString::from(color))
+        })
+        .collect()
+}
+
+/// Titles for nodes whose `name` is shared with other nodes, keyed by node id.
+/// Nodes with a unique name keep it and don't appear in the result.
+pub(crate) fn disambiguated_titles(nodes: &[TechNode], mode: DuplicateNames) -> HashMap<String, String> {
+    let mut by_name: HashMap<&str, Vec<&TechNode>> = HashMap::new();
+    for node in nodes {
+        by_name.entry(node.name.as_str()).or_default().push(node);
+    }
+
+    let mut titles = HashMap::new();
+    for (name, group) in by_name {
+        if group.len() < 2 {
+            continue;
+        }
+        for (i, node) in group.iter().enumerate() {
+            let suffix = match mode {
+                DuplicateNames::Keep => continue,
+                DuplicateNames::Waypoint => node
+                    .waypoint_symbol
+                    .rsplit('-')
+                    .next()
+                    .unwrap_or(&node.waypoint_symbol)
+                    .to_string(),
+                DuplicateNames::Numbered => (i + 1).to_string(),
+            };
+            titles.insert(node.id.clone(), format!("{} ({})", name, suffix));
+        }
+    }
+
+    titles
+}
+
+/// Plain-text description of a node for screen readers
+pub(crate) fn node_description(node: &TechNode) -> String {
+    format!(
+        "{} at {} ({}): activity {}, supply {}, volume {}, price {}c",
+        node.name, node.waypoint_symbol, node.waypoint_type, node.activity, node.supply, node.volume, node.cost
+    )
+}
+
+/// Plain-text description of an edge for screen readers and tooltips, naming its endpoints
+/// by name and waypoint of their nodes in `nodes`
+pub(crate) fn edge_description(edge: &TechEdge, nodes: &[TechNode]) -> String {
+    let name = |id: &str| {
+        nodes
+            .iter()
+            .find(|node| node.id == id)
+            .map_or(id.to_string(), |node| format!("{} at {}", node.name, node.waypoint_symbol))
+    };
+    let mut description = format!(
+        "{} to {}: activity {}, supply {}, volume {}, price {}c",
+        name(&edge.source), name(&edge.target), edge.activity, edge.supply, edge.volume, edge.cost
+    );
+    if let Some(distance) = edge.distance {
+        description.push_str(&format!(", distance {}", distance));
+    }
+    if let Some(profit) = edge.profit {
+        description.push_str(&format!(", profit {:+}c", profit));
+    }
+    description
+}
+
+/// Node background for the corner style, from its (x, y, width, height) box.
+/// `paint` carries the fill and stroke attributes.
+pub(crate) fn node_shape_svg((x, y, width, height): (f64, f64, f64, f64), corners: CornerStyle, paint: &str) -> String {
+    match corners {
+        CornerStyle::Sharp => format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" {} />"#,
+            x, y, width, height, paint
+        ),
+        CornerStyle::Rounded(radius) => format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" rx="{}" ry="{}" {} />"#,
+            x, y, width, height, radius, radius, paint
+        ),
+        CornerStyle::Cut(size) => {
+            // Chamfers can't take more than half of a side
+            let c = size.min(width / 2.0).min(height / 2.0);
+            let (right, bottom) = (x + width, y + height);
+            format!(
+                r#"<path d="M{:.2},{:.2} H{:.2} L{:.2},{:.2} V{:.2} L{:.2},{:.2} H{:.2} L{:.2},{:.2} V{:.2} Z" {} />"#,
+                x + c, y, right - c, right, y + c, bottom - c, right - c, bottom, x + c, x, bottom - c, y + c, paint
+            )
+        }
+    }
+}
+
+/// Size of a node icon and its distance to the box border
+pub(crate) const ICON_SIZE: f64 = 32.0;
+pub(crate) const ICON_PADDING: f64 = 12.0;
+
+/// The node's icon reference, escaped for an attribute value. Empty references and ones
+/// that can't be part of a URL are ignored.
+pub(crate) fn node_icon_href(node: &TechNode) -> Option<String> {
+    let href = node.icon_href.as_deref()?.trim();
+    let invalid = href.is_empty() || href.chars().any(|c| c.is_whitespace() || matches!(c, '"' | '<' | '>'));
+    (!invalid).then(|| href.replace('&', "&amp;"))
+}
+
+/// Font size multiplier of a node's first line, if it is emphasized
+pub(crate) fn title_multiplier(node: &TechNode, config: &RenderConfig) -> Option<f64> {
+    config
+        .title_emphasis_override
+        .and_then(|title_emphasis| title_emphasis(node))
+        .unwrap_or(config.title_emphasis)
+        .multiplier()
+}
+
+/// Text lines of a node with their colors: the pre-formatted label if there is one, the stats otherwise
+pub(crate) fn node_text_lines(node: &TechNode, style: &NodeStyle, theme: &Theme) -> Vec<(String, ColorString)> {
+    // Colors
+    let bold_text_color = theme.title_text.clone();
+    let normal_text_color = theme.text.clone();
+
+    if let Some(ref label) = node.label {
+        return split_label_lines(label)
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let color = if i == 0 { bold_text_color.clone() } else { normal_text_color.clone() };
+                (line, color)
+            })
+            .collect();
+    }
+
+    vec![
+        // Name (bold, title font)
+        (style.title.clone().unwrap_or_else(|| node.name.clone()), bold_text_color),
+        // Waypoint symbol
+        (node.waypoint_symbol.clone(), normal_text_color.clone()),
+        // Waypoint type
+        (node.waypoint_type.clone(), normal_text_color.clone()),
+        // Activity
+        (format!("A: {}", node.activity), node.activity_color(theme)),
+        // Supply
+        (format!("S: {}", node.supply), node.supply_color(theme)),
+        // Volume
+        (format!("v: {}", node.volume), normal_text_color.clone()),
+        // Costs
+        (format!("p: {}c", node.cost), normal_text_color),
+    ]
+}
+
+/// Draws a node the way `output_svg` does, without the graph-wide adjustments of `NodeStyle`
+impl NodeRenderer<TechNode> for RenderConfig {
+    fn render_svg(&self, node: &TechNode, x: f64, y: f64) -> String {
+        let placed = PlacedNode { node, rank: None, x: Some(x), y: Some(y) };
+        generate_node_svg(&placed, self, &NodeStyle::default())
+    }
+}
+
+/// Refactored node SVG generator with increased padding and first line font size multiplier
+pub fn generate_node_svg(node: &PlacedNode, config: &RenderConfig, style: &NodeStyle) -> String {
+    if let (Some(x), Some(y)) = (node.x, node.y) {
+        // Get activity color for border
+        let border_color = node.activity_color(&config.theme);
+
+        // Get color based on node type
+        let fill_color = style.fill.clone().unwrap_or_else(|| config.theme.node_fill(&node.waypoint_type));
+
+        // Layout parameters
+        let node_x = x - node.width / 2.0;
+        let node_y = y - node.height / 2.0;
+        let text_right_x = x + node.width / 2.0 - 16.0;  // Increased padding from 10px to 16px
+        let line_height = 20.0;
+
+        // Text styling
+        let font_family = config.font_family();
+        let normal_font_size = 10;
+        let title_font_size_multiplier = title_multiplier(node, config);
+        let border_width = 4;
+
+        // Prepare text lines with their colors
+        // A pre-formatted label replaces the fixed stats layout and is rendered centered
+        let text_lines = node_text_lines(node, style, &config.theme);
+        // The icon takes a column on the left. Centered labels move out of it, right-aligned
+        // stats keep clear of it as long as they fit (see `check_label_fit`).
+        let icon = node_icon_href(node).map(|href| {
+            format!(
+                r#"<image href="{}" x="{:.2}" y="{:.2}" width="{}" height="{}" preserveAspectRatio="xMidYMid meet" />"#,
+                href, node_x + ICON_PADDING, node_y + ICON_PADDING, ICON_SIZE, ICON_SIZE
+            )
+        });
+        let icon_column = if icon.is_some() { ICON_SIZE + ICON_PADDING } else { 0.0 };
+
+        // The text block spans from the top of the first line to the last baseline. Top
+        // alignment keeps the first baseline 30px below the box top; the other alignments
+        // keep the same distance between block and box border.
+        let max_text_width = config.wrap_node_text.then_some(node.width - 2.0 * 16.0 - icon_column);
+        let rows = wrap_text_rows(&text_lines, normal_font_size as f64, title_font_size_multiplier, max_text_width);
+        let first_size = normal_font_size as f64 * title_font_size_multiplier.unwrap_or(1.0);
+        let block_height = text_rows_height(&rows, normal_font_size as f64, line_height, title_font_size_multiplier);
+        let inset = 30.0 - first_size;
+        // Wrapped text may need more room than a box of fixed size has (see `fit_nodes_to_text`),
+        // which then grows downwards
+        let box_height = match max_text_width {
+            Some(_) => node.height.max(inset + block_height + line_height / 2.0),
+            None => node.height,
+        };
+        let block_top = match config.node_text_align {
+            VerticalAlign::Top => inset,
+            VerticalAlign::Center => (box_height - block_height) / 2.0,
+            VerticalAlign::Bottom => box_height - inset - block_height,
+        };
+        let text_y = node_y + block_top + first_size;
+
+        let (text_x, text_anchor) = if node.label.is_some() {
+            (x + icon_column / 2.0, "middle")
+        } else {
+            (text_right_x, "end")
+        };
+
+        // The description doubles as the tooltip browsers show for the <title>
+        let description = escape_xml(&node_description(node)).into_owned();
+        let accessibility_attrs = if config.accessibility {
+            format!(r#" role="img" aria-label="{description}""#)
+        } else {
+            format!(r#" aria-label="{description}""#)
+        };
+
+        // Ghosts are faded and outlined with dashes
+        let (ghost_attrs, ghost_border) = if style.ghost {
+            (r#" class="ghost" opacity="0.45""#, r#" stroke-dasharray="8,6""#)
+        } else if style.dimmed {
+            (r#" class="dimmed" opacity="0.25""#, "")
+        } else {
+            ("", "")
+        };
+
+        format!(
+            r#"<g{accessibility_attrs}{ghost_attrs}>
+                <title>{description}</title>
+                <!-- Node background -->
+                {}
+                {}
+
+                <!-- Node text content (using multiline text) -->
+                {}
+                {}
+            </g>"#,
+            node_shape_svg(
+                (node_x, node_y, node.width, box_height),
+                config.corner_style,
+                &format!(r#"fill="{fill_color}" stroke="{border_color}" stroke-width="{border_width}"{ghost_border}"#),
+            ),
+            icon.unwrap_or_default(),
+            generate_multiline_text_svg(
+                text_x,                    // x position (right-aligned with padding, or centered for labels)
+                text_y,                    // y position of the first baseline
+                &text_lines,               // text content and colors
+                text_anchor,               // right-aligned text, centered for labels
+                font_family,               // font family
+                normal_font_size,          // font size
+                line_height,               // line spacing
+                None,                      // no special baseline alignment
+                title_font_size_multiplier, // Increase size of first line
+                max_text_width,            // wrap at the box width if enabled
+            ),
+            config
+                .badges
+                .map(|badges| generate_badges_svg(node, &badges(node, &config.theme), &config.theme))
+                .unwrap_or_default(),
+        )
+    } else {
+        // Return empty string if node has no position
+        String::new()
+    }
+}
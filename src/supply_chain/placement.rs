@@ -0,0 +1,480 @@
+//! Post-processing moving placed nodes within their ranks
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::*;
+
+/// Push ranks apart until the labels `output_svg` puts along the edges with `placement` fit
+/// between the node faces of both neighbouring ranks. Gaps without labels keep their size.
+pub(crate) fn fit_edge_label_gaps(nodes: &mut [PlacedNode], edges: &[TechEdge], axis: RankAxis, placement: LabelPlacement) {
+    let padding = 20.0;
+    let needed_gap = match axis {
+        RankAxis::X => EDGE_LABEL_WIDTH,
+        RankAxis::Y => EDGE_LABEL_HEIGHT,
+    } + 2.0 * padding;
+    let along = |node: &PlacedNode| match axis {
+        RankAxis::X => node.x,
+        RankAxis::Y => node.y,
+    };
+
+    // Rank coordinate and the largest half extent of its nodes along the rank axis
+    let mut ranks: BTreeMap<usize, (f64, f64)> = BTreeMap::new();
+    for node in nodes.iter() {
+        if let (Some(rank), Some(coordinate)) = (node.rank, along(node)) {
+            let half_extent = match axis {
+                RankAxis::X => node.width / 2.0,
+                RankAxis::Y => node.height / 2.0,
+            };
+            let entry = ranks.entry(rank).or_insert((coordinate, 0.0));
+            entry.1 = entry.1.max(half_extent);
+        }
+    }
+
+    // A label sits in the gap next to the end it is placed at, on the side facing the other
+    // end, or in the middle gap of the ranks the edge spans
+    let rank_of: HashMap<&str, usize> = nodes
+        .iter()
+        .filter_map(|node| node.rank.map(|rank| (node.id.as_str(), rank)))
+        .collect();
+    let mut labeled_gaps: HashSet<usize> = HashSet::new();
+    for edge in edges {
+        if let (Some(&source), Some(&target)) = (rank_of.get(edge.source.as_str()), rank_of.get(edge.target.as_str())) {
+            // Gaps are numbered by the rank in front of them
+            if source == target {
+                continue;
+            }
+            let (low, high) = (source.min(target), source.max(target));
+            let gap = match placement {
+                LabelPlacement::Target if source < target => target - 1,
+                LabelPlacement::Source if source > target => source - 1,
+                LabelPlacement::Target => target,
+                LabelPlacement::Source => source,
+                LabelPlacement::Midpoint => (low + high - 1) / 2,
+            };
+            labeled_gaps.insert(gap);
+        }
+    }
+
+    // Shift along the rank axis accumulated by every rank
+    let mut shifts: HashMap<usize, f64> = HashMap::new();
+    let mut shift = 0.0;
+    let ranks: Vec<(usize, (f64, f64))> = ranks.into_iter().collect();
+    for pair in ranks.windows(2) {
+        let [(rank, (coordinate, half)), (next_rank, (next_coordinate, next_half))] = *pair else {
+            continue;
+        };
+        let direction = (next_coordinate - coordinate).signum();
+        let gap = (next_coordinate - coordinate).abs() - half - next_half;
+        if (rank..next_rank).any(|gap_rank| labeled_gaps.contains(&gap_rank)) && gap < needed_gap {
+            shift += direction * (needed_gap - gap);
+        }
+        shifts.insert(next_rank, shift);
+    }
+
+    for node in nodes.iter_mut() {
+        let Some(&shift) = node.rank.and_then(|rank| shifts.get(&rank)) else {
+            continue;
+        };
+        match axis {
+            RankAxis::X => node.x = node.x.map(|x| x + shift),
+            RankAxis::Y => node.y = node.y.map(|y| y + shift),
+        }
+    }
+}
+
+/// Permute the nodes of every rank onto the rank's existing slots according to `order`.
+/// Slots are the cross-axis coordinates the layout assigned, so spacing stays untouched.
+pub(crate) fn apply_secondary_order(nodes: &mut [PlacedNode], edges: &[TechEdge], order: &SecondaryOrder, axis: RankAxis) {
+    let cross_of = |node: &PlacedNode| match axis {
+        RankAxis::X => node.y,
+        RankAxis::Y => node.x,
+    };
+
+    let mut cross: Vec<Option<f64>> = nodes.iter().map(cross_of).collect();
+    let index_of: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    for edge in edges {
+        if let (Some(&s), Some(&t)) = (index_of.get(edge.source.as_str()), index_of.get(edge.target.as_str())) {
+            neighbors[s].push(t);
+            neighbors[t].push(s);
+        }
+    }
+
+    let mut ranks: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if let (Some(rank), Some(_)) = (node.rank, cross[i]) {
+            ranks.entry(rank).or_default().push(i);
+        }
+    }
+
+    let keys: Vec<i64> = nodes.iter().map(|node| (order.key)(node)).collect();
+
+    // Crossings between the edges of `a` and `b` when `a` sits before `b`, per neighbouring side
+    let crossings = |a: usize, b: usize, cross: &[Option<f64>]| {
+        let rank = nodes[a].rank;
+        let sides = |n: usize| {
+            neighbors[n]
+                .iter()
+                .filter_map(|&m| Some((nodes[m].rank? < rank?, cross[m]?)))
+                .collect::<Vec<_>>()
+        };
+        let (sides_a, sides_b) = (sides(a), sides(b));
+        sides_a
+            .iter()
+            .flat_map(|&(side_a, pos_a)| {
+                sides_b
+                    .iter()
+                    .filter(move |&&(side_b, pos_b)| side_a == side_b && pos_a > pos_b)
+            })
+            .count()
+    };
+
+    for members in ranks.values_mut() {
+        members.sort_by(|&a, &b| cross[a].unwrap().total_cmp(&cross[b].unwrap()));
+        let slots: Vec<Option<f64>> = members.iter().map(|&i| cross[i]).collect();
+
+        match order.mode {
+            SecondaryOrderMode::TieBreak => {
+                for _ in 0..members.len() {
+                    let mut swapped = false;
+                    for j in 1..members.len() {
+                        let (u, v) = (members[j - 1], members[j]);
+                        if keys[v] < keys[u] && crossings(v, u, &cross) <= crossings(u, v, &cross) {
+                            members.swap(j - 1, j);
+                            (cross[u], cross[v]) = (cross[v], cross[u]);
+                            swapped = true;
+                        }
+                    }
+                    if !swapped {
+                        break;
+                    }
+                }
+            }
+            SecondaryOrderMode::Group => {
+                let mut groups: Vec<(i64, Vec<usize>)> = Vec::new();
+                for &member in members.iter() {
+                    match groups.iter_mut().find(|(key, _)| *key == keys[member]) {
+                        Some((_, group)) => group.push(member),
+                        None => groups.push((keys[member], vec![member])),
+                    }
+                }
+
+                let position: HashMap<usize, usize> = members.iter().enumerate().map(|(p, &m)| (m, p)).collect();
+                let mean_position = |group: &[usize]| {
+                    group.iter().map(|m| position[m] as f64).sum::<f64>() / group.len() as f64
+                };
+                groups.sort_by(|a, b| mean_position(&a.1).total_cmp(&mean_position(&b.1)));
+
+                *members = groups.into_iter().flat_map(|(_, group)| group).collect();
+            }
+        }
+
+        for (&member, &slot) in members.iter().zip(&slots) {
+            cross[member] = slot;
+        }
+    }
+
+    for (node, value) in nodes.iter_mut().zip(cross) {
+        match axis {
+            RankAxis::X => node.y = value,
+            RankAxis::Y => node.x = value,
+        }
+    }
+}
+
+/// Crossing minimization by edge weight, which rust-sugiyama lacks: neighbouring nodes of a rank
+/// trade places while that lowers the crossings between their edges, a crossing counting the
+/// product of both edge weights. Swapped nodes keep the span and the gap they covered. Sweeps
+/// over all ranks until no swap helps, at most `max_iterations` times, or until a sweep removes
+/// less than `improvement_epsilon` of the weighted crossings before it.
+pub(crate) fn reduce_weighted_crossings(
+    nodes: &mut [PlacedNode],
+    edges: &[TechEdge],
+    axis: RankAxis,
+    weight_by: WeightBy,
+    max_iterations: usize,
+    improvement_epsilon: f64,
+) {
+    let cross_of = |node: &PlacedNode| match axis {
+        RankAxis::X => node.y,
+        RankAxis::Y => node.x,
+    };
+    let extent_of = |node: &PlacedNode| match axis {
+        RankAxis::X => node.height,
+        RankAxis::Y => node.width,
+    };
+
+    let mut cross: Vec<Option<f64>> = nodes.iter().map(cross_of).collect();
+    let index_of: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+
+    let mut neighbors: Vec<Vec<(usize, f64)>> = vec![Vec::new(); nodes.len()];
+    for edge in edges {
+        if let (Some(&s), Some(&t)) = (index_of.get(edge.source.as_str()), index_of.get(edge.target.as_str()))
+            && nodes[s].rank != nodes[t].rank
+        {
+            let weight = weight_by.weight(edge);
+            neighbors[s].push((t, weight));
+            neighbors[t].push((s, weight));
+        }
+    }
+
+    let mut ranks: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if let (Some(rank), Some(_)) = (node.rank, cross[i]) {
+            ranks.entry(rank).or_default().push(i);
+        }
+    }
+
+    // Weighted crossings between the edges of `a` and `b` when `a` sits before `b`, per
+    // neighbouring side
+    let crossings = |a: usize, b: usize, cross: &[Option<f64>]| -> f64 {
+        let rank = nodes[a].rank;
+        let sides = |n: usize| {
+            neighbors[n]
+                .iter()
+                .filter_map(|&(m, weight)| Some((nodes[m].rank? < rank?, cross[m]?, weight)))
+                .collect::<Vec<_>>()
+        };
+        let (sides_a, sides_b) = (sides(a), sides(b));
+        sides_a
+            .iter()
+            .flat_map(|&(side_a, pos_a, weight_a)| {
+                sides_b
+                    .iter()
+                    .filter(move |&&(side_b, pos_b, _)| side_a == side_b && pos_a > pos_b)
+                    .map(move |&(_, _, weight_b)| weight_a * weight_b)
+            })
+            .sum()
+    };
+
+    // Weighted crossings of the current order, each counted from both ranks of its edges
+    let total = |ranks: &BTreeMap<usize, Vec<usize>>, cross: &[Option<f64>]| -> f64 {
+        ranks
+            .values()
+            .flat_map(|members| members.iter().enumerate().flat_map(move |(j, &u)| members[j + 1..].iter().map(move |&v| (u, v))))
+            .map(|(u, v)| if cross[u] <= cross[v] { crossings(u, v, cross) } else { crossings(v, u, cross) })
+            .sum()
+    };
+    let mut weighted_crossings = if improvement_epsilon > 0.0 { total(&ranks, &cross) } else { 0.0 };
+
+    // Every swap lowers the weighted crossings, the default bound only guards against float noise
+    for _ in 0..max_iterations {
+        let mut swapped = false;
+        for members in ranks.values_mut() {
+            members.sort_by(|&a, &b| cross[a].unwrap().total_cmp(&cross[b].unwrap()));
+            for j in 1..members.len() {
+                let (u, v) = (members[j - 1], members[j]);
+                if crossings(v, u, &cross) < crossings(u, v, &cross) {
+                    let (start, end) = (cross[u].unwrap() - extent_of(&nodes[u]) / 2.0, cross[v].unwrap() + extent_of(&nodes[v]) / 2.0);
+                    cross[v] = Some(start + extent_of(&nodes[v]) / 2.0);
+                    cross[u] = Some(end - extent_of(&nodes[u]) / 2.0);
+                    members.swap(j - 1, j);
+                    swapped = true;
+                }
+            }
+        }
+        if !swapped {
+            break;
+        }
+        if improvement_epsilon > 0.0 {
+            let before_sweep = std::mem::replace(&mut weighted_crossings, total(&ranks, &cross));
+            if before_sweep - weighted_crossings < improvement_epsilon * before_sweep {
+                break;
+            }
+        }
+    }
+
+    for (node, value) in nodes.iter_mut().zip(cross) {
+        match axis {
+            RankAxis::X => node.y = value,
+            RankAxis::Y => node.x = value,
+        }
+    }
+}
+
+/// Coordinate optimization after ordering: every node is pulled across its rank toward the
+/// median of its neighbours' positions, weighted by `weight_by` if given. Each rank is then
+/// placed as close as possible (least squares) to those targets while keeping its order and the
+/// layout's smallest gap between nodes.
+pub fn minimize_edge_length(nodes: &mut [PlacedNode], edges: &[TechEdge], axis: RankAxis, weight_by: Option<WeightBy>) {
+    let iterations = 8;
+    let index_of: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+    // (neighbour, weight) of every node
+    let mut neighbors: Vec<Vec<(usize, f64)>> = vec![Vec::new(); nodes.len()];
+    for edge in edges {
+        if let (Some(&s), Some(&t)) = (index_of.get(edge.source.as_str()), index_of.get(edge.target.as_str()))
+            && nodes[s].rank != nodes[t].rank
+        {
+            let weight = weight_by.map_or(1.0, |weight_by| weight_by.weight(edge));
+            neighbors[s].push((t, weight));
+            neighbors[t].push((s, weight));
+        }
+    }
+
+    let cross_of = |node: &PlacedNode| match axis {
+        RankAxis::X => node.y,
+        RankAxis::Y => node.x,
+    };
+    let half_extent = |node: &PlacedNode| match axis {
+        RankAxis::X => node.height / 2.0,
+        RankAxis::Y => node.width / 2.0,
+    };
+
+    let mut cross: Vec<Option<f64>> = nodes.iter().map(cross_of).collect();
+    let mut ranks: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if let (Some(rank), Some(_)) = (node.rank, cross[i]) {
+            ranks.entry(rank).or_default().push(i);
+        }
+    }
+    for members in ranks.values_mut() {
+        members.sort_by(|&a, &b| cross[a].unwrap().total_cmp(&cross[b].unwrap()));
+    }
+
+    // Keep the density of the input layout
+    let gap = ranks
+        .values()
+        .flat_map(|members| {
+            members.windows(2).map(|pair| {
+                cross[pair[1]].unwrap() - cross[pair[0]].unwrap() - half_extent(&nodes[pair[0]]) - half_extent(&nodes[pair[1]])
+            })
+        })
+        .filter(|gap| *gap > 0.0)
+        .fold(None, |min: Option<f64>, gap| Some(min.map_or(gap, |min| min.min(gap))))
+        .unwrap_or(30.0);
+
+    for _ in 0..iterations {
+        for members in ranks.values() {
+            let targets: Vec<f64> = members
+                .iter()
+                .map(|&member| {
+                    let mut positions: Vec<(f64, f64)> =
+                        neighbors[member].iter().filter_map(|&(n, weight)| cross[n].map(|position| (position, weight))).collect();
+                    if positions.is_empty() {
+                        return cross[member].unwrap();
+                    }
+                    positions.sort_by(|a, b| a.0.total_cmp(&b.0));
+                    // Weighted median; where half the weight lies exactly on either side, the
+                    // middle between the two positions (the plain median for unit weights)
+                    let half = positions.iter().map(|&(_, weight)| weight).sum::<f64>() / 2.0;
+                    let mut below = 0.0;
+                    for (i, &(position, weight)) in positions.iter().enumerate() {
+                        below += weight;
+                        if below == half {
+                            return (position + positions[i + 1].0) / 2.0;
+                        }
+                        if below > half {
+                            return position;
+                        }
+                    }
+                    positions[positions.len() - 1].0
+                })
+                .collect();
+
+            // Shift out the required separation so the constraint becomes plain monotonicity,
+            // then fit with pool adjacent violators
+            let mut offsets = vec![0.0; members.len()];
+            for i in 1..members.len() {
+                offsets[i] = offsets[i - 1] + half_extent(&nodes[members[i - 1]]) + half_extent(&nodes[members[i]]) + gap;
+            }
+            // (mean, count) of merged blocks
+            let mut blocks: Vec<(f64, usize)> = Vec::new();
+            for (target, offset) in targets.iter().zip(&offsets) {
+                blocks.push((target - offset, 1));
+                while blocks.len() > 1 && blocks[blocks.len() - 2].0 > blocks[blocks.len() - 1].0 {
+                    let (mean_b, count_b) = blocks.pop().unwrap();
+                    let (mean_a, count_a) = blocks.pop().unwrap();
+                    let count = count_a + count_b;
+                    blocks.push(((mean_a * count_a as f64 + mean_b * count_b as f64) / count as f64, count));
+                }
+            }
+
+            let fitted = blocks.iter().flat_map(|&(mean, count)| std::iter::repeat_n(mean, count));
+            for ((&member, offset), value) in members.iter().zip(&offsets).zip(fitted) {
+                cross[member] = Some(value + offset);
+            }
+        }
+    }
+
+    for (node, value) in nodes.iter_mut().zip(cross) {
+        match axis {
+            RankAxis::X => node.y = value,
+            RankAxis::Y => node.x = value,
+        }
+    }
+}
+
+/// Move the inner nodes of every maximal chain of nodes with exactly one incoming and one
+/// outgoing edge onto the straight line between the chain's end nodes. A node stays where it
+/// is if its new place would overlap another node of its rank.
+pub fn straighten_chains(nodes: &mut [PlacedNode], edges: &[TechEdge], axis: RankAxis) {
+    let index_of: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+    let mut incoming = vec![0usize; nodes.len()];
+    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    for edge in edges {
+        if let (Some(&s), Some(&t)) = (index_of.get(edge.source.as_str()), index_of.get(edge.target.as_str()))
+            && s != t
+        {
+            outgoing[s].push(t);
+            incoming[t] += 1;
+        }
+    }
+    let inner = |n: usize| incoming[n] == 1 && outgoing[n].len() == 1;
+
+    // (coordinate along the rank axis, coordinate across it, half extent across it)
+    let split = |node: &PlacedNode| {
+        let (x, y) = (node.x?, node.y?);
+        Some(match axis {
+            RankAxis::X => (x, y, node.height / 2.0),
+            RankAxis::Y => (y, x, node.width / 2.0),
+        })
+    };
+
+    for start in 0..nodes.len() {
+        if inner(start) {
+            continue;
+        }
+        for &first in &outgoing[start] {
+            let mut chain = Vec::new();
+            let mut current = first;
+            while inner(current) && !chain.contains(&current) {
+                chain.push(current);
+                current = outgoing[current][0];
+            }
+            let end = current;
+            if chain.is_empty() {
+                continue;
+            }
+
+            let (Some((start_along, start_cross, _)), Some((end_along, end_cross, _))) =
+                (split(&nodes[start]), split(&nodes[end]))
+            else {
+                continue;
+            };
+            if (end_along - start_along).abs() < f64::EPSILON {
+                continue;
+            }
+
+            for &member in &chain {
+                let Some((along, _, half)) = split(&nodes[member]) else {
+                    continue;
+                };
+                let t = (along - start_along) / (end_along - start_along);
+                let target = start_cross + (end_cross - start_cross) * t;
+
+                let overlaps = nodes.iter().enumerate().any(|(other, node)| {
+                    other != member
+                        && node.rank == nodes[member].rank
+                        && split(node).is_some_and(|(_, cross, other_half)| (cross - target).abs() < half + other_half)
+                });
+                if !overlaps {
+                    match axis {
+                        RankAxis::X => nodes[member].y = Some(target),
+                        RankAxis::Y => nodes[member].x = Some(target),
+                    }
+                }
+            }
+        }
+    }
+}
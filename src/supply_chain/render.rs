@@ -0,0 +1,1112 @@
+//! Rendering a computed layout as SVG
+
+use crate::{Point, Transform};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Instant;
+use strum::{Display, IntoEnumIterator};
+
+use super::*;
+
+/// Suggested zoom range for interactive viewers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZoomHints {
+    pub min_zoom: f64,
+    pub max_zoom: f64,
+    /// Scale the drawing is emitted at relative to the layout, below 1 where `max_width` or
+    /// `max_height` shrink it; zooming by its inverse shows the layout 1:1
+    pub natural_scale: f64,
+}
+
+/// Whether and how zoom hints are embedded as `data-*` attributes on the root `<svg>`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ZoomMetadata {
+    #[default]
+    Off,
+    /// Derive the range from the canvas size and node density
+    Auto,
+    Fixed(ZoomHints),
+}
+
+/// `off`, `auto` or fixed hints as `<min zoom>:<max zoom>:<natural scale>`
+impl FromStr for ZoomMetadata {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(ZoomMetadata::Off),
+            "auto" => Ok(ZoomMetadata::Auto),
+            _ => match value.split(':').map(parse_number).collect::<Result<Vec<f64>, _>>()?[..] {
+                [min_zoom, max_zoom, natural_scale] => Ok(ZoomMetadata::Fixed(ZoomHints { min_zoom, max_zoom, natural_scale })),
+                _ => Err(format!("invalid zoom hints '{}', expected off, auto or <min>:<max>:<natural scale>", value)),
+            },
+        }
+    }
+}
+
+/// Number given on the command line as part of a larger value
+pub(crate) fn parse_number(value: &str) -> Result<f64, String> {
+    value.parse().map_err(|e| format!("invalid number '{}': {}", value, e))
+}
+
+/// `--scale-bar` as the length in layout units and the name shown after it, e.g. `100:distance units`
+pub fn parse_scale_bar(value: &str) -> Result<(f64, String), String> {
+    match value.split_once(':') {
+        Some((units, unit_name)) if !unit_name.is_empty() => Ok((parse_number(units)?, unit_name.to_string())),
+        _ => Err(format!("invalid scale bar '{}', expected <units>:<name>", value)),
+    }
+}
+
+/// Options controlling how `output_svg` renders an already computed layout
+#[derive(Clone, Debug, Default)]
+pub struct RenderConfig {
+    /// Draw alternating translucent bands behind the nodes of each rank
+    pub rank_bands: bool,
+    /// Draw thin dotted lines through the center of each rank
+    pub rank_guides: bool,
+    pub zoom_metadata: ZoomMetadata,
+    /// Emit edge paths with relative commands (`m`, `l`, `q`) for smaller output
+    pub relative_paths: bool,
+    /// Connect edge labels that sit away from their edge back to their anchor point
+    pub label_leaders: bool,
+    /// Add `role`/`aria-*` attributes plus a `<title>` and `<desc>` for screen readers. Nodes and
+    /// edge labels carry an `aria-label` and a `<title>` tooltip either way; this adds `role="img"`.
+    pub accessibility: bool,
+    /// Title announced for the whole diagram (defaults to "Supply chain diagram")
+    pub title: Option<String>,
+    /// Fill nodes along a color ramp by their distance from the sources instead of by type
+    pub depth_coloring: Option<DepthMetric>,
+    /// Show nodes without coordinates as ghosts in a row below the layout instead of omitting them
+    pub park_unpositioned: bool,
+    /// How titles of nodes sharing the same `name` are told apart
+    pub duplicate_names: DuplicateNames,
+    /// `stroke-linecap` and `stroke-linejoin` of edge paths
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    /// Text colors of the edge label fields
+    pub label_colors: EdgeLabelColors,
+    /// Combine the labels of parallel edges (same source and target) into one, e.g. `sum_parallel_edges`
+    pub label_merge: Option<LabelMergeFn>,
+    /// Background box of edge labels
+    pub label_box: LabelBoxStyle,
+    /// Where along its edge a label is placed
+    pub label_placement: LabelPlacement,
+    /// Inclusive range of profits drawn in the theme's neutral profit color rather than the
+    /// profitable or unprofitable one, e.g. `Some((-20, 20))`. Applies when no
+    /// `label_colors.profit` resolver is given.
+    pub profit_neutral_band: Option<(i32, i32)>,
+    /// Labeled bar in the bottom left corner showing a known length in layout units
+    pub scale_bar: Option<ScaleBar>,
+    /// Key to the supply, activity and node type colors below the drawing
+    pub include_legend: bool,
+    /// Corners of the node boxes
+    pub corner_style: CornerStyle,
+    /// Emphasis of the first text line of a node
+    pub title_emphasis: TitleEmphasis,
+    /// Per-node emphasis; `None` falls back to `title_emphasis`
+    pub title_emphasis_override: Option<fn(&TechNode) -> Option<TitleEmphasis>>,
+    /// Wrap the nodes of each rank in `<g id="rank-{n}" class="rank">` and the edges in `<g id="edges">`
+    /// (both prefixed by `id_prefix`)
+    pub group_ranks: bool,
+    /// Scale node boxes by an attribute before layout, applied through `apply_node_sizes`
+    pub size_fn: Option<NodeSizing>,
+    /// Keep the `width`/`height` the nodes come with instead of sizing their boxes to their
+    /// text in `fit_nodes_to_text`
+    pub fixed_node_sizes: bool,
+    /// Break node text lines wider than the box at spaces and underscores, growing the box
+    /// downwards when the wrapped text needs more height
+    pub wrap_node_text: bool,
+    /// CSS font stack of all text, defaults to `DEFAULT_FONT_FAMILY`.
+    /// Quote names containing spaces with single quotes, e.g. `'Segoe UI', sans-serif`.
+    pub font_family: Option<String>,
+    /// Small state indicators drawn on the corners of a node
+    pub badges: Option<BadgeFn>,
+    /// Colors of everything drawn, e.g. `Theme::named(ThemeName::Light)`
+    pub theme: Theme,
+    /// Ids of the nodes to emphasize, e.g. a node with its `ancestors` and `descendants`.
+    /// Other nodes, and edges with an endpoint among them, are dimmed.
+    pub focus: Option<HashSet<String>>,
+    /// Like `focus`, emphasizing the nodes of the given supply or activity levels
+    pub level_filter: Option<LevelFilter>,
+    /// Largest canvas size in pixels; bigger drawings are scaled down as a whole to fit
+    pub max_width: Option<f64>,
+    pub max_height: Option<f64>,
+    /// Drop polyline corners closer than this to the simplified path, see `simplify_edge_paths`
+    pub simplify_tolerance: Option<f64>,
+    /// Border around the whole diagram, drawn inside the canvas margin
+    pub frame: Option<Frame>,
+    /// Placement of the text block within the node box
+    pub node_text_align: VerticalAlign,
+    /// Dimensions of the root `<svg>`; the drawing always scales into its `viewBox`
+    pub svg_size: SvgSize,
+    /// Prepended to every id the SVG defines (arrowhead markers, title, groups), so several
+    /// diagrams inlined into one HTML page don't resolve each other's references
+    pub id_prefix: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SvgSize {
+    /// `width="100%"`, so the drawing scales to its container
+    #[default]
+    Responsive,
+    /// One pixel per unit of the drawing, after `max_width`/`max_height`
+    Natural,
+    /// Fixed size in pixels, the drawing is centered in it keeping its aspect ratio
+    Fixed { width: f64, height: f64 },
+}
+
+/// `responsive`, `natural` or a fixed `<width>x<height>`
+impl FromStr for SvgSize {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "responsive" => Ok(SvgSize::Responsive),
+            "natural" => Ok(SvgSize::Natural),
+            _ => match value.split_once('x') {
+                Some((width, height)) => Ok(SvgSize::Fixed { width: parse_number(width)?, height: parse_number(height)? }),
+                None => Err(format!("invalid SVG size '{}', expected responsive, natural or <width>x<height>", value)),
+            },
+        }
+    }
+}
+
+/// Matches nodes whose supply is in `supply` or whose activity is in `activity`, e.g.
+/// `LevelFilter { supply: SupplyLevel::Limited.at_or_below(), ..Default::default() }`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LevelFilter {
+    pub supply: HashSet<SupplyLevel>,
+    pub activity: HashSet<ActivityLevel>,
+}
+
+impl LevelFilter {
+    pub fn matches(&self, node: &TechNode) -> bool {
+        self.supply.contains(&node.supply) || self.activity.contains(&node.activity)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LabelPlacement {
+    /// In front of the source, next to where the edge leaves it
+    Source,
+    /// Centered on the middle of the drawn path
+    Midpoint,
+    /// In front of the target, next to where the edge enters it
+    #[default]
+    Target,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// Defaults to the theme's edge color
+    pub stroke: Option<ColorString>,
+    pub stroke_width: f64,
+    /// Distance from the canvas border, should stay below `SVG_MARGIN`
+    pub inset: f64,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            stroke: None,
+            stroke_width: 1.0,
+            inset: 10.0,
+        }
+    }
+}
+
+/// Fallback for rasterizers that lack Arial
+pub const DEFAULT_FONT_FAMILY: &str = "Arial, Helvetica, sans-serif";
+
+impl RenderConfig {
+    pub fn font_family(&self) -> &str {
+        self.font_family.as_deref().unwrap_or(DEFAULT_FONT_FAMILY)
+    }
+
+    /// Document id `name` with `id_prefix` applied
+    pub fn id(&self, name: &str) -> String {
+        format!("{}{}", self.id_prefix, name)
+    }
+}
+
+/// Node box scaling by an importance value such as `volume`, see `apply_node_sizes`
+#[derive(Clone, Copy, Debug)]
+pub struct NodeSizing {
+    pub size_fn: fn(&TechNode) -> f64,
+    /// Factors applied to `width`/`height` of the nodes with the smallest and largest value
+    pub min_scale: f64,
+    pub max_scale: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScaleBar {
+    /// Pixels per layout unit along x, see `SupplyChainLayout::scale_bar`
+    pub pixels_per_unit: f64,
+    /// Length of the bar in layout units
+    pub units: f64,
+    /// Shown after the length, e.g. "distance units"
+    pub unit_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Colors come from the theme's `label_background` and `label_border`
+pub struct LabelBoxStyle {
+    /// `false` drops the box, leaving only the text
+    pub background: bool,
+    pub fill_opacity: f64,
+    pub border_width: f64,
+    pub corner_radius: f64,
+}
+
+impl Default for LabelBoxStyle {
+    fn default() -> Self {
+        Self {
+            background: true,
+            fill_opacity: 1.0,
+            border_width: 1.0,
+            corner_radius: 4.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfitColors {
+    pub profitable: ColorString,
+    pub neutral: ColorString,
+    pub unprofitable: ColorString,
+}
+
+impl ProfitColors {
+    pub fn new(profitable: &'static str, neutral: &'static str, unprofitable: &'static str) -> Self {
+        Self {
+            profitable: ColorString::from(profitable),
+            neutral: ColorString::from(neutral),
+            unprofitable: ColorString::from(unprofitable),
+        }
+    }
+
+    /// Profits within the inclusive `neutral_band` count as break-even
+    pub fn color(&self, profit: i32, neutral_band: Option<(i32, i32)>) -> ColorString {
+        match neutral_band {
+            Some((low, high)) if (low..=high).contains(&profit) => self.neutral.clone(),
+            _ if profit >= 0 => self.profitable.clone(),
+            _ => self.unprofitable.clone(),
+        }
+    }
+}
+
+/// Builds the edge whose values are shown on the shared label of parallel edges
+pub type LabelMergeFn = fn(&[&TechEdge]) -> TechEdge;
+
+/// Default label merge: totals volume and profit, averages cost, keeps the shortest distance
+/// and the most frequent activity and supply level
+pub fn sum_parallel_edges(parallel: &[&TechEdge]) -> TechEdge {
+    fn most_frequent<T: Clone + Eq + std::hash::Hash>(values: impl Iterator<Item = T>) -> Option<T> {
+        let mut counts: Vec<(T, usize)> = Vec::new();
+        for value in values {
+            match counts.iter_mut().find(|(seen, _)| *seen == value) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+        // The first of equally frequent values wins
+        counts.into_iter().rev().max_by_key(|(_, count)| *count).map(|(value, _)| value)
+    }
+
+    let first = parallel[0];
+    let count = parallel.len() as u64;
+    TechEdge {
+        cost: (parallel.iter().map(|edge| edge.cost as u64).sum::<u64>() / count) as u32,
+        volume: parallel.iter().fold(0u32, |total, edge| total.saturating_add(edge.volume)),
+        activity: most_frequent(parallel.iter().map(|edge| edge.activity.clone())).unwrap_or(first.activity.clone()),
+        supply: most_frequent(parallel.iter().map(|edge| edge.supply.clone())).unwrap_or(first.supply.clone()),
+        distance: parallel.iter().filter_map(|edge| edge.distance).min(),
+        profit: parallel.iter().filter_map(|edge| edge.profit).reduce(i32::saturating_add),
+        ..first.clone()
+    }
+}
+
+/// Resolves the text color of one edge label field
+pub type LabelColorFn = fn(&TechEdge) -> ColorString;
+
+/// Per-field color overrides for edge labels; `None` keeps the default coloring
+/// (plain text for distance, volume and cost, level colors for activity and supply,
+/// green/red for the profit sign)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EdgeLabelColors {
+    pub distance: Option<LabelColorFn>,
+    pub volume: Option<LabelColorFn>,
+    pub cost: Option<LabelColorFn>,
+    pub activity: Option<LabelColorFn>,
+    pub supply: Option<LabelColorFn>,
+    pub profit: Option<LabelColorFn>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CornerStyle {
+    Sharp,
+    /// Corner radius
+    Rounded(f64),
+    /// Length cut off both sides at each corner
+    Cut(f64),
+}
+
+impl Default for CornerStyle {
+    fn default() -> Self {
+        CornerStyle::Rounded(5.0)
+    }
+}
+
+/// `sharp`, `rounded:<radius>` or `cut:<length>`
+impl FromStr for CornerStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once(':') {
+            None if value == "sharp" => Ok(CornerStyle::Sharp),
+            Some(("rounded", radius)) => Ok(CornerStyle::Rounded(parse_number(radius)?)),
+            Some(("cut", length)) => Ok(CornerStyle::Cut(parse_number(length)?)),
+            _ => Err(format!("invalid corner style '{}', expected sharp, rounded:<radius> or cut:<length>", value)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TitleEmphasis {
+    /// First line 30% larger
+    #[default]
+    Standard,
+    /// First line as large as the others
+    Off,
+    /// First line scaled by the given factor
+    Scaled(f64),
+}
+
+impl TitleEmphasis {
+    pub fn multiplier(self) -> Option<f64> {
+        match self {
+            TitleEmphasis::Standard => Some(1.3),
+            TitleEmphasis::Off => None,
+            TitleEmphasis::Scaled(multiplier) => Some(multiplier),
+        }
+    }
+}
+
+/// `standard`, `off` or the factor of `Scaled`
+impl FromStr for TitleEmphasis {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "standard" => Ok(TitleEmphasis::Standard),
+            "off" => Ok(TitleEmphasis::Off),
+            _ => parse_number(value).map(TitleEmphasis::Scaled),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, clap::ValueEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum LineCap {
+    #[default]
+    Round,
+    Butt,
+    Square,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, clap::ValueEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum LineJoin {
+    #[default]
+    Round,
+    Miter,
+    Bevel,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DuplicateNames {
+    /// Render the plain name, even if several nodes share it
+    #[default]
+    Keep,
+    /// Append the last segment of the waypoint symbol, e.g. `LIQUID_HYDROGEN (C40)`
+    Waypoint,
+    /// Append the position among the nodes sharing the name, e.g. `LIQUID_HYDROGEN (2)`
+    Numbered,
+}
+
+/// Which end of a node's `DepthRange` drives depth coloring
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DepthMetric {
+    Min,
+    Max,
+}
+
+/// Per-node adjustments `output_svg` derives from the whole graph before drawing each node
+#[derive(Clone, Debug, Default)]
+pub struct NodeStyle {
+    /// Replaces the fill color otherwise picked by waypoint type
+    pub fill: Option<ColorString>,
+    /// Replaces `name` as the first line of the stats layout
+    pub title: Option<String>,
+    /// Faded with a dashed border, for nodes that are only shown in the parking row
+    pub ghost: bool,
+    /// Faded because it lies outside `RenderConfig::focus` or `level_filter`
+    pub dimmed: bool,
+}
+
+/// Space `output_svg` leaves around the layout bounds
+pub const SVG_MARGIN: f64 = 50.0;
+
+/// Offset `output_svg` translates layout coordinates by when drawing labels at `placement`
+pub(crate) fn svg_offset(nodes: &[PlacedNode], edges: &[PlacedEdge], placement: LabelPlacement) -> Point {
+    let (min_x, min_y, _, _) = drawing_bounds(nodes, edges, placement).unwrap_or((0.0, 0.0, 0.0, 0.0));
+    (SVG_MARGIN - min_x, SVG_MARGIN - min_y)
+}
+
+/// Rectangles of all positioned nodes in the coordinate space of the SVG emitted by
+/// `output_svg` with `config`, e.g. to position HTML overlays on top of the rendered image
+pub(crate) fn svg_node_rects(nodes: &[TechNode], edges: &[TechEdge], layout: &LayoutResult, config: &RenderConfig) -> Vec<(String, Rect)> {
+    let nodes = place_nodes(nodes, layout);
+    let (offset_x, offset_y) = svg_offset(&nodes, &place_edges(edges, layout), config.label_placement);
+
+    nodes
+        .iter()
+        .filter_map(|node| {
+            let (x, y) = (node.x?, node.y?);
+            let rect = Rect {
+                x: x - node.width / 2.0 + offset_x,
+                y: y - node.height / 2.0 + offset_y,
+                width: node.width,
+                height: node.height,
+            };
+            Some((node.id.clone(), rect))
+        })
+        .collect()
+}
+
+/// Bounding boxes of all routed edges (including control points) in the SVG coordinates of
+/// `output_svg` with `config`, keyed by (source, target)
+pub(crate) fn svg_edge_rects(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    layout: &LayoutResult,
+    config: &RenderConfig,
+) -> Vec<((String, String), Rect)> {
+    let edges = place_edges(edges, layout);
+    let (offset_x, offset_y) = svg_offset(&place_nodes(nodes, layout), &edges, config.label_placement);
+
+    edges
+        .iter()
+        .filter_map(|edge| {
+            let points = edge.points.as_ref().filter(|points| !points.is_empty())?;
+            let (min_x, min_y, max_x, max_y) = points.iter().fold(
+                (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                |(min_x, min_y, max_x, max_y), &(x, y)| (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+            );
+            let rect = Rect {
+                x: min_x + offset_x,
+                y: min_y + offset_y,
+                width: max_x - min_x,
+                height: max_y - min_y,
+            };
+            Some(((edge.source.clone(), edge.target.clone()), rect))
+        })
+        .collect()
+}
+
+/// `output_svg`, recording how long rendering took in `metrics`
+pub fn output_svg_with_metrics(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    layout: &LayoutResult,
+    config: &RenderConfig,
+    metrics: &mut LayoutMetrics,
+) -> String {
+    let start = Instant::now();
+    let svg = output_svg(nodes, edges, layout, config);
+    metrics.render = Some(start.elapsed());
+    svg
+}
+
+/// Number of rank-to-rank segments edges spanning several ranks need beyond their first one
+pub(crate) fn count_dummy_vertices(nodes: &[PlacedNode], edges: &[TechEdge]) -> usize {
+    let rank_of: HashMap<&str, usize> = nodes.iter().filter_map(|n| Some((n.id.as_str(), n.rank?))).collect();
+    edges
+        .iter()
+        .filter_map(|edge| Some(rank_of.get(edge.source.as_str())?.abs_diff(*rank_of.get(edge.target.as_str())?)))
+        .map(|span| span.saturating_sub(1))
+        .sum()
+}
+
+/// Edge crossings between neighbouring ranks of a positioned layout. Edges spanning several
+/// ranks are split into one segment per rank gap, passing the gaps in a straight line.
+pub(crate) fn count_crossings(nodes: &[PlacedNode], edges: &[TechEdge]) -> usize {
+    let Some((axis, ranks)) = rank_coordinates(nodes) else {
+        return 0;
+    };
+    let along: HashMap<usize, f64> = ranks.into_iter().collect();
+    let placed: HashMap<&str, (usize, f64)> = nodes
+        .iter()
+        .filter_map(|node| {
+            let cross = match axis {
+                RankAxis::X => node.y?,
+                RankAxis::Y => node.x?,
+            };
+            Some((node.id.as_str(), (node.rank?, cross)))
+        })
+        .collect();
+
+    // Per rank gap (named by its lower rank): (cross at the lower rank, cross at the upper rank)
+    let mut segments: BTreeMap<usize, Vec<(f64, f64)>> = BTreeMap::new();
+    for edge in edges {
+        let (Some(&(source_rank, source_cross)), Some(&(target_rank, target_cross))) =
+            (placed.get(edge.source.as_str()), placed.get(edge.target.as_str()))
+        else {
+            continue;
+        };
+        let ((low, low_cross), (high, high_cross)) = if source_rank <= target_rank {
+            ((source_rank, source_cross), (target_rank, target_cross))
+        } else {
+            ((target_rank, target_cross), (source_rank, source_cross))
+        };
+        let (Some(&low_along), Some(&high_along)) = (along.get(&low), along.get(&high)) else {
+            continue;
+        };
+
+        let cross_at = |rank: usize| match along.get(&rank) {
+            Some(&at) if (high_along - low_along).abs() > f64::EPSILON => {
+                low_cross + (high_cross - low_cross) * (at - low_along) / (high_along - low_along)
+            }
+            _ => low_cross,
+        };
+        for rank in low..high {
+            segments.entry(rank).or_default().push((cross_at(rank), cross_at(rank + 1)));
+        }
+    }
+
+    // Two segments cross if their order flips between both ends of the gap
+    segments
+        .values()
+        .map(|gap| {
+            gap.iter()
+                .enumerate()
+                .flat_map(|(i, a)| gap[i + 1..].iter().map(move |b| (a, b)))
+                .filter(|(a, b)| (a.0 - b.0) * (a.1 - b.1) < 0.0)
+                .count()
+        })
+        .sum()
+}
+
+/// Draw `nodes` and `edges` at their positions and routes in `layout`
+pub fn output_svg(nodes: &[TechNode], edges: &[TechEdge], layout: &LayoutResult, config: &RenderConfig) -> String {
+    output_svg_with_scale(nodes, edges, layout, config).0
+}
+
+/// `output_svg`, also returning the factor the drawing was scaled by to respect
+/// `RenderConfig::max_width`/`max_height` (1.0 when it already fits)
+pub(crate) fn output_svg_with_scale(nodes: &[TechNode], edges: &[TechEdge], layout: &LayoutResult, config: &RenderConfig) -> (String, f64) {
+    let (svg, transform) = output_svg_with_transform(nodes, edges, layout, config);
+    (svg, transform.sx)
+}
+
+/// `output_svg`, also returning the transform from layout to SVG pixel coordinates
+pub fn output_svg_with_transform(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    layout: &LayoutResult,
+    config: &RenderConfig,
+) -> (String, Transform) {
+    // Graph data comes from the input, coordinates from the layout
+    let (graph_nodes, graph_edges) = (nodes, edges);
+    let mut nodes = place_nodes(graph_nodes, layout);
+    let mut edges = place_edges(graph_edges, layout);
+
+    // Give nodes without coordinates a spot in the parking row, drawn as ghosts
+    let ghosts: HashSet<&str> = match config.park_unpositioned {
+        true => nodes.iter().filter(|node| node.x.is_none() || node.y.is_none()).map(|node| node.node.id.as_str()).collect(),
+        false => HashSet::new(),
+    };
+    if config.park_unpositioned {
+        park_unpositioned_nodes(&mut nodes);
+    }
+
+    if let Some(tolerance) = config.simplify_tolerance {
+        simplify_edge_paths(&mut edges, tolerance);
+    }
+    let (nodes, edges) = (nodes.as_slice(), edges.as_slice());
+
+    // Calculate SVG dimensions based on node positions, edge paths and labels
+    let margin = SVG_MARGIN;
+    let (min_x, min_y, max_x, max_y) = drawing_bounds(nodes, edges, config.label_placement).unwrap_or((0.0, 0.0, 0.0, 0.0));
+
+    let mut svg_width = max_x - min_x + 2.0 * margin;
+    let mut svg_height = max_y - min_y + 2.0 * margin;
+    if config.include_legend {
+        svg_width = svg_width.max(LEGEND_WIDTH + 2.0 * margin);
+        svg_height += LEGEND_HEIGHT;
+    }
+
+    // Uniform downscale of everything, text included, so oversized canvases fit the limits
+    let scale = [(config.max_width, svg_width), (config.max_height, svg_height)]
+        .into_iter()
+        .filter_map(|(limit, size)| limit.filter(|&limit| limit > 0.0 && size > limit).map(|limit| limit / size))
+        .fold(1.0, f64::min);
+    // Rounded down to the precision it is written with, so the drawing stays within the limits
+    let scale = (scale * 1e4).floor() / 1e4;
+    let scale_transform = if scale < 1.0 { format!("scale({:.4}) ", scale) } else { String::new() };
+    let (view_width, view_height) = (svg_width * scale, svg_height * scale);
+
+    let zoom_hints = match config.zoom_metadata {
+        ZoomMetadata::Off => None,
+        ZoomMetadata::Auto => Some(compute_zoom_hints(nodes, view_width, view_height, scale)),
+        ZoomMetadata::Fixed(hints) => Some(hints),
+    };
+    let zoom_attrs = zoom_hints
+        .map(|hints| {
+            format!(
+                r#" data-min-zoom="{:.3}" data-max-zoom="{:.3}" data-natural-scale="{:.3}""#,
+                hints.min_zoom, hints.max_zoom, hints.natural_scale
+            )
+        })
+        .unwrap_or_default();
+
+    let accessibility_attrs = if config.accessibility {
+        format!(r#" role="img" aria-labelledby="{} {}""#, config.id("diagram-title"), config.id("diagram-desc"))
+    } else {
+        String::new()
+    };
+
+    let size_attrs = match config.svg_size {
+        SvgSize::Responsive => r#"width="100%""#.to_string(),
+        SvgSize::Natural => format!(r#"width="{:.2}" height="{:.2}""#, view_width, view_height),
+        SvgSize::Fixed { width, height } => format!(r#"width="{:.2}" height="{:.2}""#, width, height),
+    };
+
+    // SVG header
+    let mut svg = format!(
+        r#"<svg {} viewBox="0 0 {:.2} {:.2}" preserveAspectRatio="xMidYMid meet"{}{} xmlns="http://www.w3.org/2000/svg">"#,
+        size_attrs, view_width, view_height, zoom_attrs, accessibility_attrs
+    );
+
+    if config.accessibility {
+        let title = config.title.as_deref().unwrap_or("Supply chain diagram");
+        svg.push_str(&format!(
+            r#"<title id="{}">{}</title><desc id="{}">{} goods connected by {} trade routes</desc>"#,
+            config.id("diagram-title"),
+            escape_xml(title),
+            config.id("diagram-desc"),
+            nodes.len(),
+            edges.len()
+        ));
+    }
+
+    svg.push_str(&generate_arrow_markers_svg(config));
+
+    // Transform to adjust for margins and any negative coordinates
+    svg.push_str(&format!(
+        r#"<g transform="{}translate({:.2},{:.2})">"#,
+        scale_transform,
+        margin - min_x,
+        margin - min_y
+    ));
+
+    if let Some(ref background) = config.theme.background {
+        svg.push_str(&format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" />"#,
+            min_x - margin, min_y - margin, svg_width, svg_height, background
+        ));
+    }
+
+    if let Some(ref frame) = config.frame {
+        let stroke = frame.stroke.as_ref().unwrap_or(&config.theme.edge);
+        svg.push_str(&format!(
+            r#"<rect class="frame" x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="none" stroke="{}" stroke-width="{}" />"#,
+            min_x - margin + frame.inset,
+            min_y - margin + frame.inset,
+            (svg_width - 2.0 * frame.inset).max(0.0),
+            (svg_height - 2.0 * frame.inset).max(0.0),
+            stroke,
+            frame.stroke_width
+        ));
+    }
+
+    // Draw rank bands behind everything else
+    if config.rank_bands {
+        svg.push_str(&generate_rank_bands_svg(nodes, (min_x, min_y, max_x, max_y), &config.theme));
+    }
+
+    if config.rank_guides {
+        svg.push_str(&generate_rank_guides_svg(nodes, (min_x, min_y, max_x, max_y), &config.theme));
+    }
+
+    let filtered_out: HashSet<&str> = config
+        .level_filter
+        .as_ref()
+        .map(|filter| nodes.iter().filter(|node| !filter.matches(node)).map(|node| node.id.as_str()).collect())
+        .unwrap_or_default();
+    let in_focus = |id: &str| config.focus.as_ref().is_none_or(|focus| focus.contains(id)) && !filtered_out.contains(id);
+    let edge_dimmed = |edge: &TechEdge| !in_focus(&edge.source) || !in_focus(&edge.target);
+
+    // Draw edges
+    let edge_stroke = format!(
+        r#"stroke="{}" stroke-width="2" stroke-linecap="{}" stroke-linejoin="{}""#,
+        config.theme.edge, config.line_cap, config.line_join
+    );
+    if config.group_ranks {
+        svg.push_str(&format!(r#"<g id="{}" class="edges">"#, config.id("edges")));
+    }
+    for edge in edges {
+        if let Some(points) = logical_points(edge, nodes)
+            && points.len() >= 2
+        {
+            // End on the target's border so the arrowhead isn't hidden under the box
+            let mut points = points.into_owned();
+            if let Some((tip, _)) = nodes
+                .iter()
+                .find(|n| n.id == edge.target)
+                .and_then(|target| edge_arrow_tip(target, &points))
+            {
+                *points.last_mut().expect("at least two points") = tip;
+            }
+            let points = &points;
+            // Edges closing a cycle are dashed, as they run against the flow of the ranks
+            let dashes = if edge.reversed { r#" stroke-dasharray="8,5""# } else { "" };
+            let edge_stroke = format!(r#"{edge_stroke}{dashes} marker-end="url(#{})""#, arrow_marker_id(&edge.activity, config));
+            if edge_dimmed(edge) {
+                svg.push_str(DIMMED_GROUP);
+            }
+            if config.relative_paths && points.len() > 2 {
+                // Same geometry as below, expressed as deltas for a smaller file
+                svg.push_str(&format!(
+                    r#"<path d="{}" fill="none" {edge_stroke} />"#,
+                    relative_path_data(points, edge.path_style)
+                ));
+            } else if edge.path_style == PathStyle::Straight && points.len() > 2 {
+                // Polyline through the corner points
+                let corners = points
+                    .iter()
+                    .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                svg.push_str(&format!(
+                    r#"<polyline points="{}" fill="none" {edge_stroke} />"#,
+                    corners
+                ));
+            } else if points.len() == 2 {
+                // Simple straight line
+                svg.push_str(&format!(
+                    r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" {edge_stroke} />"#,
+                    points[0].0, points[0].1, points[1].0, points[1].1
+                ));
+            } else if points.len() > 3 {
+                // Smooth curve through all waypoints
+                let segments: String = catmull_rom_segments(points)
+                    .iter()
+                    .map(|(first, second, end)| {
+                        format!(" C{:.2},{:.2} {:.2},{:.2} {:.2},{:.2}", first.0, first.1, second.0, second.1, end.0, end.1)
+                    })
+                    .collect();
+                svg.push_str(&format!(
+                    r#"<path d="M{:.2},{:.2}{}" fill="none" {edge_stroke} />"#,
+                    points[0].0, points[0].1, segments
+                ));
+            } else {
+                // Path with control points
+                svg.push_str(&format!(
+                    r#"<path d="M{:.2},{:.2} Q{:.2},{:.2} {:.2},{:.2}" fill="none" {edge_stroke} />"#,
+                    points[0].0, points[0].1,
+                    points[1].0, points[1].1,
+                    points[2].0, points[2].1
+                ));
+            }
+
+            if edge_dimmed(edge) {
+                svg.push_str("</g>");
+            }
+        }
+    }
+    if config.group_ranks {
+        svg.push_str("</g>");
+    }
+
+    // Draw nodes using the new node generator
+    let depth_fills = config
+        .depth_coloring
+        .map(|metric| depth_fill_colors(graph_nodes, graph_edges, metric, &config.theme))
+        .unwrap_or_default();
+    let titles = disambiguated_titles(graph_nodes, config.duplicate_names);
+    let node_svg = |node: &PlacedNode| {
+        let style = NodeStyle {
+            fill: depth_fills.get(&node.id).cloned(),
+            title: titles.get(&node.id).cloned(),
+            ghost: ghosts.contains(node.id.as_str()),
+            dimmed: !in_focus(&node.id),
+        };
+        generate_node_svg(node, config, &style)
+    };
+    if config.group_ranks {
+        // One group per rank so scripts can address a whole rank; unranked nodes follow ungrouped
+        let mut ranks: BTreeMap<usize, Vec<&PlacedNode>> = BTreeMap::new();
+        for node in nodes {
+            if let Some(rank) = node.rank {
+                ranks.entry(rank).or_default().push(node);
+            }
+        }
+        for (rank, members) in ranks {
+            svg.push_str(&format!(r#"<g id="{}" class="rank">"#, config.id(&format!("rank-{}", rank))));
+            for node in members {
+                svg.push_str(&node_svg(node));
+            }
+            svg.push_str("</g>");
+        }
+        for node in nodes.iter().filter(|node| node.rank.is_none()) {
+            svg.push_str(&node_svg(node));
+        }
+    } else {
+        for node in nodes {
+            svg.push_str(&node_svg(node));
+        }
+    }
+
+    // Add edge labels after nodes to ensure they're in the foreground
+    // But only for target nodes as per your update
+    let mut merged_pairs: HashSet<(&str, &str)> = HashSet::new();
+    for edge in edges {
+        if let Some(points) = logical_points(edge, nodes)
+            && points.len() >= 2
+        {
+            let points = &points;
+            // Parallel edges share one label showing their merged values
+            let merged = match config.label_merge {
+                Some(merge) => {
+                    let pair = (edge.source.as_str(), edge.target.as_str());
+                    if !merged_pairs.insert(pair) {
+                        continue;
+                    }
+                    let parallel: Vec<&TechEdge> = graph_edges
+                        .iter()
+                        .filter(|other| (other.source.as_str(), other.target.as_str()) == pair)
+                        .collect();
+                    (parallel.len() > 1).then(|| merge(&parallel))
+                }
+                None => None,
+            };
+            let label_edge = merged.as_ref().unwrap_or(edge.edge);
+
+            if let Some((anchor, label)) = edge_label_position(edge, points, nodes, config.label_placement) {
+                let description = edge_description(label_edge, graph_nodes);
+                let label_svg = generate_edge_label_svg(anchor, label, label_edge, &description, config);
+                if edge_dimmed(edge) {
+                    svg.push_str(&format!("{DIMMED_GROUP}{label_svg}</g>"));
+                } else {
+                    svg.push_str(&label_svg);
+                }
+            }
+        }
+    }
+    if config.include_legend {
+        svg.push_str(&generate_legend_svg(min_x, max_y + margin, config.font_family(), &config.theme));
+    }
+    if let Some(ref scale_bar) = config.scale_bar {
+        svg.push_str(&generate_scale_bar_svg(scale_bar, (min_x, min_y, max_x, max_y), margin, config.font_family(), &config.theme.scale_bar));
+    }
+
+    // Close SVG
+    svg.push_str("</g></svg>");
+
+    let transform = Transform {
+        tx: (margin - min_x) * scale,
+        ty: (margin - min_y) * scale,
+        sx: scale,
+        sy: scale,
+    };
+    (svg, transform)
+}
+
+/// Opening tag wrapping elements outside `RenderConfig::focus` or `level_filter`
+pub(crate) const DIMMED_GROUP: &str = r#"<g class="dimmed" opacity="0.25">"#;
+
+/// Scale bar with end ticks and its length, in the bottom margin below the layout's left border
+pub(crate) fn generate_scale_bar_svg(scale_bar: &ScaleBar, bounds: Bounds, margin: f64, font_family: &str, color: &ColorString) -> String {
+    let (min_x, _, _, max_y) = bounds;
+    let length = scale_bar.units * scale_bar.pixels_per_unit;
+    let bar_y = max_y + margin * 0.6;
+    let tick = 4.0;
+
+    format!(
+        r#"<g class="scale-bar" stroke="{color}" stroke-width="1.5">
+            <line x1="{min_x:.2}" y1="{bar_y:.2}" x2="{:.2}" y2="{bar_y:.2}" />
+            <line x1="{min_x:.2}" y1="{:.2}" x2="{min_x:.2}" y2="{:.2}" />
+            <line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" />
+            <text x="{min_x:.2}" y="{:.2}" font-family="{font_family}" font-size="10" fill="{color}" stroke="none">{} {}</text>
+        </g>"#,
+        min_x + length,
+        bar_y - tick, bar_y + tick,
+        min_x + length, bar_y - tick, min_x + length, bar_y + tick,
+        bar_y - tick - 3.0,
+        scale_bar.units, escape_xml(&scale_bar.unit_name),
+        font_family = escape_xml(font_family),
+    )
+}
+
+/// Derive a zoom range from the canvas size and how densely it is packed with nodes.
+/// Zooming out stops once the whole canvas fits a reference viewport, zooming in once an
+/// average node fills half of it. Sparse canvases may zoom out further, dense ones less.
+/// `view_width` and `view_height` are the emitted canvas size, the layout shrunk by `scale`.
+pub(crate) fn compute_zoom_hints(nodes: &[PlacedNode], view_width: f64, view_height: f64, scale: f64) -> ZoomHints {
+    let reference_viewport = 1024.0;
+
+    let sizes: Vec<(f64, f64)> = nodes
+        .iter()
+        .filter(|node| node.x.is_some() && node.y.is_some())
+        .map(|node| (node.width * scale, node.height * scale))
+        .collect();
+
+    if sizes.is_empty() || view_width <= 0.0 || view_height <= 0.0 {
+        return ZoomHints { min_zoom: 1.0, max_zoom: 1.0, natural_scale: scale };
+    }
+
+    let node_area: f64 = sizes.iter().map(|(w, h)| w * h).sum();
+    let density = (node_area / (view_width * view_height)).clamp(0.0, 1.0);
+    let average_extent = sizes.iter().map(|(w, h)| w.max(*h)).sum::<f64>() / sizes.len() as f64;
+
+    let fit_zoom = reference_viewport / view_width.max(view_height);
+    let min_zoom = (fit_zoom * (0.5 + density)).min(1.0);
+    let max_zoom = (reference_viewport / 2.0 / average_extent).max(1.0);
+
+    ZoomHints { min_zoom, max_zoom, natural_scale: scale }
+}
+
+/// Waypoint types with a fill of their own in the built-in themes, in supply chain order
+pub(crate) const WAYPOINT_TYPES: [&str; 5] = ["RAW_MATERIAL", "REFINED", "INDUSTRIAL", "ADVANCED", "CONSUMER"];
+
+/// Size of the legend drawn by `generate_legend_svg`
+pub(crate) const LEGEND_WIDTH: f64 = 480.0;
+pub(crate) const LEGEND_HEIGHT: f64 = 130.0;
+
+/// Key to the colors of the drawing with its top left corner at (x, y): supply levels as text
+/// colors, activity levels as border colors and the node fill of each waypoint type. Colors
+/// come from `theme` like those of the nodes.
+pub fn generate_legend_svg(x: f64, y: f64, font_family: &str, theme: &Theme) -> String {
+    let column_width = LEGEND_WIDTH / 3.0;
+    let (swatch, row_height) = (12.0, 18.0);
+    let font_family = escape_xml(font_family);
+
+    let supply: Vec<(String, String)> = SupplyLevel::iter()
+        .map(|level| {
+            let color = theme.supply_color(&level);
+            (level.to_string(), format!(r#"fill="{color}""#))
+        })
+        .collect();
+    let activity: Vec<(String, String)> = ActivityLevel::iter()
+        .map(|level| {
+            let color = theme.activity_color(&level);
+            (level.to_string(), format!(r#"fill="none" stroke="{color}" stroke-width="3""#))
+        })
+        .collect();
+    let node_types: Vec<(String, String)> = WAYPOINT_TYPES
+        .iter()
+        .map(|waypoint_type| {
+            let color = theme.node_fill(waypoint_type);
+            (waypoint_type.to_string(), format!(r#"fill="{color}" stroke="{}""#, theme.text))
+        })
+        .collect();
+
+    let mut svg = String::from(r#"<g class="legend">"#);
+    for (column, (heading, entries)) in [("Supply", supply), ("Activity", activity), ("Node type", node_types)]
+        .into_iter()
+        .enumerate()
+    {
+        let column_x = x + column as f64 * column_width;
+        svg.push_str(&format!(
+            r#"<text x="{column_x:.2}" y="{:.2}" font-family="{font_family}" font-size="12" font-weight="bold" fill="{}">{heading}</text>"#,
+            y + 12.0,
+            theme.title_text
+        ));
+        for (row, (name, paint)) in entries.iter().enumerate() {
+            let row_y = y + 20.0 + row as f64 * row_height;
+            svg.push_str(&format!(
+                r#"<rect x="{column_x:.2}" y="{row_y:.2}" width="{swatch}" height="{swatch}" {paint} />"#
+            ));
+            svg.push_str(&format!(
+                r#"<text x="{:.2}" y="{:.2}" font-family="{font_family}" font-size="10" fill="{}">{}</text>"#,
+                column_x + swatch + 6.0,
+                row_y + swatch - 2.0,
+                theme.text,
+                escape_xml(name)
+            ));
+        }
+    }
+    svg.push_str("</g>");
+
+    svg
+}
+
+/// Thin dotted lines across the canvas at the coordinate of each rank
+pub(crate) fn generate_rank_guides_svg(nodes: &[PlacedNode], bounds: Bounds, theme: &Theme) -> String {
+    let Some((axis, ranks)) = rank_coordinates(nodes) else {
+        return String::new();
+    };
+    let (min_x, min_y, max_x, max_y) = bounds;
+
+    let guide_color = &theme.ranks;
+    let mut svg = format!(
+        r#"<g class="rank-guides" stroke="{}" stroke-width="1" stroke-dasharray="2,4">"#,
+        guide_color
+    );
+    for (_, coordinate) in ranks {
+        let (x1, y1, x2, y2) = match axis {
+            RankAxis::X => (coordinate, min_y, coordinate, max_y),
+            RankAxis::Y => (min_x, coordinate, max_x, coordinate),
+        };
+        svg.push_str(&format!(r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" />"#, x1, y1, x2, y2));
+    }
+    svg.push_str("</g>");
+
+    svg
+}
+
+/// Alternating translucent bands behind each rank, split halfway between neighbouring ranks
+pub(crate) fn generate_rank_bands_svg(nodes: &[PlacedNode], bounds: Bounds, theme: &Theme) -> String {
+    let Some((axis, mut ranks)) = rank_coordinates(nodes) else {
+        return String::new();
+    };
+    ranks.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let (min_x, min_y, max_x, max_y) = bounds;
+    let (axis_min, axis_max) = match axis {
+        RankAxis::X => (min_x, max_x),
+        RankAxis::Y => (min_y, max_y),
+    };
+
+    // Band styling
+    let band_color = &theme.ranks;
+    let band_opacities = [0.08, 0.16];
+
+    let mut svg = String::from(r#"<g class="rank-bands">"#);
+    for (i, &(_, coordinate)) in ranks.iter().enumerate() {
+        let start = if i == 0 { axis_min } else { (ranks[i - 1].1 + coordinate) / 2.0 };
+        let end = if i + 1 == ranks.len() { axis_max } else { (coordinate + ranks[i + 1].1) / 2.0 };
+
+        let (x, y, width, height) = match axis {
+            RankAxis::X => (start, min_y, end - start, max_y - min_y),
+            RankAxis::Y => (min_x, start, max_x - min_x, end - start),
+        };
+
+        svg.push_str(&format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" fill-opacity="{}" />"#,
+            x, y, width, height, band_color, band_opacities[i % 2]
+        ));
+    }
+    svg.push_str("</g>");
+
+    svg
+}
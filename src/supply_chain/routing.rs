@@ -0,0 +1,806 @@
+//! Edge routing: node sides, channels, detours around nodes and obstacles, and path data
+
+use crate::{Orientation, Point};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    /// Unit vector pointing out of the node through this side
+    pub fn normal(self) -> Point {
+        match self {
+            Side::Left => (-1.0, 0.0),
+            Side::Right => (1.0, 0.0),
+            Side::Top => (0.0, -1.0),
+            Side::Bottom => (0.0, 1.0),
+        }
+    }
+
+    /// Middle of this side of a positioned node
+    pub fn port(self, node: &PlacedNode) -> Point {
+        self.port_at(node, 0.5)
+    }
+
+    /// Point `fraction` of the way along this side, from its left or top end
+    pub fn port_at(self, node: &PlacedNode, fraction: f64) -> Point {
+        let (x, y) = (node.x.unwrap_or_default(), node.y.unwrap_or_default());
+        let (nx, ny) = self.normal();
+        let (px, py) = (x + nx * node.width / 2.0, y + ny * node.height / 2.0);
+        match self {
+            Side::Left | Side::Right => (px, y + (fraction - 0.5) * node.height),
+            Side::Top | Side::Bottom => (x + (fraction - 0.5) * node.width, py),
+        }
+    }
+
+    /// Side facing the next rank of `orientation`
+    pub fn forward(orientation: Orientation) -> Side {
+        match orientation {
+            Orientation::TopDown => Side::Bottom,
+            Orientation::BottomUp => Side::Top,
+            Orientation::LeftRight => Side::Right,
+            Orientation::RightLeft => Side::Left,
+        }
+    }
+
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PreferredSides {
+    /// Side incoming edges attach to
+    pub entry: Option<Side>,
+    /// Side outgoing edges leave from
+    pub exit: Option<Side>,
+}
+
+/// `LayoutOptions::preferred_sides` of `--exit-side`: edges leave every node by `exit` and
+/// enter every node by the opposite side
+pub fn sides_leaving_by(exit: Side) -> fn(&TechNode) -> PreferredSides {
+    match exit {
+        Side::Left => |_| PreferredSides { entry: Some(Side::Right), exit: Some(Side::Left) },
+        Side::Right => |_| PreferredSides { entry: Some(Side::Left), exit: Some(Side::Right) },
+        Side::Top => |_| PreferredSides { entry: Some(Side::Bottom), exit: Some(Side::Top) },
+        Side::Bottom => |_| PreferredSides { entry: Some(Side::Top), exit: Some(Side::Bottom) },
+    }
+}
+
+/// Coordinates the end of an edge at node `id` is routed to. `positioned` holds the indices into
+/// `nodes` of the nodes the layout run placed; the others only have the coordinates they came
+/// with, if any, which `unpositioned` decides whether to use.
+pub(crate) fn routing_endpoint(
+    nodes: &[PlacedNode],
+    node_positions: &HashMap<String, usize>,
+    positioned: &HashSet<usize>,
+    unpositioned: UnpositionedEdges,
+    id: &str,
+) -> Result<Point, UnroutedReason> {
+    let &pos = node_positions
+        .get(id)
+        .ok_or_else(|| UnroutedReason::UnknownNode(id.to_string()))?;
+    let node = &nodes[pos];
+    let usable = positioned.contains(&pos) || unpositioned == UnpositionedEdges::UseLastKnown;
+    match (node.x, node.y) {
+        (Some(x), Some(y)) if usable => Ok((x, y)),
+        _ => Err(UnroutedReason::MissingPosition(id.to_string())),
+    }
+}
+
+/// Move the control points of parallel curved edges (same source and target) apart, so the
+/// arcs fan out symmetrically around the straight line instead of being drawn on top of each
+/// other. Neighbouring arcs are `curve_factor` times the edge length apart at their control
+/// points; labels follow, as they are placed along the last segment towards the control point.
+pub fn fan_out_parallel_edges(edges: &mut [PlacedEdge]) {
+    let mut parallels: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        if edge.path_style == PathStyle::Curved && edge.points.as_ref().is_some_and(|points| points.len() == 3) {
+            parallels.entry((edge.source.clone(), edge.target.clone())).or_default().push(i);
+        }
+    }
+
+    for indices in parallels.into_values().filter(|indices| indices.len() > 1) {
+        let count = indices.len();
+        for (j, i) in indices.into_iter().enumerate() {
+            let edge = &mut edges[i];
+            let curve_factor = edge.curve_factor.unwrap_or(0.25);
+            let Some(points) = edge.points.as_mut() else {
+                continue;
+            };
+            let ((sx, sy), (tx, ty)) = (points[0], points[2]);
+            let length = ((tx - sx).powi(2) + (ty - sy).powi(2)).sqrt();
+            if length < 1e-9 {
+                continue;
+            }
+            // Perpendicular to the straight line, alternating sides: -0.5, +0.5 for two arcs,
+            // -1, 0, +1 for three and so on
+            let (nx, ny) = (-(ty - sy) / length, (tx - sx) / length);
+            let offset = (j as f64 - (count - 1) as f64 / 2.0) * curve_factor * length;
+            points[1] = (points[1].0 + nx * offset, points[1].1 + ny * offset);
+        }
+    }
+}
+
+/// Right-angled route from the first to the last of `points`, passing the waypoints in between
+/// (one per rank the edge spans). Without waypoints the edge runs along the rank axis up to the
+/// end's rank and bends once into the end; with waypoints it follows their cross coordinates,
+/// switching halfway between two ranks where they differ.
+pub fn orthogonal_route(points: &[Point], axis: RankAxis) -> Vec<Point> {
+    let split = |(x, y): Point| match axis {
+        RankAxis::X => (x, y),
+        RankAxis::Y => (y, x),
+    };
+    let join = |along: f64, cross: f64| match axis {
+        RankAxis::X => (along, cross),
+        RankAxis::Y => (cross, along),
+    };
+    let (Some(&start), Some(&end)) = (points.first(), points.last()) else {
+        return points.to_vec();
+    };
+
+    let mut route = vec![start];
+    if points.len() <= 2 {
+        let ((start_along, start_cross), (end_along, end_cross)) = (split(start), split(end));
+        if (start_cross - end_cross).abs() > 1e-6 && (start_along - end_along).abs() > 1e-6 {
+            route.push(join(end_along, start_cross));
+        }
+    } else {
+        for pair in points.windows(2) {
+            let ((along, cross), (next_along, next_cross)) = (split(pair[0]), split(pair[1]));
+            if (cross - next_cross).abs() > 1e-6 {
+                let middle = (along + next_along) / 2.0;
+                route.push(join(middle, cross));
+                route.push(join(middle, next_cross));
+            }
+        }
+    }
+    route.push(end);
+    route
+}
+
+/// Orthogonal channel router: every edge between different ranks gets its own lane in each gap
+/// it crosses. It leaves its source along the rank axis, turns onto its lane, runs across to the
+/// cross coordinate of its waypoint on the next rank, passes that rank there and so on until it
+/// turns into the target. Edges without waypoints head straight for the target's cross
+/// coordinate. Lanes within a gap are ordered to minimize crossings between the routed segments.
+pub fn route_edges_in_channels(nodes: &[PlacedNode], edges: &mut [PlacedEdge], waypoints: &HashMap<usize, Vec<Point>>, axis: RankAxis) {
+    // Split points into (coordinate along the rank axis, coordinate across it) and back
+    let split = |(x, y): Point| match axis {
+        RankAxis::X => (x, y),
+        RankAxis::Y => (y, x),
+    };
+    let join = |along: f64, cross: f64| match axis {
+        RankAxis::X => (along, cross),
+        RankAxis::Y => (cross, along),
+    };
+
+    struct Placed {
+        rank: usize,
+        along: f64,
+        cross: f64,
+    }
+
+    let mut placed: HashMap<&str, Placed> = HashMap::new();
+    // Rank coordinate and the largest half extent of its nodes along the rank axis
+    let mut ranks: BTreeMap<usize, (f64, f64)> = BTreeMap::new();
+    for node in nodes {
+        if let (Some(rank), Some(x), Some(y)) = (node.rank, node.x, node.y) {
+            let (along, cross) = split((x, y));
+            let half_extent = match axis {
+                RankAxis::X => node.width / 2.0,
+                RankAxis::Y => node.height / 2.0,
+            };
+            let entry = ranks.entry(rank).or_insert((along, 0.0));
+            entry.1 = entry.1.max(half_extent);
+            placed.insert(&node.id, Placed { rank, along, cross });
+        }
+    }
+
+    // One piece of an edge per gap it crosses, by the rank the gap follows
+    struct ChannelEdge {
+        edge: usize,
+        // Cross coordinate where the edge enters / leaves the gap (seen from the channel rank)
+        enter: f64,
+        exit: f64,
+    }
+
+    let mut channels: BTreeMap<usize, Vec<ChannelEdge>> = BTreeMap::new();
+    // Cross coordinates of every routed edge on each rank it meets, from its lower rank on
+    let mut passes: HashMap<usize, Vec<(usize, f64)>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        let (Some(source), Some(target)) = (placed.get(edge.source.as_str()), placed.get(edge.target.as_str())) else {
+            continue;
+        };
+        if edge.points.is_none() || source.rank == target.rank {
+            continue;
+        }
+
+        let (low, high) = if source.rank < target.rank { (source, target) } else { (target, source) };
+        let mut chain: Vec<(f64, f64)> = waypoints.get(&i).map_or_else(Vec::new, |points| points.iter().map(|&p| split(p)).collect());
+        if source.rank > target.rank {
+            chain.reverse();
+        }
+
+        // Each rank in between is passed at the waypoint nearest to it, or straight at the
+        // target's cross coordinate when the edge has no waypoints
+        let mut pass = vec![(low.rank, low.cross)];
+        for (&rank, &(along, _)) in ranks.range(low.rank + 1..high.rank) {
+            let nearest = chain.iter().min_by(|a, b| (a.0 - along).abs().total_cmp(&(b.0 - along).abs()));
+            pass.push((rank, nearest.map_or(high.cross, |&(_, cross)| cross)));
+        }
+        pass.push((high.rank, high.cross));
+
+        for pair in pass.windows(2) {
+            channels
+                .entry(pair[0].0)
+                .or_default()
+                .push(ChannelEdge { edge: i, enter: pair[0].1, exit: pair[1].1 });
+        }
+        passes.insert(i, pass);
+    }
+
+    // Crossings caused by putting lane `a` closer to the channel rank than lane `b`
+    let crossings = |a: &ChannelEdge, b: &ChannelEdge| {
+        let within = |v: f64, e: &ChannelEdge| v > e.enter.min(e.exit) && v < e.enter.max(e.exit);
+        within(a.exit, b) as usize + within(b.enter, a) as usize
+    };
+
+    // Lane coordinate of every edge in each gap it crosses, by the rank the gap follows
+    let mut lanes_by_edge: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+    for (rank, mut lanes) in channels {
+        let Some(&(rank_along, rank_half)) = ranks.get(&rank) else {
+            continue;
+        };
+        let Some((_, &(next_along, next_half))) = ranks.range(rank + 1..).next() else {
+            continue;
+        };
+
+        // Start from the entry order and improve with adjacent swaps until nothing gets better
+        lanes.sort_by(|a, b| a.enter.total_cmp(&b.enter).then(a.exit.total_cmp(&b.exit)));
+        for _ in 0..lanes.len() {
+            let mut improved = false;
+            for i in 1..lanes.len() {
+                if crossings(&lanes[i], &lanes[i - 1]) < crossings(&lanes[i - 1], &lanes[i]) {
+                    lanes.swap(i - 1, i);
+                    improved = true;
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        // The channel spans the free space between the node faces of both ranks
+        let direction = (next_along - rank_along).signum();
+        let (mut start, mut end) = (rank_along + direction * rank_half, next_along - direction * next_half);
+        if (end - start) * direction <= 0.0 {
+            (start, end) = (rank_along, next_along);
+        }
+
+        for (lane, channel_edge) in lanes.iter().enumerate() {
+            let lane_along = start + (end - start) * (lane + 1) as f64 / (lanes.len() + 1) as f64;
+            lanes_by_edge.entry(channel_edge.edge).or_default().insert(rank, lane_along);
+        }
+    }
+
+    for (i, pass) in passes {
+        // Edges whose gaps all got a lane only; the others keep their curve
+        let Some(lanes) = lanes_by_edge.get(&i).filter(|lanes| lanes.len() == pass.len() - 1) else {
+            continue;
+        };
+        let edge = &mut edges[i];
+        let (first, last) = (&placed[edge.source.as_str()], &placed[edge.target.as_str()]);
+        let (low, high) = if first.rank < last.rank { (first, last) } else { (last, first) };
+
+        let mut points = vec![join(low.along, low.cross)];
+        for pair in pass.windows(2) {
+            let lane_along = lanes[&pair[0].0];
+            points.push(join(lane_along, pair[0].1));
+            points.push(join(lane_along, pair[1].1));
+        }
+        points.push(join(high.along, high.cross));
+        if first.rank > last.rank {
+            points.reverse();
+        }
+        edge.points = Some(points);
+        edge.path_style = PathStyle::Straight;
+    }
+}
+
+/// Detour passes per edge; each resolves one collision, which may cause the next one
+pub(crate) const MAX_DETOURS: usize = 8;
+
+/// Bend edges that cross the box of a node other than their own ends around it: the offending
+/// segment gets a waypoint at the box corner nearest to it, pushed out by a margin. Repeats
+/// while segments still cross boxes, at most `MAX_DETOURS` times per edge. Detoured edges are
+/// drawn as polylines through their points.
+pub fn detour_around_nodes(nodes: &[PlacedNode], edges: &mut [PlacedEdge]) {
+    let margin = 20.0;
+    let boxes: Vec<(&str, Rect)> = nodes
+        .iter()
+        .filter_map(|node| {
+            let (x, y) = (node.x?, node.y?);
+            let rect = Rect { x: x - node.width / 2.0, y: y - node.height / 2.0, width: node.width, height: node.height };
+            Some((node.id.as_str(), rect))
+        })
+        .collect();
+
+    for edge in edges.iter_mut() {
+        let Some(ref points) = edge.points else {
+            continue;
+        };
+        let obstacles: Vec<&Rect> = boxes
+            .iter()
+            .filter(|(id, _)| *id != edge.source && *id != edge.target)
+            .map(|(_, rect)| rect)
+            .collect();
+        let crossing = |points: &[Point]| {
+            points.windows(2).enumerate().find_map(|(i, segment)| {
+                obstacles
+                    .iter()
+                    .find(|rect| segment_crosses_rect(segment[0], segment[1], rect))
+                    .map(|rect| (i, *rect))
+            })
+        };
+
+        let drawn = drawn_path(points, edge.path_style);
+        if points.len() < 2 || crossing(&drawn).is_none() {
+            continue;
+        }
+        // A curve's control point doesn't lie on the path; keep only the points it passes
+        let mut route: Vec<Point> = match edge.path_style {
+            PathStyle::Curved if points.len() == 3 => vec![points[0], points[2]],
+            _ => points.clone(),
+        };
+
+        for _ in 0..MAX_DETOURS {
+            let Some((i, rect)) = crossing(&route) else {
+                break;
+            };
+            let (a, b) = (route[i], route[i + 1]);
+            let corners = [
+                (rect.x - margin, rect.y - margin),
+                (rect.x + rect.width + margin, rect.y - margin),
+                (rect.x - margin, rect.y + rect.height + margin),
+                (rect.x + rect.width + margin, rect.y + rect.height + margin),
+            ];
+            let Some(corner) = corners
+                .into_iter()
+                .filter(|&corner| corner != a && corner != b)
+                .min_by(|&p, &q| distance_to_segment(p, a, b).total_cmp(&distance_to_segment(q, a, b)))
+            else {
+                break;
+            };
+            route.insert(i + 1, corner);
+        }
+
+        edge.points = Some(route);
+        edge.path_style = PathStyle::Straight;
+    }
+}
+
+pub(crate) fn distance_to_segment((px, py): Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0.0 { (((px - a.0) * dx + (py - a.1) * dy) / length_squared).clamp(0.0, 1.0) } else { 0.0 };
+    let (cx, cy) = (a.0 + t * dx, a.1 + t * dy);
+    ((px - cx).powi(2) + (py - cy).powi(2)).sqrt()
+}
+
+/// Passes of `route_around_svg_obstacles`; an obstacle the edges have to pass outside the
+/// drawing pushes the drawing out again in every pass and never settles
+pub(crate) const OBSTACLE_PASSES: usize = 4;
+
+/// `route_around_obstacles` for `obstacles` in the SVG coordinates of `output_svg` with labels at
+/// `placement`. Detours change the drawing and with it the offset that maps the obstacles into
+/// layout coordinates. The edges are therefore routed again from their original routes around
+/// the obstacles at every offset seen so far, until the routes lead back to one of them.
+pub(crate) fn route_around_svg_obstacles(nodes: &[PlacedNode], edges: &mut [PlacedEdge], obstacles: &[Rect], placement: LabelPlacement) {
+    let original = edges.to_vec();
+    let mut mapped: Vec<Rect> = Vec::new();
+    let mut offsets: Vec<Point> = Vec::new();
+    let mut offset = svg_offset(nodes, edges, placement);
+    for _ in 0..OBSTACLE_PASSES {
+        let (offset_x, offset_y) = offset;
+        offsets.push(offset);
+        mapped.extend(obstacles.iter().map(|rect| Rect { x: rect.x - offset_x, y: rect.y - offset_y, ..*rect }));
+        edges.clone_from_slice(&original);
+        route_around_obstacles(edges, &mapped);
+
+        offset = svg_offset(nodes, edges, placement);
+        if offsets.iter().any(|seen| (seen.0 - offset.0).abs() < 1e-6 && (seen.1 - offset.1).abs() < 1e-6) {
+            break;
+        }
+    }
+}
+
+/// Reroute every edge whose drawn path runs through one of `obstacles` along the shortest
+/// polyline that keeps a small clearance around all of them. Edges starting or ending
+/// inside an obstacle can't avoid it and are left as they are.
+pub fn route_around_obstacles(edges: &mut [PlacedEdge], obstacles: &[Rect]) {
+    let clearance = 10.0;
+    let inflated: Vec<Rect> = obstacles
+        .iter()
+        .map(|rect| Rect {
+            x: rect.x - clearance,
+            y: rect.y - clearance,
+            width: rect.width + 2.0 * clearance,
+            height: rect.height + 2.0 * clearance,
+        })
+        .collect();
+    let blocked = |a: Point, b: Point| inflated.iter().any(|rect| segment_crosses_rect(a, b, rect));
+    let inside = |(x, y): Point| {
+        inflated
+            .iter()
+            .any(|rect| x > rect.x && x < rect.x + rect.width && y > rect.y && y < rect.y + rect.height)
+    };
+
+    // Detours can only bend at obstacle corners
+    let corners: Vec<Point> = inflated
+        .iter()
+        .flat_map(|rect| {
+            [
+                (rect.x, rect.y),
+                (rect.x + rect.width, rect.y),
+                (rect.x, rect.y + rect.height),
+                (rect.x + rect.width, rect.y + rect.height),
+            ]
+        })
+        .filter(|&corner| !inside(corner))
+        .collect();
+
+    for edge in edges.iter_mut() {
+        let Some(ref points) = edge.points else {
+            continue;
+        };
+        if points.len() < 2 {
+            continue;
+        }
+        let drawn = drawn_path(points, edge.path_style);
+        if !drawn.windows(2).any(|segment| blocked(segment[0], segment[1])) {
+            continue;
+        }
+
+        let (start, end) = (points[0], points[points.len() - 1]);
+        if inside(start) || inside(end) {
+            continue;
+        }
+
+        // Dijkstra over the visibility graph of start, end and all free corners
+        let vertices: Vec<Point> = [start, end].into_iter().chain(corners.iter().copied()).collect();
+        let mut distance = vec![f64::INFINITY; vertices.len()];
+        let mut previous: Vec<Option<usize>> = vec![None; vertices.len()];
+        let mut done = vec![false; vertices.len()];
+        distance[0] = 0.0;
+
+        while let Some(current) = (0..vertices.len())
+            .filter(|&v| !done[v] && distance[v].is_finite())
+            .min_by(|&a, &b| distance[a].total_cmp(&distance[b]))
+        {
+            if current == 1 {
+                break;
+            }
+            done[current] = true;
+            for next in 0..vertices.len() {
+                if done[next] || blocked(vertices[current], vertices[next]) {
+                    continue;
+                }
+                let (dx, dy) = (vertices[next].0 - vertices[current].0, vertices[next].1 - vertices[current].1);
+                let candidate = distance[current] + (dx * dx + dy * dy).sqrt();
+                if candidate < distance[next] {
+                    distance[next] = candidate;
+                    previous[next] = Some(current);
+                }
+            }
+        }
+
+        if distance[1].is_finite() {
+            let mut route = vec![end];
+            let mut current = 1;
+            while let Some(before) = previous[current] {
+                route.push(vertices[before]);
+                current = before;
+            }
+            route.reverse();
+            edge.points = Some(route);
+            edge.path_style = PathStyle::Straight;
+        }
+    }
+}
+
+/// Points of a curved edge from `start` to `end` that `drawn_path` draws through every waypoint.
+/// Several waypoints are passed by the spline through all points; a single one would be taken
+/// for the control point of a quadratic, so it's replaced by the control point that puts it
+/// halfway along the curve instead.
+pub fn curve_through_waypoints(start: Point, waypoints: Vec<Point>, end: Point) -> Vec<Point> {
+    match waypoints[..] {
+        [(x, y)] => vec![start, (2.0 * x - (start.0 + end.0) / 2.0, 2.0 * y - (start.1 + end.1) / 2.0), end],
+        _ => [vec![start], waypoints, vec![end]].concat(),
+    }
+}
+
+/// Cubic Bézier segments (first control, second control, end) of the Catmull-Rom spline
+/// through `points`, which is how curves with more than one control point are drawn
+pub(crate) fn catmull_rom_segments(points: &[Point]) -> Vec<(Point, Point, Point)> {
+    let last = points.len() - 1;
+    (0..last)
+        .map(|i| {
+            let (before, start, end, after) = (points[i.saturating_sub(1)], points[i], points[i + 1], points[(i + 2).min(last)]);
+            (
+                (start.0 + (end.0 - before.0) / 6.0, start.1 + (end.1 - before.1) / 6.0),
+                (end.0 - (after.0 - start.0) / 6.0, end.1 - (after.1 - start.1) / 6.0),
+                end,
+            )
+        })
+        .collect()
+}
+
+/// The polyline actually drawn for `points`; curves are approximated by short segments
+pub fn drawn_path(points: &[Point], style: PathStyle) -> Vec<Point> {
+    match (style, points) {
+        (PathStyle::Curved, points) if points.len() > 3 => {
+            let mut drawn = vec![points[0]];
+            let mut start = points[0];
+            for (first, second, end) in catmull_rom_segments(points) {
+                drawn.extend((1..=8).map(|i| {
+                    let t = i as f64 / 8.0;
+                    let u = 1.0 - t;
+                    let blend = |a: f64, b: f64, c: f64, d: f64| {
+                        u * u * u * a + 3.0 * u * u * t * b + 3.0 * u * t * t * c + t * t * t * d
+                    };
+                    (blend(start.0, first.0, second.0, end.0), blend(start.1, first.1, second.1, end.1))
+                }));
+                start = end;
+            }
+            drawn
+        }
+        (PathStyle::Curved, &[start, control, end]) => (0..=16)
+            .map(|i| {
+                let t = i as f64 / 16.0;
+                let u = 1.0 - t;
+                (
+                    u * u * start.0 + 2.0 * u * t * control.0 + t * t * end.0,
+                    u * u * start.1 + 2.0 * u * t * control.1 + t * t * end.1,
+                )
+            })
+            .collect(),
+        _ => points.to_vec(),
+    }
+}
+
+/// Douglas–Peucker simplification of straight (polyline) edge paths: corners deviating less
+/// than `tolerance` from the simplified line are removed. Curves keep their control points.
+pub fn simplify_edge_paths(edges: &mut [PlacedEdge], tolerance: f64) {
+    for edge in edges {
+        if edge.path_style == PathStyle::Straight
+            && let Some(ref mut points) = edge.points
+            && points.len() > 2
+        {
+            *points = simplify_polyline(points, tolerance);
+        }
+    }
+}
+
+pub fn simplify_polyline(points: &[Point], tolerance: f64) -> Vec<Point> {
+    let (&first, &last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) if points.len() > 2 => (first, last),
+        _ => return points.to_vec(),
+    };
+
+    // Distance of `p` from the line through the end points (from `first` if they coincide)
+    let (dx, dy) = (last.0 - first.0, last.1 - first.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let distance = |p: &Point| {
+        if length < 1e-9 {
+            ((p.0 - first.0).powi(2) + (p.1 - first.1).powi(2)).sqrt()
+        } else {
+            ((p.0 - first.0) * dy - (p.1 - first.1) * dx).abs() / length
+        }
+    };
+
+    let (farthest, max_distance) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, distance(p)))
+        .fold((0, -1.0), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+
+    if max_distance <= tolerance {
+        return vec![first, last];
+    }
+    let mut simplified = simplify_polyline(&points[..=farthest], tolerance);
+    simplified.pop();
+    simplified.extend(simplify_polyline(&points[farthest..], tolerance));
+    simplified
+}
+
+/// Whether the segment a-b passes through the interior of `rect` (touching its border is fine)
+pub fn segment_crosses_rect(a: Point, b: Point, rect: &Rect) -> bool {
+    let epsilon = 1e-6;
+    let (min_x, min_y) = (rect.x + epsilon, rect.y + epsilon);
+    let (max_x, max_y) = (rect.x + rect.width - epsilon, rect.y + rect.height - epsilon);
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+
+    // Liang-Barsky clipping: narrow down the part of the segment inside the rectangle
+    let (mut enter, mut exit) = (0.0_f64, 1.0_f64);
+    for (p, q) in [(-dx, a.0 - min_x), (dx, max_x - a.0), (-dy, a.1 - min_y), (dy, max_y - a.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                enter = enter.max(t);
+            } else {
+                exit = exit.min(t);
+            }
+        }
+    }
+
+    enter < exit
+}
+
+/// Path data using relative commands (`m`, `l`, `q`), each coordinate a delta to the current point
+pub fn relative_path_data(points: &[Point], style: PathStyle) -> String {
+    // Deltas between the rounded absolute points, so rounding errors don't add up along the path
+    let round = |(x, y): Point| ((x * 100.0).round() / 100.0, (y * 100.0).round() / 100.0);
+    let (start_x, start_y) = round(points[0]);
+    let mut d = format!("m{:.2},{:.2}", start_x, start_y);
+
+    match style {
+        PathStyle::Straight => {
+            d.push_str(" l");
+            for window in points.windows(2) {
+                let (from, to) = (round(window[0]), round(window[1]));
+                d.push_str(&format!(" {:.2},{:.2}", to.0 - from.0, to.1 - from.1));
+            }
+        }
+        PathStyle::Curved if points.len() > 3 => {
+            // All points of `c` are relative to the start of their segment
+            let mut start = (start_x, start_y);
+            for (first, second, end) in catmull_rom_segments(points) {
+                let (first, second, end) = (round(first), round(second), round(end));
+                d.push_str(&format!(
+                    " c{:.2},{:.2} {:.2},{:.2} {:.2},{:.2}",
+                    first.0 - start.0, first.1 - start.1,
+                    second.0 - start.0, second.1 - start.1,
+                    end.0 - start.0, end.1 - start.1
+                ));
+                start = end;
+            }
+        }
+        PathStyle::Curved => {
+            // Both the control and the end point of `q` are relative to the start of the segment
+            let (control, end) = (round(points[1]), round(points[2]));
+            d.push_str(&format!(
+                " q{:.2},{:.2} {:.2},{:.2}",
+                control.0 - start_x, control.1 - start_y,
+                end.0 - start_x, end.1 - start_y
+            ));
+        }
+    }
+
+    d
+}
+
+/// Axis-aligned rectangle, (x, y) being the top left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// `<x>,<y>,<width>,<height>`, e.g. an `--obstacle`
+impl FromStr for Rect {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split(',').map(parse_number).collect::<Result<Vec<f64>, _>>()?[..] {
+            [x, y, width, height] if width >= 0.0 && height >= 0.0 => Ok(Rect { x, y, width, height }),
+            _ => Err(format!("invalid rectangle '{}', expected <x>,<y>,<width>,<height>", value)),
+        }
+    }
+}
+
+/// Helper function to calculate the intersection of a line with a node's rectangle border
+#[allow(clippy::too_many_arguments)]
+pub fn calculate_node_border_intersection(
+    node_x: f64,
+    node_y: f64,
+    node_width: f64,
+    node_height: f64,
+    line_x1: f64,
+    line_y1: f64,
+    line_x2: f64,
+    line_y2: f64,
+) -> (f64, f64) {
+    // Calculate node rectangle boundaries
+    let left = node_x - node_width / 2.0;
+    let right = node_x + node_width / 2.0;
+    let top = node_y - node_height / 2.0;
+    let bottom = node_y + node_height / 2.0;
+
+    // Direction vector of the line
+    let dx = line_x2 - line_x1;
+    let dy = line_y2 - line_y1;
+
+    // Parameters for intersection with each edge
+    let t_left = if dx != 0.0 {
+        (left - line_x1) / dx
+    } else {
+        f64::INFINITY
+    };
+    let t_right = if dx != 0.0 {
+        (right - line_x1) / dx
+    } else {
+        f64::INFINITY
+    };
+    let t_top = if dy != 0.0 {
+        (top - line_y1) / dy
+    } else {
+        f64::INFINITY
+    };
+    let t_bottom = if dy != 0.0 {
+        (bottom - line_y1) / dy
+    } else {
+        f64::INFINITY
+    };
+
+    // Find valid intersections (0 <= t <= 1)
+    let mut valid_intersections = Vec::new();
+
+    if (0.0..=1.0).contains(&t_left) {
+        let y = line_y1 + t_left * dy;
+        if y >= top && y <= bottom {
+            valid_intersections.push((t_left, left, y));
+        }
+    }
+
+    if (0.0..=1.0).contains(&t_right) {
+        let y = line_y1 + t_right * dy;
+        if y >= top && y <= bottom {
+            valid_intersections.push((t_right, right, y));
+        }
+    }
+
+    if (0.0..=1.0).contains(&t_top) {
+        let x = line_x1 + t_top * dx;
+        if x >= left && x <= right {
+            valid_intersections.push((t_top, x, top));
+        }
+    }
+
+    if (0.0..=1.0).contains(&t_bottom) {
+        let x = line_x1 + t_bottom * dx;
+        if x >= left && x <= right {
+            valid_intersections.push((t_bottom, x, bottom));
+        }
+    }
+
+    // Sort by parameter t and get the closest intersection
+    valid_intersections.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    if valid_intersections.is_empty() {
+        // Fallback - if no intersection found, use the point on the node's center
+        (node_x, node_y)
+    } else {
+        // Return the first valid intersection (closest to line_x1, line_y1)
+        (valid_intersections[0].1, valid_intersections[0].2)
+    }
+}
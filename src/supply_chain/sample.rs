@@ -0,0 +1,360 @@
+//! Sample supply chain with random values, drawn when no graph is given
+
+use rand::Rng;
+use strum::IntoEnumIterator;
+
+use super::*;
+
+pub fn create_full_supply_chain(rng: &mut impl Rng) -> (Vec<TechNode>, Vec<TechEdge>) {
+    // Create all the nodes from the mermaid diagram
+    let nodes = vec![
+        create_node(
+            "advanced_circuitry_at_x_1_ad_75_d_44",
+            "ADVANCED_CIRCUITRY",
+            "X1-AD75-D44",
+            "ADVANCED",
+            rng,
+        ),
+        create_node(
+            "aluminum_at_x_1_ad_75_h_51",
+            "ALUMINUM",
+            "X1-AD75-H51",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "aluminum_ore_at_x_1_ad_75_xd_5_a",
+            "ALUMINUM_ORE",
+            "X1-AD75-XD5A",
+            "RAW_MATERIAL",
+            rng,
+        ),
+        create_node(
+            "clothing_at_x_1_ad_75_k_81",
+            "CLOTHING",
+            "X1-AD75-K81",
+            "CONSUMER",
+            rng,
+        ),
+        create_node(
+            "copper_at_x_1_ad_75_h_51",
+            "COPPER",
+            "X1-AD75-H51",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "copper_ore_at_x_1_ad_75_xd_5_a",
+            "COPPER_ORE",
+            "X1-AD75-XD5A",
+            "RAW_MATERIAL",
+            rng,
+        ),
+        create_node(
+            "electronics_at_x_1_ad_75_f_49",
+            "ELECTRONICS",
+            "X1-AD75-F49",
+            "INDUSTRIAL",
+            rng,
+        ),
+        create_node(
+            "equipment_at_x_1_ad_75_k_81",
+            "EQUIPMENT",
+            "X1-AD75-K81",
+            "INDUSTRIAL",
+            rng,
+        ),
+        create_node(
+            "fabrics_at_x_1_ad_75_e_46",
+            "FABRICS",
+            "X1-AD75-E46",
+            "INDUSTRIAL",
+            rng,
+        ),
+        create_node(
+            "fab_mats_at_x_1_ad_75_f_49",
+            "FAB_MATS",
+            "X1-AD75-F49",
+            "INDUSTRIAL",
+            rng,
+        ),
+        create_node(
+            "fertilizers_at_x_1_ad_75_g_50",
+            "FERTILIZERS",
+            "X1-AD75-G50",
+            "INDUSTRIAL",
+            rng,
+        ),
+        create_node("iron_at_x_1_ad_75_h_51", "IRON", "X1-AD75-H51", "REFINED", rng),
+        create_node(
+            "iron_ore_at_x_1_ad_75_xd_5_a",
+            "IRON_ORE",
+            "X1-AD75-XD5A",
+            "RAW_MATERIAL",
+            rng,
+        ),
+        create_node(
+            "liquid_hydrogen_at_x_1_ad_75_c_40",
+            "LIQUID_HYDROGEN",
+            "X1-AD75-C40",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "liquid_hydrogen_at_x_1_ad_75_c_41",
+            "LIQUID_HYDROGEN",
+            "X1-AD75-C41",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "liquid_nitrogen_at_x_1_ad_75_c_40",
+            "LIQUID_NITROGEN",
+            "X1-AD75-C40",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "liquid_nitrogen_at_x_1_ad_75_c_41",
+            "LIQUID_NITROGEN",
+            "X1-AD75-C41",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "machinery_at_x_1_ad_75_e_46",
+            "MACHINERY",
+            "X1-AD75-E46",
+            "INDUSTRIAL",
+            rng,
+        ),
+        create_node(
+            "microprocessors_at_x_1_ad_75_a_3",
+            "MICROPROCESSORS",
+            "X1-AD75-A3",
+            "ADVANCED",
+            rng,
+        ),
+        create_node(
+            "plastics_at_x_1_ad_75_g_50",
+            "PLASTICS",
+            "X1-AD75-G50",
+            "INDUSTRIAL",
+            rng,
+        ),
+        create_node(
+            "quartz_sand_at_x_1_ad_75_h_53",
+            "QUARTZ_SAND",
+            "X1-AD75-H53",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "quartz_sand_at_x_1_ad_75_xd_5_a",
+            "QUARTZ_SAND",
+            "X1-AD75-XD5A",
+            "RAW_MATERIAL",
+            rng,
+        ),
+        create_node(
+            "ship_parts_at_x_1_ad_75_d_43",
+            "SHIP_PARTS",
+            "X1-AD75-D43",
+            "ADVANCED",
+            rng,
+        ),
+        create_node(
+            "ship_plating_at_x_1_ad_75_d_44",
+            "SHIP_PLATING",
+            "X1-AD75-D44",
+            "ADVANCED",
+            rng,
+        ),
+        create_node(
+            "silicon_crystals_at_x_1_ad_75_h_53",
+            "SILICON_CRYSTALS",
+            "X1-AD75-H53",
+            "REFINED",
+            rng,
+        ),
+        create_node(
+            "silicon_crystals_at_x_1_ad_75_xd_5_a",
+            "SILICON_CRYSTALS",
+            "X1-AD75-XD5A",
+            "RAW_MATERIAL",
+            rng,
+        ),
+    ];
+
+    // Create all the edges from the mermaid diagram
+    let edges = vec![
+        create_edge("iron_at_x_1_ad_75_h_51", "machinery_at_x_1_ad_75_e_46", rng),
+        create_edge(
+            "liquid_hydrogen_at_x_1_ad_75_c_41",
+            "plastics_at_x_1_ad_75_g_50",
+            rng,
+        ),
+        create_edge(
+            "silicon_crystals_at_x_1_ad_75_h_53",
+            "electronics_at_x_1_ad_75_f_49",
+            rng,
+        ),
+        create_edge("copper_at_x_1_ad_75_h_51", "electronics_at_x_1_ad_75_f_49", rng),
+        create_edge("aluminum_at_x_1_ad_75_h_51", "equipment_at_x_1_ad_75_k_81", rng),
+        create_edge("plastics_at_x_1_ad_75_g_50", "equipment_at_x_1_ad_75_k_81", rng),
+        create_edge(
+            "aluminum_at_x_1_ad_75_h_51",
+            "ship_plating_at_x_1_ad_75_d_44",
+            rng,
+        ),
+        create_edge(
+            "machinery_at_x_1_ad_75_e_46",
+            "ship_plating_at_x_1_ad_75_d_44",
+            rng,
+        ),
+        create_edge("iron_at_x_1_ad_75_h_51", "fab_mats_at_x_1_ad_75_f_49", rng),
+        create_edge(
+            "quartz_sand_at_x_1_ad_75_h_53",
+            "fab_mats_at_x_1_ad_75_f_49",
+            rng,
+        ),
+        create_edge(
+            "silicon_crystals_at_x_1_ad_75_h_53",
+            "microprocessors_at_x_1_ad_75_a_3",
+            rng,
+        ),
+        create_edge(
+            "copper_at_x_1_ad_75_h_51",
+            "microprocessors_at_x_1_ad_75_a_3",
+            rng,
+        ),
+        create_edge(
+            "liquid_nitrogen_at_x_1_ad_75_c_41",
+            "fertilizers_at_x_1_ad_75_g_50",
+            rng,
+        ),
+        create_edge(
+            "equipment_at_x_1_ad_75_k_81",
+            "ship_parts_at_x_1_ad_75_d_43",
+            rng,
+        ),
+        create_edge(
+            "electronics_at_x_1_ad_75_f_49",
+            "ship_parts_at_x_1_ad_75_d_43",
+            rng,
+        ),
+        create_edge("fertilizers_at_x_1_ad_75_g_50", "fabrics_at_x_1_ad_75_e_46", rng),
+        create_edge(
+            "electronics_at_x_1_ad_75_f_49",
+            "advanced_circuitry_at_x_1_ad_75_d_44",
+            rng,
+        ),
+        create_edge(
+            "microprocessors_at_x_1_ad_75_a_3",
+            "advanced_circuitry_at_x_1_ad_75_d_44",
+            rng,
+        ),
+        create_edge("fabrics_at_x_1_ad_75_e_46", "clothing_at_x_1_ad_75_k_81", rng),
+        create_edge(
+            "quartz_sand_at_x_1_ad_75_xd_5_a",
+            "quartz_sand_at_x_1_ad_75_h_53",
+            rng,
+        ),
+        create_edge(
+            "liquid_nitrogen_at_x_1_ad_75_c_40",
+            "liquid_nitrogen_at_x_1_ad_75_c_41",
+            rng,
+        ),
+        create_edge("copper_ore_at_x_1_ad_75_xd_5_a", "copper_at_x_1_ad_75_h_51", rng),
+        create_edge(
+            "liquid_hydrogen_at_x_1_ad_75_c_40",
+            "liquid_hydrogen_at_x_1_ad_75_c_41",
+            rng,
+        ),
+        create_edge("iron_ore_at_x_1_ad_75_xd_5_a", "iron_at_x_1_ad_75_h_51", rng),
+        create_edge(
+            "aluminum_ore_at_x_1_ad_75_xd_5_a",
+            "aluminum_at_x_1_ad_75_h_51",
+            rng,
+        ),
+        create_edge(
+            "silicon_crystals_at_x_1_ad_75_xd_5_a",
+            "silicon_crystals_at_x_1_ad_75_h_53",
+            rng,
+        ),
+    ];
+
+    (nodes, edges)
+}
+
+/// Helper function to create nodes with random values
+pub(crate) fn create_node(id: &str, name: &str, waypoint: &str, node_type: &str, rng: &mut impl Rng) -> TechNode {
+    // Generate random supply level
+    let supplies: Vec<SupplyLevel> = SupplyLevel::iter().collect();
+    let random_supply = supplies[rng.random_range(0..supplies.len())].clone();
+
+    // Generate random activity level
+    let activities: Vec<ActivityLevel> = ActivityLevel::iter().collect();
+    let random_activity = activities[rng.random_range(0..activities.len())].clone();
+
+    // Random cost between 50 and 500
+    let random_cost = rng.random_range(50..=500);
+
+    // Random volume between 5 and 100
+    let random_volume = rng.random_range(5..=100);
+
+    TechNode {
+        id: id.to_string(),
+        name: name.to_string(),
+        waypoint_symbol: waypoint.to_string(),
+        waypoint_type: node_type.to_string(),
+        supply: random_supply,
+        activity: random_activity,
+        cost: random_cost,
+        volume: random_volume,
+        width: 200.0,
+        height: 165.0,
+        x: None,
+        y: None,
+        label: None,
+        icon_href: None,
+    }
+}
+
+/// Helper function to create edges with random activity and supply levels
+pub(crate) fn create_edge(source: &str, target: &str, rng: &mut impl Rng) -> TechEdge {
+    // Generate random activity level
+    let activities: Vec<ActivityLevel> = ActivityLevel::iter().collect();
+    let random_activity = activities[rng.random_range(0..activities.len())].clone();
+
+    // Generate random supply level
+    let supplies: Vec<SupplyLevel> = SupplyLevel::iter().collect();
+    let random_supply = supplies[rng.random_range(0..supplies.len())].clone();
+
+    // Random cost between 10 and 200
+    let random_cost = rng.random_range(10..=200);
+
+    // Random volume between 1 and 50
+    let random_volume = rng.random_range(1..=50);
+
+    // Random distance between 10 and 150
+    let random_distance = rng.random_range(10..=150);
+
+    // Random profit between -50 and 250 (can be negative)
+    let random_profit = rng.random_range(-50..=250);
+
+
+
+    TechEdge {
+        source: source.to_string(),
+        target: target.to_string(),
+        cost: random_cost,
+        activity: random_activity,
+        volume: random_volume,
+        supply: random_supply,
+        points: None,
+        distance: Some(random_distance),
+        profit: Some(random_profit),
+        path_style: PathStyle::Curved,
+    }
+}
@@ -0,0 +1,1732 @@
+use crate::{Orientation, Point};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::time::Duration;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use strum::IntoEnumIterator;
+
+use super::*;
+
+const ORIENTATIONS: [Orientation; 4] =
+    [Orientation::TopDown, Orientation::BottomUp, Orientation::LeftRight, Orientation::RightLeft];
+
+// A fan-out and a fan-in, so edges enter their targets straight as well as at an angle
+fn sample_graph() -> (Vec<TechNode>, Vec<TechEdge>) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes = ["a", "b", "c", "d"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges = vec![create_edge("a", "b", &mut rng), create_edge("a", "c", &mut rng), create_edge("d", "b", &mut rng)];
+    (nodes, edges)
+}
+
+// Refined goods with the given ids and an edge per pair, with seeded random values
+fn graph(ids: &[&str], pairs: &[(&str, &str)]) -> (Vec<TechNode>, Vec<TechEdge>) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes = ids.iter().map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng)).collect();
+    let edges = pairs.iter().map(|(source, target)| create_edge(source, target, &mut rng)).collect();
+    (nodes, edges)
+}
+
+// Attributes of every `<tag ...>` element of `svg` by name
+fn elements<'a>(svg: &'a str, tag: &str) -> Vec<HashMap<&'a str, &'a str>> {
+    svg.split(&format!("<{tag}"))
+        .skip(1)
+        .filter(|element| element.starts_with(char::is_whitespace))
+        .map(|element| {
+            let parts: Vec<&str> = element[..element.find('>').unwrap()].split('"').collect();
+            parts.chunks_exact(2).map(|pair| (pair[0].trim().trim_end_matches('='), pair[1])).collect()
+        })
+        .collect()
+}
+
+// Numeric attribute `name` of an element found by `elements`
+fn number(element: &HashMap<&str, &str>, name: &str) -> f64 {
+    element[name].parse().unwrap()
+}
+
+fn node_rect(node: &PlacedNode) -> Rect {
+    Rect {
+        x: node.x.unwrap() - node.width / 2.0,
+        y: node.y.unwrap() - node.height / 2.0,
+        width: node.width,
+        height: node.height,
+    }
+}
+
+// Calls `check` with the target and route of every edge of the sample graph
+fn for_routed_edges(orientation: Orientation, mut check: impl FnMut(&PlacedNode, &[Point])) {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let placed = place_nodes(&nodes, &layout.result);
+    for edge in place_edges(&edges, &layout.result) {
+        let target = placed.iter().find(|n| n.id == edge.target).unwrap();
+        check(target, edge.points.as_ref().unwrap());
+    }
+}
+
+#[test]
+fn layout_types_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<TechNode>();
+    assert_send_sync::<TechEdge>();
+    assert_send_sync::<LayoutOptions>();
+    assert_send_sync::<SupplyChainLayout>();
+    assert_send_sync::<LayoutError>();
+    assert_send_sync::<RenderConfig>();
+}
+
+#[test]
+fn default_theme_keeps_the_original_level_colors() {
+    let theme = Theme::default();
+    let supply: Vec<String> = SupplyLevel::iter().map(|level| theme.supply_color(&level).to_string()).collect();
+    assert_eq!(supply, ["#22c55e", "#86efac", "#fde047", "#f97316", "#ef4444"]);
+    let activity: Vec<String> = ActivityLevel::iter().map(|level| theme.activity_color(&level).to_string()).collect();
+    assert_eq!(activity, ["#eab308", "#86efac", "#22c55e", "#ef4444"]);
+    assert_eq!(theme.node_fill("RAW_MATERIAL").to_string(), "#091c26");
+    assert_eq!((theme.label_background.to_string(), theme.label_text.to_string()), ("#666".to_string(), "#eee".to_string()));
+    assert_eq!(theme.profit, ProfitColors::new("#22c55e", "#eab308", "#ef4444"));
+}
+
+#[test]
+fn profits_and_badges_follow_the_theme() {
+    let (mut nodes, mut edges) = graph(&["a", "b"], &[("a", "b")]);
+    let label = Rect { x: 0.0, y: 0.0, width: EDGE_LABEL_WIDTH, height: EDGE_LABEL_HEIGHT };
+    nodes[0].supply = SupplyLevel::Scarce;
+    nodes[1].supply = SupplyLevel::Abundant;
+    for name in ThemeName::iter() {
+        let theme = Theme::named(name);
+        let config = RenderConfig { theme: theme.clone(), ..RenderConfig::default() };
+        for (profit, color) in [(10, &theme.profit.profitable), (-10, &theme.profit.unprofitable)] {
+            edges[0].profit = Some(profit);
+            let svg = generate_edge_label_svg((0.0, 0.0), label, &edges[0], "a to b", &config);
+            assert_eq!(elements(&svg, "tspan")[5]["fill"], color.to_string(), "{} profit {}", name, profit);
+        }
+
+        let badges = supply_badges_at(Corner::TopRight);
+        let colors: Vec<ColorString> = nodes.iter().flat_map(|node| badges(node, &theme)).map(|badge| badge.color).collect();
+        assert_eq!(colors, [theme.badges[&SupplyLevel::Scarce].clone(), theme.badges[&SupplyLevel::Abundant].clone()], "{}", name);
+    }
+    assert_ne!(Theme::light().profit, Theme::dark().profit);
+    assert_ne!(Theme::print().badges, Theme::dark().badges);
+}
+
+#[test]
+fn escapes_markup_in_names() {
+    assert!(matches!(escape_xml("IRON_ORE"), Cow::Borrowed("IRON_ORE")));
+    assert_eq!(escape_xml(r#"a "b" & 'c'"#), "a &quot;b&quot; &amp; &apos;c&apos;");
+
+    let (mut nodes, edges) = sample_graph();
+    nodes[0].name = "IRON & STEEL <v2>".to_string();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let svg = output_svg(&nodes, &edges, &layout.result, &RenderConfig::default());
+    assert!(svg.contains("IRON &amp; STEEL &lt;v2&gt;"));
+    assert!(!svg.contains("<v2>") && !svg.contains("& STEEL"));
+}
+
+#[test]
+fn scale_bar_is_drawn_to_the_layout_scale() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let config = RenderConfig { scale_bar: Some(layout.scale_bar(100.0, "distance units")), ..RenderConfig::default() };
+    let svg = output_svg(&nodes, &edges, &layout.result, &config);
+
+    let bar = svg.split(r#"class="scale-bar""#).nth(1).unwrap();
+    let coordinate = |name: &str| -> f64 {
+        bar.split(&format!(r#" {name}=""#)).nth(1).unwrap().split('"').next().unwrap().parse().unwrap()
+    };
+    assert!((coordinate("x2") - coordinate("x1") - 150.0).abs() < 1e-6);
+    assert!(bar.contains("100 distance units"));
+}
+
+#[test]
+fn rank_bands_alternate_along_the_rank_axis() {
+    let (nodes, edges) = graph(&["a", "b", "c", "d"], &[("a", "b"), ("b", "c"), ("a", "d")]);
+    for (orientation, along, across) in [(Orientation::LeftRight, ("x", "width"), "height"), (Orientation::TopDown, ("y", "height"), "width")] {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+        let bounds = layout.result.bounds;
+        let svg = generate_rank_bands_svg(&place_nodes(&nodes, &layout.result), bounds, &Theme::default());
+        let bands = elements(&svg, "rect");
+
+        assert_eq!(bands.len(), 3, "{:?}", orientation);
+        let opacities: Vec<&str> = bands.iter().map(|band| band["fill-opacity"]).collect();
+        assert_eq!(opacities, ["0.08", "0.16", "0.08"], "{:?}", orientation);
+        // Each band spans the drawing across the ranks and picks up where the last one ended
+        let extent = match orientation {
+            Orientation::LeftRight => bounds.3 - bounds.1,
+            _ => bounds.2 - bounds.0,
+        };
+        for band in &bands {
+            assert!((number(band, across) - extent).abs() < 0.01, "{:?}: {:?}", orientation, band);
+        }
+        for pair in bands.windows(2) {
+            let end = number(&pair[0], along.0) + number(&pair[0], along.1);
+            assert!((number(&pair[1], along.0) - end).abs() < 0.02, "{:?}: {:?}", orientation, pair);
+        }
+    }
+}
+
+#[test]
+fn rank_guides_run_through_the_rank_positions() {
+    let (nodes, edges) = graph(&["a", "b", "c", "d"], &[("a", "b"), ("b", "c"), ("a", "d")]);
+    for (orientation, ends) in [(Orientation::LeftRight, ("x1", "x2")), (Orientation::TopDown, ("y1", "y2"))] {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+        let svg = generate_rank_guides_svg(&place_nodes(&nodes, &layout.result), layout.result.bounds, &Theme::default());
+        let guides = elements(&svg, "line");
+
+        assert_eq!(guides.len(), layout.rank_positions.len(), "{:?}", orientation);
+        for (guide, position) in guides.iter().zip(&layout.rank_positions) {
+            assert!((number(guide, ends.0) - position).abs() < 0.01, "{:?}: {:?} at {}", orientation, guide, position);
+            assert_eq!(guide[ends.0], guide[ends.1], "{:?}", orientation);
+        }
+    }
+}
+
+#[test]
+fn frame_surrounds_the_drawing_inside_the_margin() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let frame = Frame::default();
+    let config = RenderConfig { frame: Some(frame.clone()), ..RenderConfig::default() };
+    let svg = output_svg(&nodes, &edges, &layout.result, &config);
+    let rect = elements(&svg, "rect").into_iter().find(|rect| rect.get("class") == Some(&"frame")).unwrap();
+
+    // The frame keeps `inset` to the canvas border, which lies `SVG_MARGIN` outside the bounds
+    let (min_x, min_y, max_x, max_y) = layout.result.bounds;
+    let gap = SVG_MARGIN - frame.inset;
+    assert!(gap > 0.0);
+    assert!((number(&rect, "x") - (min_x - gap)).abs() < 0.01, "{:?}", rect);
+    assert!((number(&rect, "y") - (min_y - gap)).abs() < 0.01, "{:?}", rect);
+    assert!((number(&rect, "width") - (max_x - min_x + 2.0 * gap)).abs() < 0.01, "{:?}", rect);
+    assert!((number(&rect, "height") - (max_y - min_y + 2.0 * gap)).abs() < 0.01, "{:?}", rect);
+}
+
+#[test]
+fn legend_lists_every_level_and_node_type() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let config = RenderConfig { include_legend: true, ..RenderConfig::default() };
+    let svg = output_svg(&nodes, &edges, &layout.result, &config);
+    let legend = svg.split(r#"<g class="legend">"#).nth(1).unwrap().split("</g>").next().unwrap();
+
+    let theme = &config.theme;
+    let mut expected: Vec<(String, (&str, String))> = Vec::new();
+    expected.extend(SupplyLevel::iter().map(|level| (level.to_string(), ("fill", theme.supply_color(&level).to_string()))));
+    expected.extend(ActivityLevel::iter().map(|level| (level.to_string(), ("stroke", theme.activity_color(&level).to_string()))));
+    expected.extend(WAYPOINT_TYPES.iter().map(|name| (name.to_string(), ("fill", theme.node_fill(name).to_string()))));
+    let swatches = elements(legend, "rect");
+    assert_eq!(swatches.len(), expected.len());
+    for (swatch, (name, (paint, color))) in swatches.iter().zip(&expected) {
+        assert_eq!(swatch[*paint], color, "{}", name);
+        assert!(legend.contains(&format!(">{}</text>", name)), "{}", name);
+    }
+}
+
+#[test]
+fn title_emphasis_override_applies_per_node() {
+    let (nodes, _) = graph(&["plain", "large"], &[]);
+    let config = RenderConfig {
+        title_emphasis: TitleEmphasis::Scaled(2.0),
+        title_emphasis_override: Some(|node| (node.id == "plain").then_some(TitleEmphasis::Off)),
+        ..RenderConfig::default()
+    };
+    // Font size given to the first line of the node, if it differs from the others
+    let title_size = |node: &TechNode| {
+        let placed = PlacedNode { node, rank: Some(0), x: Some(0.0), y: Some(0.0) };
+        let svg = generate_node_svg(&placed, &config, &NodeStyle::default());
+        elements(&svg, "tspan")[0].get("font-size").map(|size| size.to_string())
+    };
+
+    assert_eq!(title_size(&nodes[0]), None);
+    assert_eq!(title_size(&nodes[1]).as_deref(), Some("20"));
+}
+
+#[test]
+fn unpositioned_nodes_are_parked_as_ghosts() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let mut result = layout.result;
+    result.positions.remove("d");
+    // (aria-label, opacity) of every ghost node
+    let ghosts = |park_unpositioned: bool| {
+        let svg = output_svg(&nodes, &edges, &result, &RenderConfig { park_unpositioned, ..RenderConfig::default() });
+        elements(&svg, "g")
+            .into_iter()
+            .filter(|group| group.get("class") == Some(&"ghost"))
+            .map(|group| (group["aria-label"].to_string(), group["opacity"].to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    assert!(ghosts(false).is_empty());
+    assert_eq!(ghosts(true), [(node_description(&nodes[3]), "0.45".to_string())]);
+}
+
+#[test]
+fn zoom_hints_follow_the_downscaled_canvas() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let attribute = |svg: &str, name: &str| -> f64 {
+        let start = svg.find(&format!(r#" {name}=""#)).unwrap() + name.len() + 3;
+        svg[start..].split('"').next().unwrap().parse().unwrap()
+    };
+    let config = RenderConfig { zoom_metadata: ZoomMetadata::Auto, ..RenderConfig::default() };
+    let natural = output_svg(&nodes, &edges, &layout.result, &config);
+    let view_width: f64 = natural.split(r#"viewBox="0 0 "#).nth(1).unwrap().split(' ').next().unwrap().parse().unwrap();
+    let max_width = Some(view_width / 2.0);
+    let halved = output_svg(&nodes, &edges, &layout.result, &RenderConfig { max_width, ..config.clone() });
+
+    // The downscale is written with four decimals like an attribute, not as a raw float
+    assert!(!natural.contains("scale("));
+    let transform = halved.split("scale(").nth(1).unwrap().split(')').next().unwrap();
+    assert_eq!(transform.split('.').nth(1).map(str::len), Some(4), "scale({})", transform);
+    assert_eq!(attribute(&natural, "data-natural-scale"), 1.0);
+    assert_eq!(attribute(&halved, "data-natural-scale"), 0.5);
+    // Nodes drawn at half the size take twice the zoom to fill the viewport
+    let max_zoom = attribute(&natural, "data-max-zoom");
+    assert!(max_zoom > 1.0);
+    assert!((attribute(&halved, "data-max-zoom") - 2.0 * max_zoom).abs() < 0.01);
+}
+
+#[test]
+fn wraps_text_after_spaces_and_underscores() {
+    // 7.5 per capital at font size 10
+    assert_eq!(wrap_text("ADVANCED_CIRCUITRY", 10.0, 80.0), ["ADVANCED_", "CIRCUITRY"]);
+    assert_eq!(wrap_text("ADVANCED_CIRCUITRY", 10.0, 200.0), ["ADVANCED_CIRCUITRY"]);
+    // A word wider than the limit keeps a row of its own instead of being cut
+    assert_eq!(wrap_text("IRON MICROPROCESSORS ORE", 10.0, 60.0), ["IRON", "MICROPROCESSORS", "ORE"]);
+}
+
+#[test]
+fn wrapped_text_sizes_the_box_before_layout() {
+    let (mut nodes, _) = sample_graph();
+    nodes[0].name = "ADVANCED_CIRCUITRY_AND_SHIP_PLATING_PARTS".to_string();
+    let config = RenderConfig { wrap_node_text: true, ..RenderConfig::default() };
+    let wrapped = fit_nodes_to_text(&nodes, &config);
+    let unwrapped = fit_nodes_to_text(&nodes, &RenderConfig::default());
+
+    // Wrapping keeps the width and takes more rows instead
+    assert_eq!(wrapped[0].width, nodes[0].width.ceil());
+    assert!(unwrapped[0].width > wrapped[0].width);
+    assert!(wrapped[0].height > unwrapped[0].height);
+    assert_eq!(wrapped[1].height, unwrapped[1].height);
+
+    // The box drawn is the one laid out
+    let placed = PlacedNode { node: &wrapped[0], rank: None, x: Some(0.0), y: Some(0.0) };
+    let svg = generate_node_svg(&placed, &config, &NodeStyle::default());
+    assert!(svg.contains(&format!(r#"height="{:.2}""#, wrapped[0].height)), "{}", svg);
+}
+
+#[test]
+fn level_filter_orders_levels_by_strength() {
+    let filter = LevelFilter { activity: ActivityLevel::Weak.at_or_below(), ..LevelFilter::default() };
+    let node = |supply: SupplyLevel, activity: ActivityLevel| TechNode { supply, activity, ..sample_graph().0[0].clone() };
+    assert!(filter.matches(&node(SupplyLevel::Abundant, ActivityLevel::Restricted)));
+    assert!(filter.matches(&node(SupplyLevel::Abundant, ActivityLevel::Weak)));
+    assert!(!filter.matches(&node(SupplyLevel::Abundant, ActivityLevel::Growing)));
+    assert_eq!(ActivityLevel::Strong.at_or_above(), HashSet::from([ActivityLevel::Strong]));
+
+    // Either level is enough
+    let filter = LevelFilter { supply: SupplyLevel::Limited.at_or_below(), ..filter };
+    assert!(filter.matches(&node(SupplyLevel::Scarce, ActivityLevel::Strong)));
+    assert!(!filter.matches(&node(SupplyLevel::Moderate, ActivityLevel::Strong)));
+    assert_eq!(SupplyLevel::High.at_or_above(), HashSet::from([SupplyLevel::High, SupplyLevel::Abundant]));
+}
+
+#[test]
+fn level_filter_dims_edges_with_an_endpoint_outside_it() {
+    // a -> b stays emphasized, a -> c and d -> b lead to nodes filtered out
+    let (mut nodes, edges) = sample_graph();
+    for node in &mut nodes {
+        node.supply = if node.id == "a" || node.id == "b" { SupplyLevel::Scarce } else { SupplyLevel::Abundant };
+    }
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let filter = LevelFilter { supply: SupplyLevel::Limited.at_or_below(), ..LevelFilter::default() };
+    let svg = output_svg(&nodes, &edges, &layout.result, &RenderConfig { level_filter: Some(filter), ..RenderConfig::default() });
+
+    assert_eq!(svg.matches(&format!("{DIMMED_GROUP}<path")).count(), 2);
+    let label = |source: &str, target: &str| format!(r#"<g aria-label="{source} at X1-TEST-A1 to {target} at X1-TEST-A1"#);
+    assert!(svg.contains(&format!("{DIMMED_GROUP}{}", label("A", "C"))));
+    assert!(svg.contains(&format!("{DIMMED_GROUP}{}", label("D", "B"))));
+    assert!(svg.contains(&label("A", "B")) && !svg.contains(&format!("{DIMMED_GROUP}{}", label("A", "B"))));
+}
+
+#[test]
+fn nodes_and_edge_labels_carry_escaped_tooltips() {
+    let (mut nodes, edges) = sample_graph();
+    nodes[0].name = "IRON & STEEL <v2>".to_string();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let svg = output_svg(&nodes, &edges, &layout.result, &RenderConfig::default());
+
+    let node = "IRON &amp; STEEL &lt;v2&gt; at X1-TEST-A1 (REFINED)";
+    assert!(svg.contains(&format!(r#"<g aria-label="{node}"#)), "{}", svg);
+    assert!(svg.contains(&format!("<title>{node}")));
+    // Both edges leaving the node describe it as their source
+    let edge = "IRON &amp; STEEL &lt;v2&gt; at X1-TEST-A1 to ";
+    assert_eq!(svg.matches(&format!(r#"<g aria-label="{edge}"#)).count(), 2);
+    assert_eq!(svg.matches(&format!("<title>{edge}")).count(), 2);
+}
+
+#[test]
+fn exports_dot_with_quoted_ids_and_cost_labels() {
+    let (mut nodes, edges) = sample_graph();
+    nodes[3].id = r#"d "quoted" \ id"#.to_string();
+    let edges: Vec<TechEdge> = edges
+        .into_iter()
+        .map(|mut edge| {
+            if edge.source == "d" {
+                edge.source = nodes[3].id.clone();
+            }
+            edge
+        })
+        .collect();
+    let dot = to_dot(&nodes, &edges);
+    assert!(dot.starts_with("digraph G {\n    rankdir=LR;"));
+    assert!(dot.contains(r##""a" [label="A\nX1-TEST-A1", fillcolor="#"##));
+    assert!(dot.contains(&format!(r#""a" -> "b" [label="{}"];"#, edges[0].cost)));
+    assert!(dot.contains(r#""d \"quoted\" \\ id" -> "b""#));
+    assert!(::layout::gv::DotParser::new(&dot).process().is_ok());
+}
+
+#[test]
+fn saved_graph_loads_with_its_layout() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let path = std::env::temp_dir().join(format!("saved_graph_loads_with_its_layout-{}.json", std::process::id()));
+    save_graph(&path, &nodes, &edges, &layout.result).unwrap();
+    let (loaded_nodes, loaded_edges) = load_graph(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let close = |a: Point, b: Point| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9;
+    for (node, loaded) in place_nodes(&nodes, &layout.result).iter().zip(&loaded_nodes) {
+        assert_eq!((&node.id, &node.name, node.width, node.height), (&loaded.id, &loaded.name, loaded.width, loaded.height));
+        assert!(close((node.x.unwrap(), node.y.unwrap()), (loaded.x.unwrap(), loaded.y.unwrap())), "{}", node.id);
+    }
+    for (edge, loaded) in place_edges(&edges, &layout.result).iter().zip(&loaded_edges) {
+        assert_eq!((&edge.source, &edge.target, edge.cost), (&loaded.source, &loaded.target, loaded.cost));
+        let (points, loaded_points) = (edge.points.as_ref().unwrap(), loaded.points.as_ref().unwrap());
+        assert_eq!(points.len(), loaded_points.len());
+        assert!(points.iter().zip(loaded_points).all(|(&a, &b)| close(a, b)), "{} -> {}", edge.source, edge.target);
+        assert_eq!(edge.path_style, loaded.path_style);
+    }
+    assert_eq!((loaded_nodes.len(), loaded_edges.len()), (nodes.len(), edges.len()));
+}
+
+#[test]
+fn loading_an_edge_to_an_unknown_node_fails() {
+    let (nodes, mut edges) = sample_graph();
+    edges[0].target = "unknown".to_string();
+    let path = std::env::temp_dir().join(format!("loading_an_edge_to_an_unknown_node_fails-{}.json", std::process::id()));
+    fs::write(&path, serde_json::to_string(&GraphDocument { nodes, edges }).unwrap()).unwrap();
+    let error = load_graph(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("unknown node 'unknown'"), "{}", error);
+}
+
+#[test]
+fn geojson_has_a_point_per_node_and_a_line_string_per_route() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let geojson: serde_json::Value = serde_json::from_str(&to_geojson(&nodes, &edges, &layout.result)).unwrap();
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let features = geojson["features"].as_array().unwrap();
+    // Coordinates of a Point or LineString, flattened to x, y, x, y, ...
+    let coordinates = |geometry: &serde_json::Value| -> Vec<f64> {
+        let coordinates = geometry["coordinates"].as_array().unwrap();
+        let numbers = coordinates.iter().flat_map(|c| c.as_array().map_or_else(|| vec![c], |point| point.iter().collect()));
+        numbers.map(|number| number.as_f64().unwrap()).collect()
+    };
+
+    let (points, line_strings): (Vec<_>, Vec<_>) =
+        features.iter().map(|feature| &feature["geometry"]).partition(|geometry| geometry["type"] == "Point");
+    for (node, point) in place_nodes(&nodes, &layout.result).iter().zip(&points) {
+        assert_eq!(coordinates(point), [node.x.unwrap(), node.y.unwrap()]);
+    }
+    let edges = place_edges(&edges, &layout.result);
+    assert_eq!(line_strings.len(), edges.len());
+    for (edge, line_string) in edges.iter().zip(&line_strings) {
+        assert_eq!(line_string["type"], "LineString");
+        let route: Vec<f64> = drawn_path(edge.points.as_ref().unwrap(), edge.path_style).into_iter().flat_map(|(x, y)| [x, y]).collect();
+        assert_eq!(coordinates(line_string), route, "{} -> {}", edge.source, edge.target);
+    }
+    assert_eq!(features[nodes.len()]["properties"]["source"], "a");
+}
+
+#[test]
+fn regions_json_lists_the_svg_rects() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let config = RenderConfig::default();
+    let regions: serde_json::Value = serde_json::from_str(&to_regions_json(&nodes, &edges, &layout.result, &config)).unwrap();
+    let rect = |region: &serde_json::Value| Rect {
+        x: region["rect"]["x"].as_f64().unwrap(),
+        y: region["rect"]["y"].as_f64().unwrap(),
+        width: region["rect"]["width"].as_f64().unwrap(),
+        height: region["rect"]["height"].as_f64().unwrap(),
+    };
+
+    let node_regions: Vec<(String, Rect)> = regions["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|region| (region["id"].as_str().unwrap().to_string(), rect(region)))
+        .collect();
+    assert_eq!(node_regions, svg_node_rects(&nodes, &edges, &layout.result, &config));
+    let edge_regions: Vec<((String, String), Rect)> = regions["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|region| {
+            let endpoint = |key: &str| region[key].as_str().unwrap().to_string();
+            ((endpoint("source"), endpoint("target")), rect(region))
+        })
+        .collect();
+    assert_eq!(edge_regions, svg_edge_rects(&nodes, &edges, &layout.result, &config));
+}
+
+#[test]
+fn label_fit_reports_the_boxes_too_small_for_their_text() {
+    let (mut nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    for node in &mut nodes {
+        (node.width, node.height) = (1000.0, 1000.0);
+    }
+    let config = RenderConfig::default();
+    let roomy = check_label_fit(&nodes, &layout.result, 10.0, &config);
+    assert!(roomy.all_fit(), "{:?}", roomy.overflowing);
+
+    (nodes[1].width, nodes[1].height) = (10.0, 10.0);
+    (nodes[2].width, nodes[2].height) = (0.0, 0.0);
+    let cramped = check_label_fit(&nodes, &layout.result, 10.0, &config);
+    assert_eq!(cramped.overflowing, ["b", "c"]);
+    // Only the small box grows the canvas, the empty one leaves it finite
+    assert!(cramped.canvas.0.is_finite() && cramped.canvas.1.is_finite());
+    assert!(cramped.canvas.0 > roomy.canvas.0 && cramped.canvas.1 > roomy.canvas.1, "{:?}", cramped.canvas);
+}
+
+#[test]
+fn node_sizes_scale_between_the_extreme_values_but_hold_their_text() {
+    let (mut nodes, _) = sample_graph();
+    for (node, volume) in nodes.iter_mut().zip([10, 40, 70, 100]) {
+        node.volume = volume;
+    }
+    let sizing = NodeSizing { size_fn: |node| node.volume as f64, min_scale: 0.1, max_scale: 2.0 };
+    let config = RenderConfig { size_fn: Some(sizing), ..RenderConfig::default() };
+    let titles = disambiguated_titles(&nodes, config.duplicate_names);
+    let sized = apply_node_sizes(&nodes, &config);
+
+    for ((node, sized), factor) in nodes.iter().zip(&sized).zip([0.1, 0.1 + 1.9 / 3.0, 0.1 + 3.8 / 3.0, 2.0]) {
+        let (needed_width, needed_height) = needed_node_size(node, titles.get(&node.id).cloned(), 10.0, &config);
+        assert_eq!((sized.width, sized.height), ((node.width * factor).max(needed_width), (node.height * factor).max(needed_height)), "{}", node.id);
+    }
+    // The smallest value shrinks its box onto the text, the largest doubles its box
+    let (needed_width, needed_height) = needed_node_size(&nodes[0], titles.get("a").cloned(), 10.0, &config);
+    assert_eq!((sized[0].width, sized[0].height), (needed_width, needed_height));
+    assert_eq!((sized[3].width, sized[3].height), (2.0 * nodes[3].width, 2.0 * nodes[3].height));
+}
+
+#[test]
+fn badges_sit_on_their_corner_and_line_up_toward_the_center() {
+    let (nodes, _) = sample_graph();
+    let node = PlacedNode { node: &nodes[0], rank: None, x: Some(100.0), y: Some(50.0) };
+    let (half_width, half_height) = (node.width / 2.0, node.height / 2.0);
+    let dot = |corner| Badge { shape: BadgeShape::Dot, color: ColorString::from("#ff0000"), corner };
+    let badges = [dot(Corner::TopRight), dot(Corner::BottomLeft), dot(Corner::TopRight)];
+    let svg = generate_badges_svg(&node, &badges, &RenderConfig::default().theme);
+
+    let centers: Vec<Point> = elements(&svg, "circle").iter().map(|circle| (number(circle, "cx"), number(circle, "cy"))).collect();
+    assert_eq!(
+        centers,
+        [
+            (100.0 + half_width, 50.0 - half_height),
+            (100.0 - half_width, 50.0 + half_height),
+            (100.0 + half_width - 2.5 * BADGE_RADIUS, 50.0 - half_height),
+        ]
+    );
+}
+
+#[test]
+fn png_renders_the_default_font_stack_with_installed_fonts() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let path = std::env::temp_dir().join(format!("png_renders_the_default_font_stack-{}.png", std::process::id()));
+
+    let config = RenderConfig::default();
+    assert_eq!(config.font_family(), DEFAULT_FONT_FAMILY);
+    let rendered = render_png(&output_svg(&nodes, &edges, &layout.result, &config), &path, 1.0);
+    let png = fs::read(&path);
+    let _ = fs::remove_file(&path);
+    assert!(rendered.is_ok(), "{}", rendered.unwrap_err());
+    assert!(png.unwrap().starts_with(b"\x89PNG"));
+
+    let config = RenderConfig { font_family: Some("No Such Font".to_string()), ..RenderConfig::default() };
+    let rendered = render_png(&output_svg(&nodes, &edges, &layout.result, &config), &path, 1.0);
+    assert!(matches!(rendered, Err(PngError::MissingFont { .. })));
+    assert!(!path.exists());
+}
+
+#[test]
+fn html_page_embeds_the_svg_and_the_pan_zoom_script() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let svg = output_svg(&nodes, &edges, &layout.result, &RenderConfig::default());
+    let html = output_html(&svg, &Theme::default());
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains(&format!(r#"<div id="canvas">{}</div>"#, svg)));
+    let script = &html[html.find("<script>").unwrap()..html.find("</script>").unwrap()];
+    assert!(script.contains(r#"addEventListener("wheel""#) && script.contains(r#"addEventListener("mousedown""#));
+    assert!(html.trim_end().ends_with("</html>"));
+
+    for name in ThemeName::iter() {
+        let theme = Theme::named(name);
+        let html = output_html(&svg, &theme);
+        assert!(html.contains(&format!("overflow: hidden; background: {}; }}", theme.page_background)), "{}", name);
+    }
+    assert_eq!(Theme::light().page_background.to_string(), "#ffffff");
+}
+
+#[test]
+fn lays_out_fixture_with_finite_coordinates_and_expected_ranks() {
+    // a -> b -> c -> d plus the shortcut a -> c: four ranks deep
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges = vec![create_edge("a", "b", &mut rng), create_edge("b", "c", &mut rng), create_edge("c", "d", &mut rng), create_edge("a", "c", &mut rng)];
+
+    for orientation in ORIENTATIONS {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+        for node in &nodes {
+            let (x, y) = layout.result.positions[&node.id];
+            assert!(x.is_finite() && y.is_finite(), "{:?}: node '{}' at ({}, {})", orientation, node.id, x, y);
+        }
+        assert_eq!(layout.rank_positions.len(), 4, "{:?}", orientation);
+    }
+}
+
+#[test]
+fn minimum_length_spreads_the_ranks_apart() {
+    let (nodes, edges) = graph(&["a", "b", "c"], &[("a", "b"), ("b", "c"), ("a", "c")]);
+    for orientation in ORIENTATIONS {
+        for straight_long_edges in [false, true] {
+            let layout_with = |minimum_length| {
+                let options = LayoutOptions { minimum_length: Some(minimum_length), straight_long_edges, ..LayoutOptions::default() };
+                build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &options).unwrap()
+            };
+            let (unit, double) = (layout_with(1), layout_with(2));
+            let ranks: Vec<usize> = ["a", "b", "c"].iter().map(|id| double.result.ranks[*id]).collect();
+            assert_eq!(ranks, [0, 2, 4], "{:?}", orientation);
+            let gaps = |positions: &[f64]| positions.windows(2).map(|pair| pair[1] - pair[0]).collect::<Vec<_>>();
+            let doubled: Vec<f64> = gaps(&unit.rank_positions).iter().map(|gap| 2.0 * gap).collect();
+            assert_eq!(gaps(&double.rank_positions), doubled, "{:?}", orientation);
+
+            let svg = output_svg(&nodes, &edges, &double.result, &RenderConfig::default());
+            assert_eq!(elements(&svg, "path").iter().filter(|path| path.contains_key("marker-end")).count(), edges.len());
+        }
+    }
+    let options = LayoutOptions { minimum_length: Some(0), ..LayoutOptions::default() };
+    assert!(matches!(
+        build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options),
+        Err(LayoutError::InvalidMinimumLength(0))
+    ));
+}
+
+#[test]
+fn ranks_every_node_of_a_cycle_and_reverses_one_edge() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["a", "b", "c"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges = vec![create_edge("a", "b", &mut rng), create_edge("b", "c", &mut rng), create_edge("c", "a", &mut rng)];
+
+    for orientation in ORIENTATIONS {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+        assert!(nodes.iter().all(|node| layout.result.ranks.contains_key(&node.id)), "{:?}", orientation);
+        assert_eq!(layout.reversed_edges.len(), 1, "{:?}", orientation);
+        let routes = layout.result.routes(&edges);
+        let flagged: Vec<usize> = (0..edges.len()).filter(|&i| routes[i].unwrap().reversed).collect();
+        assert_eq!(flagged, layout.reversed_edges, "{:?}", orientation);
+    }
+}
+
+#[test]
+fn split_components_sit_side_by_side() {
+    // a -> b apart from the cycle c -> d -> c
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["c", "a", "d", "b"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges = vec![create_edge("c", "d", &mut rng), create_edge("a", "b", &mut rng), create_edge("d", "c", &mut rng)];
+
+    let layout = build_component_layouts(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let (nodes, edges) = layout.graph(&nodes, &edges);
+    let ids: Vec<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["c", "d", "a", "b"]);
+    assert_eq!(layout.reversed_edges.len(), 1);
+    assert!(layout.result.routes(edges)[layout.reversed_edges[0]].unwrap().reversed);
+
+    let placed = place_nodes(nodes, &layout.result);
+    let first_right = placed[..2].iter().map(|node| node_rect(node).x + node.width).fold(f64::MIN, f64::max);
+    let second_left = placed[2..].iter().map(|node| node_rect(node).x).fold(f64::MAX, f64::min);
+    assert!(second_left - first_right >= SVG_MARGIN, "{} {}", first_right, second_left);
+}
+
+#[test]
+fn component_metrics_add_up() {
+    let (nodes, edges) = graph(&["a", "b", "c", "d", "e"], &[("a", "b"), ("a", "c"), ("d", "e")]);
+    let options = LayoutOptions { collect_metrics: true, ..LayoutOptions::default() };
+    let layout = build_component_layouts(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options).unwrap();
+    let metrics = layout.metrics.unwrap();
+    assert_eq!((metrics.nodes, metrics.edges, metrics.crossings), (5, 3, 0));
+    assert!(metrics.render.is_none());
+
+    let single = build_supply_chain_layout(&nodes[..3], &edges[..2], Orientation::LeftRight, 1.5, 0.75, &options).unwrap();
+    let mut total = single.metrics.unwrap();
+    total.add(&LayoutMetrics { nodes: 2, edges: 1, render: Some(Duration::from_millis(2)), ..LayoutMetrics::default() });
+    assert_eq!((total.nodes, total.edges, total.render), (5, 3, Some(Duration::from_millis(2))));
+}
+
+#[test]
+fn parses_command_line_values() {
+    assert_eq!("cut:6".parse(), Ok(CornerStyle::Cut(6.0)));
+    assert_eq!("sharp".parse(), Ok(CornerStyle::Sharp));
+    assert!("rounded".parse::<CornerStyle>().is_err());
+    assert_eq!("1.6".parse(), Ok(TitleEmphasis::Scaled(1.6)));
+    assert_eq!("800x600".parse(), Ok(SvgSize::Fixed { width: 800.0, height: 600.0 }));
+    assert_eq!(
+        "0.5:4:1".parse(),
+        Ok(ZoomMetadata::Fixed(ZoomHints { min_zoom: 0.5, max_zoom: 4.0, natural_scale: 1.0 }))
+    );
+    assert!("0.5:4".parse::<ZoomMetadata>().is_err());
+    assert_eq!(parse_scale_bar("100:distance units"), Ok((100.0, "distance units".to_string())));
+    assert!(parse_scale_bar("100").is_err());
+    assert_eq!("10,-20,30.5,40".parse(), Ok(Rect { x: 10.0, y: -20.0, width: 30.5, height: 40.0 }));
+    assert!("10,20,30".parse::<Rect>().is_err());
+
+    let (_, edges) = sample_graph();
+    let uniform: WeightBy = "uniform".parse().unwrap();
+    assert!(edges.iter().all(|edge| uniform.weight(edge) == 1.0));
+    assert!("weight".parse::<WeightBy>().is_err());
+}
+
+#[test]
+fn transposing_swaps_the_axes_and_twice_restores_the_layout() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap().result;
+    let transposed = transpose_layout(&nodes, &edges, &layout);
+
+    for (id, &(x, y)) in &layout.positions {
+        assert_eq!(transposed.positions[id], (y, x), "{}", id);
+    }
+    for (pair, paths) in &layout.edge_paths {
+        for (path, transposed_path) in paths.iter().zip(&transposed.edge_paths[pair]) {
+            let swapped: Vec<Point> = path.points.iter().map(|&(x, y)| (y, x)).collect();
+            assert_eq!(transposed_path.points, swapped, "{:?}", pair);
+        }
+    }
+    // The bounds are those of the transposed drawing: the boxes keep their width and height
+    let (min_x, min_y, max_x, max_y) = transposed.bounds;
+    for rect in place_nodes(&nodes, &transposed).iter().map(node_rect) {
+        assert!(rect.x >= min_x && rect.y >= min_y && rect.x + rect.width <= max_x && rect.y + rect.height <= max_y, "{:?}", rect);
+    }
+    assert_ne!(transposed.bounds, layout.bounds);
+
+    assert_eq!(transpose_layout(&nodes, &edges, &transposed), layout);
+}
+
+#[test]
+fn layout_diff_lists_moved_nodes_by_id() {
+    let layout = |positions: &[(&str, Point)]| LayoutResult {
+        positions: positions.iter().map(|&(id, position)| (id.to_string(), position)).collect(),
+        ..LayoutResult::default()
+    };
+    let before = layout(&[("c", (0.0, 0.0)), ("a", (1.0, 1.0)), ("still", (5.0, 5.0)), ("gone", (2.0, 2.0)), ("b", (3.0, 3.0))]);
+    let after = layout(&[("a", (1.0, 4.0)), ("b", (0.0, 3.0)), ("c", (2.0, -1.0)), ("still", (5.0, 5.0)), ("new", (9.0, 9.0))]);
+
+    assert_eq!(
+        diff_layouts(&before, &after),
+        [("a".to_string(), (0.0, 3.0)), ("b".to_string(), (-3.0, 0.0)), ("c".to_string(), (2.0, -1.0))]
+    );
+}
+
+#[test]
+fn reachability_follows_the_edges_up_and_downstream() {
+    let sorted = |ids: HashSet<String>| {
+        let mut ids: Vec<String> = ids.into_iter().collect();
+        ids.sort();
+        ids
+    };
+
+    let (nodes, edges) = graph(&["a", "b", "c", "d"], &[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d")]);
+    assert_eq!(sorted(ancestors(&nodes, &edges, "d")), ["a", "b", "c"]);
+    assert_eq!(sorted(descendants(&nodes, &edges, "a")), ["b", "c", "d"]);
+    assert_eq!(sorted(descendants(&nodes, &edges, "b")), ["d"]);
+    assert!(ancestors(&nodes, &edges, "a").is_empty());
+
+    // The start belongs to the result only when it lies on a cycle
+    let (nodes, edges) = graph(&["x", "y", "z", "w"], &[("x", "y"), ("y", "z"), ("z", "x"), ("z", "w")]);
+    assert_eq!(sorted(descendants(&nodes, &edges, "x")), ["w", "x", "y", "z"]);
+    assert_eq!(sorted(ancestors(&nodes, &edges, "y")), ["x", "y", "z"]);
+    assert_eq!(sorted(ancestors(&nodes, &edges, "w")), ["x", "y", "z"]);
+}
+
+#[test]
+fn edge_runs_in_screen_direction_of_orientation() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["source", "sink"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges = vec![create_edge("source", "sink", &mut rng)];
+
+    for (orientation, expected) in [
+        (Orientation::TopDown, (0.0, 1.0)),
+        (Orientation::BottomUp, (0.0, -1.0)),
+        (Orientation::LeftRight, (1.0, 0.0)),
+        (Orientation::RightLeft, (-1.0, 0.0)),
+    ] {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+        let points = &layout.result.routes(&edges)[0].unwrap().points;
+        let (start, end) = (points[0], points[points.len() - 1]);
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        assert!(
+            (dx / length - expected.0).abs() < 1e-6 && (dy / length - expected.1).abs() < 1e-6,
+            "{:?}: edge runs along ({}, {})",
+            orientation,
+            dx,
+            dy
+        );
+    }
+}
+
+#[test]
+fn orthogonal_routes_bend_once_between_adjacent_ranks() {
+    let (mut nodes, mut edges) = sample_graph();
+    // a -> e spans two ranks, past b on the rank in between
+    let mut rng = StdRng::seed_from_u64(1);
+    nodes.push(create_node("e", "E", "X1-TEST-A1", "REFINED", &mut rng));
+    edges.extend([create_edge("b", "e", &mut rng), create_edge("a", "e", &mut rng)]);
+    let options = LayoutOptions { edge_routing: EdgeRouting::Orthogonal, ..LayoutOptions::default() };
+    for orientation in ORIENTATIONS {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &options).unwrap();
+        assert_eq!(layout.result.ranks["e"], layout.result.ranks["a"] + 2, "{:?}", orientation);
+        let placed = place_nodes(&nodes, &layout.result);
+        for (edge, path) in edges.iter().zip(layout.result.routes(&edges)) {
+            let points = &path.unwrap().points;
+            assert_eq!(path.unwrap().style, PathStyle::Straight);
+            for pair in points.windows(2) {
+                let axis_aligned = (pair[0].0 - pair[1].0).abs() < 1e-6 || (pair[0].1 - pair[1].1).abs() < 1e-6;
+                assert!(axis_aligned, "{:?}: diagonal segment {:?}", orientation, pair);
+            }
+            if edge.source != "a" || edge.target != "e" {
+                assert!(points.len() <= 3, "{:?}: {:?}", orientation, points);
+                continue;
+            }
+            // The long edge runs along the channel the layout keeps clear past the middle
+            // rank, so it cuts through no box
+            for node in placed.iter().filter(|node| node.id != edge.source && node.id != edge.target) {
+                let rect = node_rect(node);
+                for pair in points.windows(2) {
+                    assert!(!segment_crosses_rect(pair[0], pair[1], &rect), "{:?}: {:?} crosses '{}'", orientation, pair, node.id);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn channel_routes_take_a_lane_in_every_gap_they_cross() {
+    let (mut nodes, mut edges) = sample_graph();
+    // a -> e spans two ranks, past b on the rank in between
+    let mut rng = StdRng::seed_from_u64(1);
+    nodes.push(create_node("e", "E", "X1-TEST-A1", "REFINED", &mut rng));
+    edges.extend([create_edge("b", "e", &mut rng), create_edge("a", "e", &mut rng)]);
+    let options = LayoutOptions { edge_routing: EdgeRouting::Channels, ..LayoutOptions::default() };
+    for orientation in ORIENTATIONS {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &options).unwrap();
+        assert_eq!(layout.result.ranks["e"], layout.result.ranks["a"] + 2, "{:?}", orientation);
+        let placed = place_nodes(&nodes, &layout.result);
+        let path = layout.result.routes(&edges)[4].unwrap();
+        assert_eq!(path.style, PathStyle::Straight);
+        // Into a lane, across, into the next lane, across and into the target
+        assert_eq!(path.points.len(), 6, "{:?}: {:?}", orientation, path.points);
+        for node in placed.iter().filter(|node| node.id != "a" && node.id != "e") {
+            let rect = node_rect(node);
+            for pair in path.points.windows(2) {
+                assert!(!segment_crosses_rect(pair[0], pair[1], &rect), "{:?}: {:?} crosses '{}'", orientation, pair, node.id);
+            }
+        }
+    }
+}
+
+#[test]
+fn weights_pull_nodes_toward_their_heavy_edges() {
+    // y may sit on rank 1 or 2 between a and the end of a -> b -> c -> d
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["a", "b", "c", "d", "y"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges: Vec<TechEdge> = [("a", "b"), ("b", "c"), ("c", "d"), ("a", "y"), ("y", "d")]
+        .iter()
+        .map(|(source, target)| create_edge(source, target, &mut rng))
+        .collect();
+    let into_y = WeightBy::Custom(|edge| if edge.target == "y" { 1000 } else { 1 });
+    let out_of_y = WeightBy::Custom(|edge| if edge.source == "y" { 1000 } else { 1 });
+    for (weight_by, rank) in [(into_y, 1), (out_of_y, 2)] {
+        let options = LayoutOptions { weight_by: Some(weight_by), ..LayoutOptions::default() };
+        let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &options).unwrap();
+        let ranks = &layout.result.ranks;
+        assert_eq!((ranks["b"], ranks["c"], ranks["d"]), (ranks["a"] + 1, ranks["a"] + 2, ranks["a"] + 3));
+        assert_eq!(ranks["y"], ranks["a"] + rank, "{:?}", weight_by);
+    }
+}
+
+#[test]
+fn relative_paths_resolve_to_the_absolute_points() {
+    let numbers = |d: &str| -> Vec<f64> {
+        d.replace(|c: char| c.is_ascii_alphabetic() || c == ',', " ").split_whitespace().map(|n| n.parse().unwrap()).collect()
+    };
+    // Deltas of 37.337 round the same way every time, which used to add up
+    let points: Vec<Point> = (0..20).map(|i| (i as f64 * 37.337 + 3.0, (i as f64 * 1.7).sin() * 50.0 + 100.0)).collect();
+
+    let absolute: Vec<f64> = points.iter().flat_map(|&(x, y)| [x, y]).collect();
+    let relative = numbers(&relative_path_data(&points, PathStyle::Straight));
+    let mut current = (0.0, 0.0);
+    let resolved: Vec<f64> = relative
+        .chunks(2)
+        .flat_map(|delta| {
+            current = (current.0 + delta[0], current.1 + delta[1]);
+            [current.0, current.1]
+        })
+        .collect();
+    for (resolved, absolute) in resolved.iter().zip(&absolute) {
+        assert!((resolved - absolute).abs() <= 0.005 + 1e-9, "{} drifted from {}", resolved, absolute);
+    }
+
+    // Control and end points of `c` are relative to the start of their segment
+    let absolute: Vec<f64> =
+        catmull_rom_segments(&points).iter().flat_map(|&(a, b, end)| [a.0, a.1, b.0, b.1, end.0, end.1]).collect();
+    let relative = numbers(&relative_path_data(&points, PathStyle::Curved));
+    let mut start = (relative[0], relative[1]);
+    let mut resolved = Vec::new();
+    for segment in relative[2..].chunks(6) {
+        resolved.extend(segment.chunks(2).flat_map(|delta| [start.0 + delta[0], start.1 + delta[1]]));
+        start = (start.0 + segment[4], start.1 + segment[5]);
+    }
+    assert_eq!(resolved.len(), absolute.len());
+    for (resolved, absolute) in resolved.iter().zip(&absolute) {
+        assert!((resolved - absolute).abs() <= 0.005 + 1e-9, "{} drifted from {}", resolved, absolute);
+    }
+}
+
+#[test]
+fn simplification_keeps_only_corners_beyond_the_tolerance() {
+    let collinear = [(0.0, 0.0), (10.0, 10.0), (25.0, 25.0), (40.0, 40.0)];
+    assert_eq!(simplify_polyline(&collinear, 0.5), [(0.0, 0.0), (40.0, 40.0)]);
+
+    // The corner at (100, 0) lies 50 off the diagonal, the jitter at (50, 1) within it
+    let corner = [(0.0, 0.0), (50.0, 1.0), (100.0, 0.0), (100.0, 100.0)];
+    assert_eq!(simplify_polyline(&corner, 5.0), [(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)]);
+    assert_eq!(simplify_polyline(&corner, 100.0), [(0.0, 0.0), (100.0, 100.0)]);
+}
+
+#[test]
+fn curves_pass_through_their_waypoints() {
+    let (start, end) = ((0.0, 0.0), (300.0, 600.0));
+    for waypoints in [vec![(200.0, 300.0)], vec![(200.0, 150.0), (-100.0, 300.0), (250.0, 450.0)]] {
+        let drawn = drawn_path(&curve_through_waypoints(start, waypoints.clone(), end), PathStyle::Curved);
+        assert_eq!((drawn[0], drawn[drawn.len() - 1]), (start, end));
+        for waypoint in waypoints {
+            let distance = drawn.iter().map(|p| (p.0 - waypoint.0).hypot(p.1 - waypoint.1)).fold(f64::INFINITY, f64::min);
+            assert!(distance < 1e-6, "{:?} misses {:?}", drawn, waypoint);
+        }
+    }
+
+    // A two-rank edge bends through the waypoint of the rank it skips
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["a", "b", "c"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges = vec![create_edge("a", "b", &mut rng), create_edge("b", "c", &mut rng), create_edge("a", "c", &mut rng)];
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let path = layout.result.routes(&edges)[2].unwrap();
+    let drawn = drawn_path(&path.points, path.style);
+    let b = node_rect(&place_nodes(&nodes, &layout.result)[1]);
+    for segment in drawn.windows(2) {
+        assert!(!segment_crosses_rect(segment[0], segment[1], &b), "{:?} crosses {:?}", segment, b);
+    }
+
+    // Opting out leaves the single control point
+    let options = LayoutOptions { straight_long_edges: true, ..LayoutOptions::default() };
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &options).unwrap();
+    assert_eq!(layout.result.routes(&edges)[2].unwrap().points.len(), 3);
+}
+
+#[test]
+fn ports_spread_edges_over_the_faces_toward_the_ranks() {
+    let (nodes, edges) = sample_graph();
+    let options = LayoutOptions { ports: true, ..LayoutOptions::default() };
+    for orientation in ORIENTATIONS {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &options).unwrap();
+        let placed = place_nodes(&nodes, &layout.result);
+        let node = |id: &str| placed.iter().find(|node| node.id == id).unwrap();
+        let exit = Side::forward(orientation);
+        let mut ends: Vec<(&str, Point)> = Vec::new();
+        for (edge, path) in edges.iter().zip(layout.result.routes(&edges)) {
+            let points = &path.unwrap().points;
+            let (start, end) = (points[0], *points.last().unwrap());
+            assert!(on_face(start, node(&edge.source), exit), "{:?}: {:?} leaves {}", orientation, start, edge.source);
+            assert!(on_face(end, node(&edge.target), exit.opposite()), "{:?}: {:?} enters {}", orientation, end, edge.target);
+            ends.extend([(edge.source.as_str(), start), (edge.target.as_str(), end)]);
+        }
+        // a has two outgoing edges and b two incoming ones, each on their own port
+        for (i, (id, point)) in ends.iter().enumerate() {
+            assert!(ends[i + 1..].iter().all(|(other, other_point)| other != id || other_point != point), "{:?}: shared port on {}", orientation, id);
+        }
+    }
+}
+
+#[test]
+fn layout_result_leaves_the_graph_untouched_and_bounds_the_labels() {
+    let (nodes, mut edges) = sample_graph();
+    // A parallel edge, which needs a route of its own
+    edges.push(edges[0].clone());
+    let before = serde_json::to_value((&nodes, &edges)).unwrap();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    assert_eq!(serde_json::to_value((&nodes, &edges)).unwrap(), before);
+
+    let result = &layout.result;
+    assert_eq!(result.positions.len(), nodes.len());
+    assert_eq!(result.edge_paths[&("a".to_string(), "b".to_string())].len(), 2);
+    let (min_x, min_y, max_x, max_y) = result.bounds;
+    let contains = |x: f64, y: f64| min_x <= x && x <= max_x && min_y <= y && y <= max_y;
+    for (id, &(x, y)) in &result.positions {
+        assert!(contains(x, y), "{} outside the bounds", id);
+    }
+    let placed = place_nodes(&nodes, result);
+    for edge in place_edges(&edges, result) {
+        let (_, label) = edge_label_position(&edge, edge.points.as_ref().unwrap(), &placed, LabelPlacement::default()).unwrap();
+        assert!(contains(label.x, label.y) && contains(label.x + label.width, label.y + label.height), "{:?} outside the bounds", label);
+    }
+}
+
+#[test]
+fn canvas_contains_labels_and_control_points_beyond_the_nodes() {
+    let (mut nodes, mut edges) = sample_graph();
+    nodes.truncate(2);
+    edges.truncate(1);
+    // b on top of a, with the edge bulging out far to the right
+    for (node, y) in nodes.iter_mut().zip([150.0, 0.0]) {
+        (node.x, node.y) = (Some(0.0), Some(y));
+    }
+    edges[0].points = Some(vec![(0.0, 150.0), (400.0, 75.0), (0.0, 0.0)]);
+
+    let layout = LayoutResult::last_known(&nodes, &edges);
+    let (svg, transform) = output_svg_with_transform(&nodes, &edges, &layout, &RenderConfig::default());
+    let start = svg.find("viewBox=\"0 0 ").unwrap() + "viewBox=\"0 0 ".len();
+    let view_box: Vec<f64> = svg[start..start + svg[start..].find('"').unwrap()]
+        .split(' ')
+        .map(|size| size.parse().unwrap())
+        .collect();
+    let (width, height) = (view_box[0], view_box[1]);
+
+    let placed = place_nodes(&nodes, &layout);
+    let target = &placed[1];
+    let points = edges[0].points.as_ref().unwrap();
+    let (anchor, direction) = edge_arrow_tip(target, points).unwrap();
+    let label = edge_label_rect(anchor, direction, target).unwrap();
+    assert!(label.x + label.width > target.x.unwrap() + target.width / 2.0, "label should lie beyond the node");
+    for corner in [(label.x, label.y), (label.x + label.width, label.y + label.height), (400.0, 75.0)] {
+        let (x, y) = transform.apply(corner);
+        assert!((0.0..=width).contains(&x) && (0.0..=height).contains(&y), "{:?} outside {}x{}", (x, y), width, height);
+    }
+}
+
+#[test]
+fn svg_rects_follow_the_label_placement() {
+    let (mut nodes, mut edges) = sample_graph();
+    nodes.truncate(2);
+    edges.truncate(1);
+    // b on top of a, with the edge bulging out to the left just as far as the nodes, so
+    // only the labels at either end reach beyond them
+    for (node, y) in nodes.iter_mut().zip([150.0, 0.0]) {
+        (node.x, node.y) = (Some(0.0), Some(y));
+    }
+    edges[0].points = Some(vec![(0.0, 150.0), (-100.0, 75.0), (0.0, 0.0)]);
+    let layout = LayoutResult::last_known(&nodes, &edges);
+    let placed = place_nodes(&nodes, &layout);
+    let default_rects = svg_node_rects(&nodes, &edges, &layout, &RenderConfig::default());
+
+    let mut lefts = Vec::new();
+    for label_placement in [LabelPlacement::Source, LabelPlacement::Midpoint, LabelPlacement::Target] {
+        let config = RenderConfig { label_placement, ..RenderConfig::default() };
+        let (_, transform) = output_svg_with_transform(&nodes, &edges, &layout, &config);
+        let rects = svg_node_rects(&nodes, &edges, &layout, &config);
+        for (node, (id, rect)) in placed.iter().zip(&rects) {
+            let (x, y) = transform.apply((node.x.unwrap() - node.width / 2.0, node.y.unwrap() - node.height / 2.0));
+            assert_eq!(id, &node.id);
+            assert!((rect.x - x).abs() < 1e-6 && (rect.y - y).abs() < 1e-6, "{:?}: {:?} drawn at ({}, {})", label_placement, rect, x, y);
+        }
+        let (_, edge_rect) = &svg_edge_rects(&nodes, &edges, &layout, &config)[0];
+        let (x, y) = transform.apply((-100.0, 0.0));
+        assert!((edge_rect.x - x).abs() < 1e-6 && (edge_rect.y - y).abs() < 1e-6, "{:?}: {:?} drawn at ({}, {})", label_placement, edge_rect, x, y);
+        if label_placement == LabelPlacement::Target {
+            assert_eq!(rects, default_rects);
+        }
+        lefts.push(rects[0].1.x);
+    }
+    // Labels at the ends widen the canvas to the left, the one in the middle doesn't
+    assert!(lefts[1] < lefts[0] && lefts[0] == lefts[2], "{:?}", lefts);
+}
+
+#[test]
+fn edges_avoid_obstacles_where_the_svg_draws_them() {
+    let (mut nodes, mut edges) = sample_graph();
+    nodes.truncate(2);
+    edges.truncate(1);
+    // b on top of a, the edge bulging out to the left as the leftmost part of the drawing
+    for (node, y) in nodes.iter_mut().zip([150.0, 0.0]) {
+        (node.x, node.y) = (Some(0.0), Some(y));
+    }
+    edges[0].points = Some(vec![(0.0, 150.0), (-200.0, 75.0), (0.0, 0.0)]);
+    let layout = LayoutResult::last_known(&nodes, &edges);
+    let config = RenderConfig::default();
+    let (_, transform) = output_svg_with_transform(&nodes, &edges, &layout, &config);
+    // On the bulge. The straight edge that avoids it shrinks the drawing to the nodes, which
+    // moves the SVG origin and with it the obstacle onto the straight edge.
+    let (x, y) = transform.apply((-100.0, 75.0));
+    let obstacle = Rect { x: x - 20.0, y: y - 20.0, width: 40.0, height: 40.0 };
+
+    let placed = place_nodes(&nodes, &layout);
+    let mut routed = place_edges(&edges, &layout);
+    route_around_svg_obstacles(&placed, &mut routed, &[obstacle], config.label_placement);
+
+    let routed = LayoutResult::new(&placed, &routed);
+    let (_, transform) = output_svg_with_transform(&nodes, &edges, &routed, &config);
+    let path = routed.routes(&edges)[0].unwrap();
+    let drawn: Vec<Point> = drawn_path(&path.points, path.style).into_iter().map(|point| transform.apply(point)).collect();
+    for segment in drawn.windows(2) {
+        assert!(!segment_crosses_rect(segment[0], segment[1], &obstacle), "{:?} crosses {:?}", segment, obstacle);
+    }
+}
+
+#[test]
+fn layout_routes_edges_around_the_obstacles_of_its_options() {
+    let (nodes, edges) = graph(&["a", "b"], &[("a", "b")]);
+    let config = RenderConfig::default();
+    let drawn_route = |options: &LayoutOptions| {
+        let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, options).unwrap();
+        let (_, transform) = output_svg_with_transform(&nodes, &edges, &layout.result, &config);
+        let route = layout.result.routes(&edges)[0].unwrap();
+        drawn_path(&route.points, route.style).into_iter().map(|point| transform.apply(point)).collect::<Vec<Point>>()
+    };
+    // Halfway along the edge as drawn without obstacles
+    let straight = drawn_route(&LayoutOptions::default());
+    let (x, y) = straight[straight.len() / 2];
+    let obstacle = Rect { x: x - 20.0, y: y - 20.0, width: 40.0, height: 40.0 };
+
+    let detoured = drawn_route(&LayoutOptions { obstacles: vec![obstacle], ..LayoutOptions::default() });
+    for segment in detoured.windows(2) {
+        assert!(!segment_crosses_rect(segment[0], segment[1], &obstacle), "{:?} crosses {:?}", segment, obstacle);
+    }
+}
+
+#[test]
+fn same_rank_groups_share_a_rank_without_overlapping() {
+    // d would otherwise rank right in front of c, one rank behind a
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let edges = vec![create_edge("a", "b", &mut rng), create_edge("b", "c", &mut rng), create_edge("d", "c", &mut rng)];
+
+    let options = LayoutOptions { same_rank: vec![vec!["a".to_string(), "d".to_string()]], ..LayoutOptions::default() };
+    for orientation in ORIENTATIONS {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &options).unwrap();
+        assert_eq!(layout.result.ranks["a"], layout.result.ranks["d"], "{:?}", orientation);
+        let placed = place_nodes(&nodes, &layout.result);
+        for (i, first) in placed.iter().enumerate() {
+            for second in &placed[i + 1..] {
+                let (a, b) = (node_rect(first), node_rect(second));
+                let overlaps = a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height;
+                assert!(!overlaps, "{:?}: '{}' overlaps '{}'", orientation, first.id, second.id);
+            }
+        }
+    }
+
+    let options = LayoutOptions { same_rank: vec![vec!["c".to_string(), "a".to_string()]], ..LayoutOptions::default() };
+    let result = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &options);
+    assert!(matches!(result, Err(LayoutError::SameRankContradicted { ref source, ref target }) if source == "a" && target == "c"));
+}
+
+#[test]
+fn same_rank_groups_sharing_a_rank_keep_apart() {
+    // Two groups of sources feeding the same sink, both on the first rank. Some members are
+    // far larger than the vertex spacing, so only their sizes keep them apart.
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut nodes: Vec<TechNode> = ["a", "b", "c", "d", "e", "x"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    (nodes[2].width, nodes[2].height) = (900.0, 500.0);
+    let edges: Vec<TechEdge> = ["a", "b", "c", "d", "e"].iter().map(|id| create_edge(id, "x", &mut rng)).collect();
+    let groups = vec![vec!["a".to_string(), "b".to_string()], vec!["c".to_string(), "d".to_string(), "e".to_string()]];
+    let options = LayoutOptions { same_rank: groups, vertex_spacing: Some(150), ..LayoutOptions::default() };
+
+    for orientation in ORIENTATIONS {
+        let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &options).unwrap();
+        assert_eq!(layout.result.ranks["a"], layout.result.ranks["c"], "{:?}", orientation);
+        let placed = place_nodes(&nodes, &layout.result);
+        for (i, first) in placed.iter().enumerate() {
+            for second in &placed[i + 1..] {
+                let (a, b) = (node_rect(first), node_rect(second));
+                let overlaps = a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height;
+                assert!(!overlaps, "{:?}: '{}' overlaps '{}'", orientation, first.id, second.id);
+            }
+        }
+
+        // Members of a group follow each other `GROUP_GAP` apart across the rank
+        let axis = rank_coordinates(&placed).unwrap().0;
+        let span = |node: &PlacedNode| {
+            let rect = node_rect(node);
+            match axis {
+                RankAxis::X => (rect.y, rect.y + rect.height),
+                RankAxis::Y => (rect.x, rect.x + rect.width),
+            }
+        };
+        let mut members: Vec<(f64, f64)> = placed[2..5].iter().map(span).collect();
+        members.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for pair in members.windows(2) {
+            assert!((pair[1].0 - pair[0].1 - GROUP_GAP).abs() < 1e-6, "{:?}: {:?}", orientation, members);
+        }
+    }
+}
+
+#[test]
+fn heavier_edges_are_drawn_shorter() {
+    // x has one neighbour on either side of it, a heavy and a light one
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["a", "b", "x"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let mut edges = vec![create_edge("a", "x", &mut rng), create_edge("b", "x", &mut rng)];
+    edges[0].volume = 1000;
+    edges[1].volume = 1;
+
+    let length = |layout: &SupplyChainLayout, source: &str| {
+        let (from, to) = (layout.result.positions[source], layout.result.positions["x"]);
+        (to.0 - from.0).hypot(to.1 - from.1)
+    };
+    // The same weights computed by the caller, the displayed cost left alone
+    let by_source = WeightBy::Custom(|edge| if edge.source == "a" { 1000 } else { 1 });
+    for orientation in ORIENTATIONS {
+        let unweighted = LayoutOptions { minimize_edge_length: true, ..LayoutOptions::default() };
+        let unweighted = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &unweighted).unwrap();
+        for weight_by in [WeightBy::Volume, by_source] {
+            let weighted = LayoutOptions { minimize_edge_length: true, weight_by: Some(weight_by), ..LayoutOptions::default() };
+            let weighted = build_supply_chain_layout(&nodes, &edges, orientation, 1.5, 0.75, &weighted).unwrap();
+            assert!(
+                length(&weighted, "a") < length(&unweighted, "a") - 1.0,
+                "{:?} by {:?}: {} vs {}",
+                orientation,
+                weight_by,
+                length(&weighted, "a"),
+                length(&unweighted, "a")
+            );
+            assert!(length(&weighted, "b") > length(&unweighted, "b"), "{:?} by {:?}", orientation, weight_by);
+        }
+    }
+}
+
+#[test]
+fn weighted_ordering_crosses_light_edges_rather_than_heavy_ones() {
+    // a and b above c and d, the heavy edges a -> d and b -> c crossing
+    let mut rng = StdRng::seed_from_u64(0);
+    let nodes: Vec<TechNode> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|id| create_node(id, &id.to_uppercase(), "X1-TEST-A1", "REFINED", &mut rng))
+        .collect();
+    let mut edges = vec![
+        create_edge("a", "d", &mut rng),
+        create_edge("b", "c", &mut rng),
+        create_edge("a", "c", &mut rng),
+        create_edge("b", "d", &mut rng),
+    ];
+    for (edge, volume) in edges.iter_mut().zip([10, 10, 1, 1]) {
+        edge.volume = volume;
+    }
+    let placed = || -> Vec<PlacedNode> {
+        nodes
+            .iter()
+            .zip([(0, 0.0), (0, 300.0), (1, 0.0), (1, 300.0)])
+            .map(|(node, (rank, x))| PlacedNode { node, rank: Some(rank), x: Some(x), y: Some(rank as f64 * 200.0) })
+            .collect()
+    };
+
+    // Unit weights tie, one crossing either way
+    let mut unweighted = placed();
+    reduce_weighted_crossings(&mut unweighted, &edges, RankAxis::Y, WeightBy::Custom(|_| 1), nodes.len(), 0.0);
+    assert_eq!(unweighted.iter().map(|node| node.x.unwrap()).collect::<Vec<_>>(), [0.0, 300.0, 0.0, 300.0]);
+
+    let mut weighted = placed();
+    reduce_weighted_crossings(&mut weighted, &edges, RankAxis::Y, WeightBy::Volume, nodes.len(), 0.0);
+    let x = |id: &str| weighted.iter().find(|node| node.id == id).unwrap().x.unwrap();
+    assert!((x("a") - x("b")) * (x("d") - x("c")) > 0.0, "heavy edges still cross");
+    assert_eq!(count_crossings(&weighted, &edges), 1);
+    // No sweeps leave the order as it was
+    let mut capped = placed();
+    reduce_weighted_crossings(&mut capped, &edges, RankAxis::Y, WeightBy::Volume, 0, 0.0);
+    assert_eq!(capped.iter().map(|node| node.x.unwrap()).collect::<Vec<_>>(), [0.0, 300.0, 0.0, 300.0]);
+    // Each rank keeps the span it covered
+    let span = |nodes: &[PlacedNode], rank: usize| {
+        let members = nodes.iter().filter(|node| node.rank == Some(rank));
+        let start = members.clone().map(|node| node.x.unwrap() - node.width / 2.0).fold(f64::INFINITY, f64::min);
+        let end = members.map(|node| node.x.unwrap() + node.width / 2.0).fold(f64::NEG_INFINITY, f64::max);
+        (start, end)
+    };
+    for rank in 0..2 {
+        assert_eq!(span(&weighted, rank), span(&placed(), rank));
+    }
+}
+
+#[test]
+fn straightened_chain_runs_on_the_line_between_its_ends() {
+    let (nodes, edges) = graph(&["ore", "refined", "product"], &[("ore", "refined"), ("refined", "product")]);
+    let mut placed: Vec<PlacedNode> = nodes
+        .iter()
+        .zip([(0.0, 0.0), (300.0, 400.0), (600.0, 200.0)])
+        .enumerate()
+        .map(|(rank, (node, (x, y)))| PlacedNode { node, rank: Some(rank), x: Some(x), y: Some(y) })
+        .collect();
+
+    straighten_chains(&mut placed, &edges, RankAxis::X);
+
+    // Halfway along the rank axis, halfway across between the ends
+    assert_eq!((placed[1].x, placed[1].y), (Some(300.0), Some(100.0)));
+    assert_eq!((placed[0].y, placed[2].y), (Some(0.0), Some(200.0)));
+}
+
+#[test]
+fn transitive_reduction_drops_only_implied_edges() {
+    // a→c is implied by a→b→c; c→a closes a cycle
+    let (nodes, edges) = graph(&["a", "b", "c"], &[("a", "b"), ("b", "c"), ("a", "c"), ("a", "b"), ("c", "a")]);
+    let (reduced_nodes, reduced_edges) = transitive_reduction(&nodes, &edges);
+
+    assert_eq!(reduced_nodes.len(), 3);
+    let pairs: Vec<(&str, &str)> = reduced_edges.iter().map(|edge| (edge.source.as_str(), edge.target.as_str())).collect();
+    assert_eq!(pairs, [("a", "b"), ("b", "c"), ("a", "b"), ("c", "a")]);
+}
+
+#[test]
+fn collapsed_group_merges_the_edges_of_its_members() {
+    let (nodes, edges) =
+        graph(&["a", "b", "c", "d"], &[("a", "b"), ("a", "c"), ("b", "c"), ("b", "d"), ("c", "d"), ("a", "d")]);
+    let group = NodeGroup { id: "g".to_string(), name: "G".to_string(), members: vec!["b".to_string(), "c".to_string()], collapsed: true };
+    let (collapsed_nodes, collapsed_edges) = collapse_groups(&nodes, &edges, std::slice::from_ref(&group)).unwrap();
+
+    let ids: Vec<&str> = collapsed_nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["a", "g", "d"]);
+    // b→c stays inside the group, a→b and a→c as well as b→d and c→d merge
+    let pairs: Vec<(&str, &str, u32)> =
+        collapsed_edges.iter().map(|edge| (edge.source.as_str(), edge.target.as_str(), edge.volume)).collect();
+    assert_eq!(
+        pairs,
+        [("a", "g", edges[0].volume + edges[1].volume), ("g", "d", edges[3].volume + edges[4].volume), ("a", "d", edges[5].volume)]
+    );
+
+    let taken = NodeGroup { id: "d".to_string(), ..group };
+    let options = LayoutOptions { groups: vec![taken], ..LayoutOptions::default() };
+    let result = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options);
+    assert_eq!(result.err(), Some(LayoutError::GroupIdTaken("d".to_string())));
+}
+
+#[test]
+fn initial_order_decides_between_equal_siblings() {
+    let (mut nodes, edges) = graph(&["a", "b", "c"], &[("a", "b"), ("a", "c")]);
+    let options = LayoutOptions { initial_order: Some(|node| node.cost as i64), ..LayoutOptions::default() };
+    let mut cross = |costs: (u32, u32)| {
+        (nodes[1].cost, nodes[2].cost) = costs;
+        let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options).unwrap();
+        (layout.result.positions["b"].1, layout.result.positions["c"].1)
+    };
+
+    let (b, c) = cross((100, 200));
+    assert!(b < c, "b at {}, c at {}", b, c);
+    let (b, c) = cross((200, 100));
+    assert!(c < b, "b at {}, c at {}", b, c);
+}
+
+#[test]
+fn edges_to_unpositioned_nodes_are_reported() {
+    let (mut nodes, _) = graph(&["placed", "known", "lost"], &[]);
+    (nodes[1].x, nodes[1].y) = (Some(10.0), Some(20.0));
+    let placed: Vec<PlacedNode> = nodes.iter().map(|node| PlacedNode { node, rank: None, x: node.x, y: node.y }).collect();
+    let node_positions: HashMap<String, usize> = nodes.iter().enumerate().map(|(i, node)| (node.id.clone(), i)).collect();
+    let positioned = HashSet::new();
+    let endpoint = |mode, id| routing_endpoint(&placed, &node_positions, &positioned, mode, id);
+
+    assert_eq!(endpoint(UnpositionedEdges::UseLastKnown, "known"), Ok((10.0, 20.0)));
+    assert_eq!(endpoint(UnpositionedEdges::Report, "known"), Err(UnroutedReason::MissingPosition("known".to_string())));
+    for mode in [UnpositionedEdges::UseLastKnown, UnpositionedEdges::Report] {
+        assert_eq!(endpoint(mode, "lost"), Err(UnroutedReason::MissingPosition("lost".to_string())));
+        assert_eq!(endpoint(mode, "gone"), Err(UnroutedReason::UnknownNode("gone".to_string())));
+    }
+}
+
+#[test]
+fn fitted_gaps_hold_the_edge_labels() {
+    let (nodes, edges) = sample_graph();
+    for (orientation, label_extent) in [(Orientation::LeftRight, EDGE_LABEL_WIDTH), (Orientation::TopDown, EDGE_LABEL_HEIGHT)] {
+        for placement in [LabelPlacement::Source, LabelPlacement::Midpoint, LabelPlacement::Target] {
+            let gap = |fit_edge_labels: bool| {
+                let options =
+                    LayoutOptions { vertex_spacing: Some(1), fit_edge_labels, label_placement: placement, ..LayoutOptions::default() };
+                let layout = build_supply_chain_layout(&nodes, &edges, orientation, 1.0, 1.0, &options).unwrap();
+                let placed = place_nodes(&nodes, &layout.result);
+                let (a, b) = (&placed[0], &placed[1]);
+                match orientation {
+                    Orientation::LeftRight => (b.x.unwrap() - a.x.unwrap()) - (a.width + b.width) / 2.0,
+                    _ => (b.y.unwrap() - a.y.unwrap()) - (a.height + b.height) / 2.0,
+                }
+            };
+            assert!(gap(false) < label_extent, "{:?}: {}", orientation, gap(false));
+            assert!(gap(true) >= label_extent, "{:?} {:?}: {}", orientation, placement, gap(true));
+        }
+    }
+}
+
+// Whether `point` lies on the `side` face of `node`
+fn on_face(point: Point, node: &PlacedNode, side: Side) -> bool {
+    let (x, y) = side.port(node);
+    match side {
+        Side::Left | Side::Right => (point.0 - x).abs() < 1e-6 && (point.1 - y).abs() <= node.height / 2.0,
+        Side::Top | Side::Bottom => (point.1 - y).abs() < 1e-6 && (point.0 - x).abs() <= node.width / 2.0,
+    }
+}
+
+#[test]
+fn edges_enter_by_the_preferred_side() {
+    // Top down, incoming edges would enter c from above
+    let (nodes, edges) = graph(&["a", "b", "c"], &[("a", "c"), ("b", "c")]);
+    let options = LayoutOptions {
+        preferred_sides: Some(|node| match node.id.as_str() {
+            "c" => PreferredSides { entry: Some(Side::Left), exit: None },
+            _ => PreferredSides::default(),
+        }),
+        ..LayoutOptions::default()
+    };
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::TopDown, 1.5, 0.75, &options).unwrap();
+    let placed = place_nodes(&nodes, &layout.result);
+    for path in layout.result.routes(&edges) {
+        let end = *path.unwrap().points.last().unwrap();
+        assert!(on_face(end, &placed[2], Side::Left), "{:?} enters c elsewhere", end);
+    }
+}
+
+#[test]
+fn edges_leave_and_enter_by_opposite_sides() {
+    let (nodes, edges) = sample_graph();
+    for exit in [Side::Left, Side::Right, Side::Top, Side::Bottom] {
+        let options = LayoutOptions { preferred_sides: Some(sides_leaving_by(exit)), ..LayoutOptions::default() };
+        let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options).unwrap();
+        let placed = place_nodes(&nodes, &layout.result);
+        let node = |id: &str| placed.iter().find(|node| node.id == id).unwrap();
+        for (edge, path) in edges.iter().zip(layout.result.routes(&edges)) {
+            let points = &path.unwrap().points;
+            assert!(on_face(points[0], node(&edge.source), exit), "{:?}: {:?}", exit, points);
+            assert!(on_face(*points.last().unwrap(), node(&edge.target), exit.opposite()), "{:?}: {:?}", exit, points);
+        }
+    }
+}
+
+#[test]
+fn contradicting_preferred_sides_still_route_the_edge() {
+    // Left to right, a leaves backwards and b is entered from behind
+    let (nodes, edges) = graph(&["a", "b"], &[("a", "b")]);
+    let options = LayoutOptions {
+        preferred_sides: Some(|node| match node.id.as_str() {
+            "a" => PreferredSides { entry: None, exit: Some(Side::Left) },
+            _ => PreferredSides { entry: Some(Side::Right), exit: None },
+        }),
+        ..LayoutOptions::default()
+    };
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options).unwrap();
+    assert!(layout.unrouted_edges.is_empty());
+    let placed = place_nodes(&nodes, &layout.result);
+    let points = &layout.result.routes(&edges)[0].unwrap().points;
+    assert!(points.iter().all(|(x, y)| x.is_finite() && y.is_finite()), "{:?}", points);
+    assert!(on_face(points[0], &placed[0], Side::Left), "{:?}", points);
+    assert!(on_face(*points.last().unwrap(), &placed[1], Side::Right), "{:?}", points);
+}
+
+// Waypoint types of the order in `rank_constraints`
+fn by_waypoint_type(order: &[&str]) -> LayoutOptions {
+    LayoutOptions {
+        rank_constraints: Some(RankConstraints {
+            category: |node| node.waypoint_type.clone(),
+            order: order.iter().map(|category| category.to_string()).collect(),
+        }),
+        ..LayoutOptions::default()
+    }
+}
+
+#[test]
+fn rank_constraints_order_the_categories() {
+    // Without the order, ore and metal would both start on the first rank
+    let (mut nodes, edges) = graph(&["ore", "metal", "part"], &[("ore", "part"), ("metal", "part")]);
+    nodes[0].waypoint_type = "RAW_MATERIAL".to_string();
+    nodes[2].waypoint_type = "INDUSTRIAL".to_string();
+    let options = by_waypoint_type(&["RAW_MATERIAL", "REFINED", "INDUSTRIAL"]);
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options).unwrap();
+
+    let ranks: Vec<usize> = nodes.iter().map(|node| layout.result.ranks[&node.id]).collect();
+    assert_eq!(ranks, [0, 1, 2]);
+    // The edges enforcing the order are not part of the result
+    assert_eq!(layout.result.edge_paths.len(), 2);
+}
+
+#[test]
+fn rank_constraints_contradicted_by_an_edge_fail() {
+    let (mut nodes, edges) = graph(&["metal", "ore"], &[("metal", "ore")]);
+    nodes[1].waypoint_type = "RAW_MATERIAL".to_string();
+    let options = by_waypoint_type(&["RAW_MATERIAL", "REFINED"]);
+    let result = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &options);
+    assert_eq!(
+        result.err(),
+        Some(LayoutError::RankConstraintViolated { earlier: "ore".to_string(), later: "metal".to_string() })
+    );
+}
+
+#[test]
+fn leader_line_runs_from_the_label_to_its_anchor() {
+    let (_, edges) = graph(&["a", "b"], &[("a", "b")]);
+    let label = Rect { x: 50.0, y: 50.0, width: EDGE_LABEL_WIDTH, height: EDGE_LABEL_HEIGHT };
+    let config = RenderConfig { label_leaders: true, ..RenderConfig::default() };
+    let svg = generate_edge_label_svg((0.0, 0.0), label, &edges[0], "a to b", &config);
+    let leaders = elements(&svg, "line");
+
+    assert_eq!(leaders.len(), 1);
+    assert_eq!((number(&leaders[0], "x2"), number(&leaders[0], "y2")), (0.0, 0.0));
+    // It starts where the line from the center of the box to the anchor leaves the top face
+    let (x1, y1) = (number(&leaders[0], "x1"), number(&leaders[0], "y1"));
+    assert_eq!(y1, 50.0);
+    let (center_x, center_y) = (50.0 + EDGE_LABEL_WIDTH / 2.0, 50.0 + EDGE_LABEL_HEIGHT / 2.0);
+    assert!((x1 - center_x * 50.0 / center_y).abs() < 0.01, "{}", x1);
+
+    let inside = generate_edge_label_svg((60.0, 60.0), label, &edges[0], "a to b", &config);
+    assert!(elements(&inside, "line").is_empty());
+}
+
+#[test]
+fn label_box_follows_its_style() {
+    let (_, edges) = graph(&["a", "b"], &[("a", "b")]);
+    let label = Rect { x: 0.0, y: 0.0, width: EDGE_LABEL_WIDTH, height: EDGE_LABEL_HEIGHT };
+    let label_svg = |label_box: LabelBoxStyle| {
+        let config = RenderConfig { label_box, ..RenderConfig::default() };
+        generate_edge_label_svg((0.0, 0.0), label, &edges[0], "a to b", &config)
+    };
+
+    let translucent = label_svg(LabelBoxStyle { fill_opacity: 0.5, ..LabelBoxStyle::default() });
+    let boxes = elements(&translucent, "rect");
+    assert_eq!(boxes.len(), 1);
+    assert_eq!(boxes[0]["fill-opacity"], "0.5");
+    assert!(elements(&label_svg(LabelBoxStyle { background: false, ..LabelBoxStyle::default() }), "rect").is_empty());
+}
+
+#[test]
+fn label_color_resolvers_override_the_default_colors() {
+    let (_, edges) = graph(&["a", "b"], &[("a", "b")]);
+    let label = Rect { x: 0.0, y: 0.0, width: EDGE_LABEL_WIDTH, height: EDGE_LABEL_HEIGHT };
+    // Fill of the volume row, the second of the left column
+    let volume_fill = |config: &RenderConfig| {
+        let svg = generate_edge_label_svg((0.0, 0.0), label, &edges[0], "a to b", config);
+        elements(&svg, "tspan")[1]["fill"].to_string()
+    };
+
+    assert_eq!(volume_fill(&RenderConfig::default()), Theme::default().label_text.to_string());
+    let label_colors = EdgeLabelColors { volume: Some(|_| ColorString::from("#123456")), ..EdgeLabelColors::default() };
+    assert_eq!(volume_fill(&RenderConfig { label_colors, ..RenderConfig::default() }), "#123456");
+}
+
+#[test]
+fn profits_within_the_neutral_band_get_the_neutral_color() {
+    let (_, mut edges) = graph(&["a", "b"], &[("a", "b")]);
+    let label = Rect { x: 0.0, y: 0.0, width: EDGE_LABEL_WIDTH, height: EDGE_LABEL_HEIGHT };
+    let config = RenderConfig { profit_neutral_band: Some((-20, 20)), ..RenderConfig::default() };
+    let profit_colors = config.theme.profit.clone();
+    // Fill of the profit row, the last of the right column
+    let mut profit_fill = |profit: i32| {
+        edges[0].profit = Some(profit);
+        let svg = generate_edge_label_svg((0.0, 0.0), label, &edges[0], "a to b", &config);
+        elements(&svg, "tspan")[5]["fill"].to_string()
+    };
+
+    for (profit, color) in [
+        (-21, &profit_colors.unprofitable),
+        (-20, &profit_colors.neutral),
+        (20, &profit_colors.neutral),
+        (21, &profit_colors.profitable),
+    ] {
+        assert_eq!(profit_fill(profit), color.to_string(), "profit {}", profit);
+    }
+}
+
+#[test]
+fn merged_parallel_edges_share_one_label() {
+    let (nodes, mut edges) = graph(&["a", "b"], &[("a", "b"), ("a", "b"), ("a", "b"), ("a", "b")]);
+    for (volume, edge) in (1..).zip(&mut edges) {
+        edge.volume = volume;
+    }
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let labels = |config: &RenderConfig| output_svg(&nodes, &edges, &layout.result, config).matches("<!-- Label background -->").count();
+    assert_eq!(labels(&RenderConfig::default()), 4);
+
+    let config = RenderConfig { label_merge: Some(sum_parallel_edges), ..RenderConfig::default() };
+    assert_eq!(labels(&config), 1);
+    assert!(output_svg(&nodes, &edges, &layout.result, &config).contains(">v: 10<"));
+}
+
+#[test]
+fn parallel_edges_fan_out_with_separate_labels() {
+    let (nodes, mut edges) = sample_graph();
+    let mut rng = StdRng::seed_from_u64(1);
+    edges.push(create_edge("a", "b", &mut rng));
+    edges.push(create_edge("a", "b", &mut rng));
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+
+    let placed = place_nodes(&nodes, &layout.result);
+    let target = placed.iter().find(|node| node.id == "b").unwrap();
+    let placed_edges = place_edges(&edges, &layout.result);
+    let parallel: Vec<&PlacedEdge> = placed_edges.iter().filter(|edge| edge.source == "a" && edge.target == "b").collect();
+    assert_eq!(parallel.len(), 3);
+    let controls: Vec<Point> = parallel.iter().map(|edge| edge.points.as_ref().unwrap()[1]).collect();
+    let labels: Vec<Rect> = parallel
+        .iter()
+        .map(|edge| {
+            let (anchor, direction) = edge_arrow_tip(target, edge.points.as_ref().unwrap()).unwrap();
+            edge_label_rect(anchor, direction, target).unwrap()
+        })
+        .collect();
+    for i in 0..3 {
+        for j in i + 1..3 {
+            let (a, b) = (controls[i], controls[j]);
+            assert!((a.0 - b.0).abs() + (a.1 - b.1).abs() > 1.0, "arcs {} and {} coincide", i, j);
+            assert!((labels[i].x - labels[j].x).abs() + (labels[i].y - labels[j].y).abs() > 1.0, "labels {} and {} coincide", i, j);
+        }
+    }
+}
+
+#[test]
+fn edges_detour_around_nodes_in_their_way() {
+    let (mut nodes, mut edges) = sample_graph();
+    nodes.truncate(3);
+    // a, b and c in a row, the edge from a to c straight through b
+    for (node, x) in nodes.iter_mut().zip([0.0, 300.0, 600.0]) {
+        (node.x, node.y) = (Some(x), Some(0.0));
+    }
+    edges.truncate(2);
+    edges[1].points = Some(vec![(0.0, 0.0), (300.0, 0.0), (600.0, 0.0)]);
+
+    let layout = LayoutResult::last_known(&nodes, &edges);
+    let placed = place_nodes(&nodes, &layout);
+    let mut edges = place_edges(&edges, &layout);
+    detour_around_nodes(&placed, &mut edges);
+
+    let middle = node_rect(&placed[1]);
+    let points = edges[1].points.as_ref().unwrap();
+    assert!(points.len() > 2);
+    assert_eq!((points[0], points[points.len() - 1]), ((0.0, 0.0), (600.0, 0.0)));
+    for segment in drawn_path(points, edges[1].path_style).windows(2) {
+        assert!(!segment_crosses_rect(segment[0], segment[1], &middle), "{:?} crosses {:?}", segment, middle);
+    }
+    // The edge from a to b ends in b and stays as it was
+    assert_eq!(edges[0].path_style, PathStyle::Curved);
+}
+
+#[test]
+fn layout_routes_edges_around_nodes_when_asked_to() {
+    // The straight edge from a to c passes the box of b once the ranks are squeezed together
+    let (nodes, edges) = graph(&["a", "b", "c"], &[("a", "b"), ("b", "c"), ("a", "c")]);
+    let crossings = |avoid_nodes| {
+        let options = LayoutOptions { straight_long_edges: true, avoid_nodes, ..LayoutOptions::default() };
+        let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.4, &options).unwrap();
+        let b = place_nodes(&nodes, &layout.result).into_iter().find(|node| node.id == "b").map(|node| node_rect(&node)).unwrap();
+        let route = layout.result.routes(&edges)[2].unwrap();
+        drawn_path(&route.points, route.style).windows(2).filter(|segment| segment_crosses_rect(segment[0], segment[1], &b)).count()
+    };
+    assert!(crossings(false) > 0);
+    assert_eq!(crossings(true), 0);
+}
+
+#[test]
+fn document_ids_carry_the_prefix() {
+    let (nodes, edges) = sample_graph();
+    let layout = build_supply_chain_layout(&nodes, &edges, Orientation::LeftRight, 1.5, 0.75, &LayoutOptions::default()).unwrap();
+    let config = RenderConfig { accessibility: true, group_ranks: true, id_prefix: "first-".to_string(), ..RenderConfig::default() };
+    let svg = output_svg(&nodes, &edges, &layout.result, &config);
+
+    let ids: Vec<&str> = svg.split(r#" id=""#).skip(1).map(|rest| &rest[..rest.find('"').unwrap()]).collect();
+    assert!(ids.len() > 3);
+    assert!(ids.iter().all(|id| id.starts_with("first-")), "{:?}", ids);
+    for reference in svg.split("url(#").skip(1).map(|rest| &rest[..rest.find(')').unwrap()]) {
+        assert!(ids.contains(&reference), "{} is not defined", reference);
+    }
+    assert!(svg.contains(r#"aria-labelledby="first-diagram-title first-diagram-desc""#));
+}
+
+#[test]
+fn arrow_points_into_target_in_every_orientation() {
+    for orientation in ORIENTATIONS {
+        for_routed_edges(orientation, |target, points| {
+            let ((tip_x, tip_y), _) = edge_arrow_tip(target, points).unwrap();
+            let rect = node_rect(target);
+
+            // The tip sits on the target's border ...
+            let on_vertical = (tip_x - rect.x).abs() < 1e-6 || (tip_x - rect.x - rect.width).abs() < 1e-6;
+            let on_horizontal = (tip_y - rect.y).abs() < 1e-6 || (tip_y - rect.y - rect.height).abs() < 1e-6;
+            assert!(on_vertical || on_horizontal, "{:?}: tip ({}, {}) not on border of {:?}", orientation, tip_x, tip_y, rect);
+
+            // ... and the edge arrives there heading into the node
+            let (prev_x, prev_y) = points[points.len() - 2];
+            let (center_x, center_y) = (target.x.unwrap(), target.y.unwrap());
+            let heading = (tip_x - prev_x) * (center_x - tip_x) + (tip_y - prev_y) * (center_y - tip_y);
+            assert!(heading > 0.0, "{:?}: edge into '{}' points away from it", orientation, target.id);
+        });
+    }
+}
+
+#[test]
+fn edge_label_sits_outside_target_in_every_orientation() {
+    for orientation in ORIENTATIONS {
+        for_routed_edges(orientation, |target, points| {
+            let (anchor, direction) = edge_arrow_tip(target, points).unwrap();
+            let label = edge_label_rect(anchor, direction, target).unwrap();
+            let rect = node_rect(target);
+
+            let overlaps = label.x < rect.x + rect.width
+                && rect.x < label.x + label.width
+                && label.y < rect.y + rect.height
+                && rect.y < label.y + label.height;
+            assert!(!overlaps, "{:?}: label {:?} overlaps target {:?}", orientation, label, rect);
+
+            // The label lies on the side the edge comes from
+            let (label_center_x, label_center_y) = (label.x + label.width / 2.0, label.y + label.height / 2.0);
+            let outward = (label_center_x - target.x.unwrap()) * direction.0
+                + (label_center_y - target.y.unwrap()) * direction.1;
+            assert!(outward > 0.0, "{:?}: label of edge into '{}' is not outside", orientation, target.id);
+        });
+    }
+}
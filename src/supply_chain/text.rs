@@ -0,0 +1,310 @@
+//! Text escaping, wrapping and fitting nodes to their text
+
+use std::borrow::Cow;
+
+use super::*;
+
+/// Text with `& < > " '` replaced by entities, safe inside elements and attribute values.
+/// Borrows the input when there is nothing to replace.
+pub fn escape_xml(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// A utility function to generate SVG multiline text with varying colors
+/// Now with support for a font size multiplier for the first line
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn generate_multiline_text_svg(
+    x: f64,                              // X position (anchor point)
+    y: f64,                              // Y position (top of first line)
+    lines: &[(String, ColorString)],     // Text content and colors
+    text_anchor: &str,                   // "start", "middle", or "end"
+    font_family: &str,                   // Font family
+    font_size: u32,                      // Base font size
+    line_height: f64,                    // Space between lines
+    dominant_baseline: Option<&str>,     // Optional baseline alignment
+    first_line_size_multiplier: Option<f64>, // Optional font size multiplier for the first line
+    max_width: Option<f64>,              // Optional width lines are wrapped at
+) -> String {
+    let baseline_attr = if let Some(baseline) = dominant_baseline {
+        format!(" dominant-baseline=\"{}\"", baseline)
+    } else {
+        String::new()
+    };
+
+    let mut svg = format!(
+        r#"<text x="{:.2}" y="{:.2}" font-family="{}" font-size="{}"{} text-anchor="{}">"#,
+        x, y, escape_xml(font_family), font_size, baseline_attr, text_anchor
+    );
+
+    let rows = wrap_text_rows(lines, font_size as f64, first_line_size_multiplier, max_width);
+    for (i, (text, color, first_line)) in rows.iter().enumerate() {
+        // A larger first line pushes the next row down by the extra height it takes
+        let dy = row_advance(&rows, i, font_size as f64, line_height, first_line_size_multiplier);
+
+        // Apply font size multiplier to first line if specified
+        let font_size_attr = match first_line_size_multiplier {
+            Some(multiplier) if *first_line => {
+                let adjusted_size = (font_size as f64 * multiplier).round() as u32;
+                format!(" font-size=\"{}\"", adjusted_size)
+            }
+            _ => String::new(),
+        };
+
+        svg.push_str(&format!(
+            r#"<tspan x="{:.2}" dy="{:.2}"{} fill="{}">{}</tspan>"#,
+            x, dy, font_size_attr, color.0, escape_xml(text)
+        ));
+    }
+
+    svg.push_str("</text>");
+    svg
+}
+
+/// A row of wrapped text: its content, color and whether it belongs to the first line
+pub(crate) type TextRow<'a> = (String, &'a ColorString, bool);
+
+/// `lines` as rendered by `generate_multiline_text_svg`, with lines wider than `max_width`
+/// broken into several rows
+pub(crate) fn wrap_text_rows(
+    lines: &[(String, ColorString)],
+    font_size: f64,
+    first_line_size_multiplier: Option<f64>,
+    max_width: Option<f64>,
+) -> Vec<TextRow<'_>> {
+    let mut rows = Vec::new();
+    for (i, (text, color)) in lines.iter().enumerate() {
+        let size = match (i, first_line_size_multiplier) {
+            (0, Some(multiplier)) => font_size * multiplier,
+            _ => font_size,
+        };
+        let segments = match max_width {
+            Some(max_width) => wrap_text(text, size, max_width),
+            None => vec![text.clone()],
+        };
+        rows.extend(segments.into_iter().map(|segment| (segment, color, i == 0)));
+    }
+    rows
+}
+
+/// Greedily break `text` after spaces and underscores into segments whose estimated width
+/// at `font_size` stays within `max_width`. Words wider than that keep a segment of their own.
+pub fn wrap_text(text: &str, font_size: f64, max_width: f64) -> Vec<String> {
+    let width = |text: &str| text.chars().map(glyph_advance).sum::<f64>() * font_size;
+    let mut words: Vec<&str> = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if c == ' ' || c == '_' {
+            words.push(&text[start..i + 1]);
+            start = i + 1;
+        }
+    }
+    if start < text.len() || words.is_empty() {
+        words.push(&text[start..]);
+    }
+
+    let mut segments: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in words {
+        if !current.is_empty() && width(current.trim_end()) + width(word.trim_end()) > max_width {
+            segments.push(current.trim_end().to_string());
+            current.clear();
+        }
+        current.push_str(word);
+    }
+    segments.push(current.trim_end().to_string());
+    segments
+}
+
+/// `dy` of row `i`: nothing for the first one, one line height (plus the extra height of a
+/// larger first line) for the others
+pub(crate) fn row_advance(rows: &[TextRow], i: usize, font_size: f64, line_height: f64, first_line_size_multiplier: Option<f64>) -> f64 {
+    match (i.checked_sub(1).map(|prev| rows[prev].2), first_line_size_multiplier) {
+        (None, _) => 0.0,
+        (Some(true), Some(multiplier)) => line_height + font_size * (multiplier - 1.0),
+        _ => line_height,
+    }
+}
+
+/// Height of `rows` from the top of the first row to the last baseline
+pub(crate) fn text_rows_height(rows: &[TextRow], font_size: f64, line_height: f64, first_line_size_multiplier: Option<f64>) -> f64 {
+    let first_size = font_size * first_line_size_multiplier.unwrap_or(1.0);
+    first_size + (0..rows.len()).map(|i| row_advance(rows, i, font_size, line_height, first_line_size_multiplier)).sum::<f64>()
+}
+
+/// Split a pre-formatted label into lines on `\n` and Mermaid-style `<br/>` (also `<br>`, `<br />`)
+pub(crate) fn split_label_lines(label: &str) -> Vec<String> {
+    label
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .lines()
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+/// Result of `check_label_fit`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelFit {
+    /// Ids of nodes whose text would overflow their box
+    pub overflowing: Vec<String>,
+    /// Canvas size once every box is scaled up enough to hold its text
+    pub canvas: (f64, f64),
+}
+
+impl LabelFit {
+    pub fn all_fit(&self) -> bool {
+        self.overflowing.is_empty()
+    }
+}
+
+/// Pre-flight check whether node texts would fit their boxes when rendered at `font_size`
+/// with `config`. Text extents are estimated from an average glyph width, as nothing is
+/// rendered or measured. Uses `layout` for the canvas size. Boxes without a positive width
+/// and height always overflow.
+pub fn check_label_fit(
+    nodes: &[TechNode],
+    layout: &LayoutResult,
+    font_size: f64,
+    config: &RenderConfig,
+) -> LabelFit {
+    let titles = disambiguated_titles(nodes, config.duplicate_names);
+    let mut overflowing = Vec::new();
+    let mut growth: f64 = 1.0;
+
+    for node in nodes {
+        let (needed_width, needed_height) =
+            needed_node_size(node, titles.get(&node.id).cloned(), font_size, config);
+        // No scale factor makes an empty box hold its text, so it doesn't grow the canvas
+        if node.width <= 0.0 || node.height <= 0.0 {
+            overflowing.push(node.id.clone());
+            continue;
+        }
+        if needed_width > node.width || needed_height > node.height {
+            overflowing.push(node.id.clone());
+            growth = growth.max(needed_width / node.width).max(needed_height / node.height);
+        }
+    }
+
+    let (min_x, min_y, max_x, max_y) =
+        layout_bounds(&place_nodes(nodes, layout)).unwrap_or((0.0, 0.0, 0.0, 0.0));
+    let canvas = (
+        (max_x - min_x) * growth + 2.0 * SVG_MARGIN,
+        (max_y - min_y) * growth + 2.0 * SVG_MARGIN,
+    );
+
+    LabelFit { overflowing, canvas }
+}
+
+/// Advance of `c` in Arial relative to the font size, rounded up so that fallback fonts and
+/// bold titles still fit
+pub(crate) fn glyph_advance(c: char) -> f64 {
+    match c {
+        'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '\'' | '|' | '!' | ' ' => 0.3,
+        'M' | 'W' | 'm' | 'w' => 0.95,
+        'A'..='Z' | '_' | '%' | '&' => 0.75,
+        'a'..='z' | '0'..='9' => 0.6,
+        _ => 0.7,
+    }
+}
+
+/// Estimated box size (width, height) needed to hold the text of `node` at `font_size`
+pub(crate) fn needed_node_size(node: &TechNode, title: Option<String>, font_size: f64, config: &RenderConfig) -> (f64, f64) {
+    // Node text metrics at the default font size of 10 (see `generate_node_svg`)
+    let scale = font_size / 10.0;
+    let (side_padding, top_offset, line_height) = (16.0 * scale, 30.0 * scale, 20.0 * scale);
+    let bottom_padding = line_height / 2.0;
+
+    let style = NodeStyle { title, ..NodeStyle::default() };
+    let title_multiplier = title_multiplier(node, config);
+    let multiplier = title_multiplier.unwrap_or(1.0);
+    let lines = node_text_lines(node, &style, &config.theme);
+    let icon_column = if node_icon_href(node).is_some() { ICON_SIZE + ICON_PADDING } else { 0.0 };
+
+    // Wrapped text keeps the width of the box and needs more height instead
+    let max_text_width = config.wrap_node_text.then_some(node.width - 2.0 * side_padding - icon_column);
+    let rows = wrap_text_rows(&lines, font_size, title_multiplier, max_text_width);
+    let needed_height = top_offset - font_size * multiplier
+        + text_rows_height(&rows, font_size, line_height, title_multiplier)
+        + bottom_padding;
+    if max_text_width.is_some() {
+        return (node.width, needed_height);
+    }
+
+    let text_width = lines
+        .iter()
+        .enumerate()
+        .map(|(i, (text, _))| {
+            let size = if i == 0 { font_size * multiplier } else { font_size };
+            text.chars().map(glyph_advance).sum::<f64>() * size
+        })
+        .fold(0.0, f64::max);
+    let needed_width = text_width + 2.0 * side_padding + icon_column;
+
+    (needed_width, needed_height)
+}
+
+/// Copies of `nodes` with boxes as large as the text `output_svg` draws into them with
+/// `config`, to be passed to `build_supply_chain_layout` so the spacing fits the real boxes.
+/// Keeps the sizes when `config.fixed_node_sizes` is set.
+pub fn fit_nodes_to_text(nodes: &[TechNode], config: &RenderConfig) -> Vec<TechNode> {
+    if config.fixed_node_sizes {
+        return nodes.to_vec();
+    }
+    let titles = disambiguated_titles(nodes, config.duplicate_names);
+    nodes
+        .iter()
+        .map(|node| {
+            let (width, height) = needed_node_size(node, titles.get(&node.id).cloned(), 10.0, config);
+            TechNode {
+                width: width.ceil(),
+                height: height.ceil(),
+                ..node.clone()
+            }
+        })
+        .collect()
+}
+
+/// Copies of `nodes` with their boxes scaled by `config.size_fn`, to be passed to
+/// `build_supply_chain_layout`. Boxes never shrink below what their text needs when
+/// rendered with `config`.
+pub fn apply_node_sizes(nodes: &[TechNode], config: &RenderConfig) -> Vec<TechNode> {
+    let Some(ref sizing) = config.size_fn else {
+        return nodes.to_vec();
+    };
+    let values: Vec<f64> = nodes.iter().map(sizing.size_fn).collect();
+    let (low, high) = values
+        .iter()
+        .filter(|value| value.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &value| (low.min(value), high.max(value)));
+
+    let titles = disambiguated_titles(nodes, config.duplicate_names);
+    nodes
+        .iter()
+        .zip(values)
+        .map(|(node, value)| {
+            // Linear in the value between the smallest and the largest one
+            let t = if high > low && value.is_finite() { (value - low) / (high - low) } else { 0.0 };
+            let factor = sizing.min_scale + t * (sizing.max_scale - sizing.min_scale);
+            let (needed_width, needed_height) = needed_node_size(node, titles.get(&node.id).cloned(), 10.0, config);
+            TechNode {
+                width: (node.width * factor).max(needed_width),
+                height: (node.height * factor).max(needed_height),
+                ..node.clone()
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,204 @@
+//! Color themes the drawing takes its fills, strokes and text colors from
+
+use std::collections::HashMap;
+use strum::{Display, EnumIter};
+
+use super::*;
+
+/// Every color used by `output_svg`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// Canvas fill; `None` leaves the SVG transparent
+    pub background: Option<ColorString>,
+    /// Page around the drawing in `output_html`
+    pub page_background: ColorString,
+    /// Node fills keyed by `waypoint_type`, `node_fill` for all other types
+    pub node_fills: HashMap<String, ColorString>,
+    pub node_fill: ColorString,
+    /// First line of a node and the remaining ones
+    pub title_text: ColorString,
+    pub text: ColorString,
+    pub edge: ColorString,
+    pub label_background: ColorString,
+    pub label_border: ColorString,
+    pub label_text: ColorString,
+    pub supply: HashMap<SupplyLevel, ColorString>,
+    pub activity: HashMap<ActivityLevel, ColorString>,
+    /// Rank bands and guides
+    pub ranks: ColorString,
+    /// Node fills of the shallowest and the deepest nodes with `RenderConfig::depth_coloring`
+    pub depth_ramp: ((u8, u8, u8), (u8, u8, u8)),
+    pub scale_bar: ColorString,
+    /// Edge label profits, see `RenderConfig::profit_neutral_band`
+    pub profit: ProfitColors,
+    /// Fills of the badges `supply_badges` puts on nodes of these supply levels
+    pub badges: HashMap<SupplyLevel, ColorString>,
+    pub badge_outline: ColorString,
+}
+
+/// Built-in themes, see `Theme::named`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, EnumIter, clap::ValueEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    Print,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn named(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Self::dark(),
+            ThemeName::Light => Self::light(),
+            ThemeName::Print => Self::print(),
+        }
+    }
+
+    /// Light text on dark blue boxes
+    pub fn dark() -> Self {
+        Self {
+            background: None,
+            page_background: ColorString::from("#1a1a1a"),
+            node_fills: node_fills([
+                ("RAW_MATERIAL", "#091c26"),
+                ("REFINED", "#0a2533"),
+                ("INDUSTRIAL", "#0c3040"),
+                ("ADVANCED", "#0e3a4d"),
+                ("CONSUMER", "#10425a"),
+            ]),
+            node_fill: ColorString::from("#000000"),
+            title_text: ColorString::from("#FFFFFF"),
+            text: ColorString::from("#CCCCCC"),
+            edge: ColorString::from("gray"),
+            label_background: ColorString::from("#666"),
+            label_border: ColorString::from("gray"),
+            label_text: ColorString::from("#eee"),
+            supply: level_colors([
+                (SupplyLevel::Abundant, "#22c55e"), // green-500
+                (SupplyLevel::High, "#86efac"),     // green-300
+                (SupplyLevel::Moderate, "#fde047"), // yellow-300
+                (SupplyLevel::Limited, "#f97316"),  // orange-500
+                (SupplyLevel::Scarce, "#ef4444"),   // red-500
+            ]),
+            activity: level_colors([
+                (ActivityLevel::Strong, "#22c55e"),     // green-500
+                (ActivityLevel::Growing, "#86efac"),    // green-300
+                (ActivityLevel::Weak, "#eab308"),       // yellow-500
+                (ActivityLevel::Restricted, "#ef4444"), // red-500
+            ]),
+            ranks: ColorString::from("#94a3b8"), // slate-400
+            depth_ramp: ((0x0b, 0x3b, 0x3a), (0x3b, 0x0b, 0x4a)),
+            scale_bar: ColorString::from("#666"),
+            profit: ProfitColors::new("#22c55e", "#eab308", "#ef4444"),
+            badges: level_colors([(SupplyLevel::Abundant, "#22c55e"), (SupplyLevel::Scarce, "#ef4444")]),
+            badge_outline: ColorString::from("#111"),
+        }
+    }
+
+    /// Dark text on pale boxes over a white canvas, for screens
+    pub fn light() -> Self {
+        Self {
+            background: Some(ColorString::from("#ffffff")),
+            page_background: ColorString::from("#ffffff"),
+            node_fills: node_fills([
+                ("RAW_MATERIAL", "#e0f2fe"),
+                ("REFINED", "#dbeafe"),
+                ("INDUSTRIAL", "#e0e7ff"),
+                ("ADVANCED", "#ede9fe"),
+                ("CONSUMER", "#fae8ff"),
+            ]),
+            node_fill: ColorString::from("#f1f5f9"),
+            title_text: ColorString::from("#0f172a"),
+            text: ColorString::from("#334155"),
+            edge: ColorString::from("#64748b"),
+            label_background: ColorString::from("#f8fafc"),
+            label_border: ColorString::from("#94a3b8"),
+            label_text: ColorString::from("#1e293b"),
+            supply: level_colors([
+                (SupplyLevel::Abundant, "#15803d"), // green-700
+                (SupplyLevel::High, "#16a34a"),     // green-600
+                (SupplyLevel::Moderate, "#ca8a04"), // yellow-600
+                (SupplyLevel::Limited, "#ea580c"),  // orange-600
+                (SupplyLevel::Scarce, "#dc2626"),   // red-600
+            ]),
+            activity: level_colors([
+                (ActivityLevel::Strong, "#15803d"),
+                (ActivityLevel::Growing, "#16a34a"),
+                (ActivityLevel::Weak, "#ca8a04"),
+                (ActivityLevel::Restricted, "#dc2626"),
+            ]),
+            ranks: ColorString::from("#94a3b8"),
+            depth_ramp: ((0xcc, 0xfb, 0xf1), (0xf3, 0xe8, 0xff)),
+            scale_bar: ColorString::from("#475569"),
+            profit: ProfitColors::new("#15803d", "#ca8a04", "#dc2626"),
+            badges: level_colors([(SupplyLevel::Abundant, "#15803d"), (SupplyLevel::Scarce, "#dc2626")]),
+            badge_outline: ColorString::from("#0f172a"),
+        }
+    }
+
+    /// Black on white with no tinted areas, levels in dark tones that survive grayscale printing
+    pub fn print() -> Self {
+        Self {
+            background: Some(ColorString::from("#ffffff")),
+            page_background: ColorString::from("#ffffff"),
+            node_fills: HashMap::new(),
+            node_fill: ColorString::from("#ffffff"),
+            title_text: ColorString::from("#000000"),
+            text: ColorString::from("#222222"),
+            edge: ColorString::from("#000000"),
+            label_background: ColorString::from("#ffffff"),
+            label_border: ColorString::from("#000000"),
+            label_text: ColorString::from("#000000"),
+            supply: level_colors([
+                (SupplyLevel::Abundant, "#14532d"), // green-900
+                (SupplyLevel::High, "#166534"),     // green-800
+                (SupplyLevel::Moderate, "#854d0e"), // yellow-800
+                (SupplyLevel::Limited, "#9a3412"),  // orange-800
+                (SupplyLevel::Scarce, "#991b1b"),   // red-800
+            ]),
+            activity: level_colors([
+                (ActivityLevel::Strong, "#14532d"),
+                (ActivityLevel::Growing, "#166534"),
+                (ActivityLevel::Weak, "#854d0e"),
+                (ActivityLevel::Restricted, "#991b1b"),
+            ]),
+            ranks: ColorString::from("#999999"),
+            depth_ramp: ((0xff, 0xff, 0xff), (0xc8, 0xc8, 0xc8)),
+            scale_bar: ColorString::from("#000000"),
+            profit: ProfitColors::new("#14532d", "#854d0e", "#991b1b"),
+            badges: level_colors([(SupplyLevel::Abundant, "#14532d"), (SupplyLevel::Scarce, "#991b1b")]),
+            badge_outline: ColorString::from("#000000"),
+        }
+    }
+
+    pub fn node_fill(&self, waypoint_type: &str) -> ColorString {
+        self.node_fills.get(waypoint_type).unwrap_or(&self.node_fill).clone()
+    }
+
+    /// Levels missing from a custom palette fall back to the plain text color
+    pub fn supply_color(&self, supply: &SupplyLevel) -> ColorString {
+        self.supply.get(supply).unwrap_or(&self.text).clone()
+    }
+
+    pub fn activity_color(&self, activity: &ActivityLevel) -> ColorString {
+        self.activity.get(activity).unwrap_or(&self.text).clone()
+    }
+}
+
+pub(crate) fn node_fills<const N: usize>(fills: [(&str, &'static str); N]) -> HashMap<String, ColorString> {
+    fills
+        .into_iter()
+        .map(|(waypoint_type, color)| (waypoint_type.to_string(), ColorString::from(color)))
+        .collect()
+}
+
+pub(crate) fn level_colors<L: Eq + std::hash::Hash, const N: usize>(colors: [(L, &'static str); N]) -> HashMap<L, ColorString> {
+    colors.into_iter().map(|(level, color)| (level, ColorString::from(color))).collect()
+}
@@ -0,0 +1,169 @@
+//! Errors for options and graphs a layout can not be computed for, and the rank constraint checks
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::*;
+
+/// Errors reported by `build_supply_chain_layout`
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayoutError {
+    /// A node's width or height is zero, negative, not finite or above `MAX_NODE_EXTENT`
+    InvalidNodeSize { id: String, width: f64, height: f64 },
+    /// `x_scale` or `y_scale` is zero, negative, not finite or above `MAX_SCALE`
+    InvalidScale { axis: &'static str, scale: f64 },
+    /// `LayoutOptions::vertex_spacing` is zero or above `MAX_VERTEX_SPACING`
+    InvalidVertexSpacing(usize),
+    /// `LayoutOptions::minimum_length` is zero or above `MAX_MINIMUM_LENGTH`
+    InvalidMinimumLength(u32),
+    /// The finished layout is wider or taller than `MAX_CANVAS_EXTENT`
+    CanvasTooLarge { width: f64, height: f64 },
+    /// `LayoutOptions::rank_constraints` ranks `earlier` before `later`, but edges lead from
+    /// `later` to `earlier` (or the engine's cycle breaking left them out of order)
+    RankConstraintViolated { earlier: String, later: String },
+    /// `LayoutOptions::same_rank` puts `source` and `target` on one rank, but edges lead from
+    /// `source` to `target`
+    SameRankContradicted { source: String, target: String },
+    /// A collapsed `NodeGroup` has the id of a node
+    GroupIdTaken(String),
+}
+
+/// Sanity bounds of the layout input; anything beyond is almost certainly a typo
+pub const MAX_NODE_EXTENT: f64 = 10_000.0;
+pub const MAX_SCALE: f64 = 100.0;
+pub const MAX_VERTEX_SPACING: usize = 10_000;
+pub const MAX_MINIMUM_LENGTH: u32 = 100;
+pub const MAX_CANVAS_EXTENT: f64 = 1_000_000.0;
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::InvalidNodeSize { id, width, height } => write!(
+                f,
+                "node '{}' has an invalid size {}x{} (width and height must be positive, finite and at most {})",
+                id, width, height, MAX_NODE_EXTENT
+            ),
+            LayoutError::InvalidScale { axis, scale } => write!(
+                f,
+                "invalid {}_scale {} (must be positive, finite and at most {})",
+                axis, scale, MAX_SCALE
+            ),
+            LayoutError::InvalidVertexSpacing(spacing) => write!(
+                f,
+                "invalid vertex spacing {} (must be between 1 and {})",
+                spacing, MAX_VERTEX_SPACING
+            ),
+            LayoutError::InvalidMinimumLength(length) => write!(
+                f,
+                "invalid minimum edge length {} (must be between 1 and {})",
+                length, MAX_MINIMUM_LENGTH
+            ),
+            LayoutError::CanvasTooLarge { width, height } => write!(
+                f,
+                "layout would be {}x{} pixels (at most {} per side)",
+                width, height, MAX_CANVAS_EXTENT
+            ),
+            LayoutError::RankConstraintViolated { earlier, later } => write!(
+                f,
+                "the category order ranks node '{}' before node '{}', which the edges contradict",
+                earlier, later
+            ),
+            LayoutError::SameRankContradicted { source, target } => write!(
+                f,
+                "nodes '{}' and '{}' must share a rank, but edges lead from one to the other",
+                source, target
+            ),
+            LayoutError::GroupIdTaken(id) => write!(f, "group id '{}' is already the id of a node", id),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Fail if a node of a listed category leads, through any path of edges, to a node of a
+/// category that must come before it. Such a path would have to run against the rank axis.
+pub fn check_rank_constraints(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    constraints: &RankConstraints,
+) -> Result<(), LayoutError> {
+    let positions: HashMap<&str, usize> = nodes
+        .iter()
+        .filter_map(|node| constraints.position(node).map(|position| (node.id.as_str(), position)))
+        .collect();
+    for node in nodes {
+        let Some(&position) = positions.get(node.id.as_str()) else {
+            continue;
+        };
+        let mut earlier: Vec<String> = descendants(nodes, edges, &node.id)
+            .into_iter()
+            .filter(|id| positions.get(id.as_str()).is_some_and(|&other| other < position))
+            .collect();
+        // Report the same node on every run
+        earlier.sort();
+        if let Some(earlier) = earlier.into_iter().next() {
+            return Err(LayoutError::RankConstraintViolated { earlier, later: node.id.clone() });
+        }
+    }
+    Ok(())
+}
+
+/// Fail if the engine ranked a node of a listed category at or before a node of an earlier one
+pub fn check_constrained_ranks(nodes: &[PlacedNode], constraints: &RankConstraints) -> Result<(), LayoutError> {
+    let ranked: Vec<(usize, &PlacedNode, usize)> = nodes
+        .iter()
+        .filter_map(|node| Some((constraints.position(node)?, node, node.rank?)))
+        .collect();
+    for &(position, node, rank) in &ranked {
+        if let Some(&(_, later, _)) = ranked
+            .iter()
+            .find(|&&(other_position, _, other_rank)| other_position > position && other_rank <= rank)
+        {
+            return Err(LayoutError::RankConstraintViolated {
+                earlier: node.id.clone(),
+                later: later.id.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Index of the same rank group every grouped node belongs to. Ids of unknown nodes and groups
+/// with fewer than two known members are ignored.
+pub(crate) fn same_rank_groups(nodes: &[TechNode], groups: &[Vec<String>]) -> HashMap<String, usize> {
+    let known: HashSet<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+    let mut membership: HashMap<String, usize> = HashMap::new();
+    for (group, ids) in groups.iter().enumerate() {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let members: Vec<&String> = ids
+            .iter()
+            .filter(|id| known.contains(id.as_str()) && !membership.contains_key(*id) && seen.insert(id.as_str()))
+            .collect();
+        if members.len() > 1 {
+            membership.extend(members.into_iter().map(|id| (id.clone(), group)));
+        }
+    }
+    membership
+}
+
+/// Same rank groups can't be honored when edges lead from one member to another
+pub fn check_same_rank_groups(
+    nodes: &[TechNode],
+    edges: &[TechEdge],
+    membership: &HashMap<String, usize>,
+) -> Result<(), LayoutError> {
+    // Report the same pair on every run
+    let mut members: Vec<(&String, &usize)> = membership.iter().collect();
+    members.sort();
+    for (id, group) in members {
+        let mut reached: Vec<String> = descendants(nodes, edges, id)
+            .into_iter()
+            .filter(|other| other != id && membership.get(other) == Some(group))
+            .collect();
+        reached.sort();
+        if let Some(target) = reached.into_iter().next() {
+            return Err(LayoutError::SameRankContradicted { source: id.clone(), target });
+        }
+    }
+    Ok(())
+}